@@ -6,11 +6,14 @@
 use std::env;
 use std::path::Path;
 
+#[allow(clippy::unusual_byte_groupings)]
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(has_dev_sev)");
     if cfg!(feature = "hw_tests") || Path::new("/dev/sev").exists() {
         println!("cargo:rustc-cfg=has_dev_sev");
     }
 
+    println!("cargo:rustc-check-cfg=cfg(has_dev_csv_guest)");
     if cfg!(feature = "hw_tests") || Path::new("/dev/csv-guest").exists() {
         println!("cargo:rustc-cfg=has_dev_csv_guest");
     }
@@ -19,6 +22,9 @@ fn main() {
         println!("cargo:rustc-cfg=has_dev_dcu");
     }
 
+    println!("cargo:rustc-check-cfg=cfg(has_sev)");
+    println!("cargo:rustc-check-cfg=cfg(ossl111)");
+    println!("cargo:rustc-check-cfg=cfg(ossl300)");
     if let Ok(version) = env::var("DEP_OPENSSL_VERSION_NUMBER") {
         let version = u64::from_str_radix(&version, 16).unwrap();
         match version {
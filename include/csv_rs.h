@@ -0,0 +1,77 @@
+/*
+ * Copyright (C) Hygon Info Technologies Ltd.
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+/* C bindings of csv-rs, generated with `cbindgen --config cbindgen.toml`. */
+
+#ifndef CSV_RS_H
+#define CSV_RS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/* The size in bytes of a report page. */
+#define REPORT_RSP_LEN 4096
+
+/* The call succeeded. */
+#define CSV_OK 0
+
+/* A required pointer is null. */
+#define CSV_ERR_NULL_POINTER -1
+
+/* The report page can't be decoded. */
+#define CSV_ERR_INVALID_REPORT -2
+
+/* The certificate chain can't be decoded. */
+#define CSV_ERR_INVALID_CHAIN -3
+
+/* The report doesn't carry the requested mnonce. */
+#define CSV_ERR_MNONCE_MISMATCH -4
+
+/* The report, its signer evidence or the certificate chain doesn't verify. */
+#define CSV_ERR_VERIFICATION -5
+
+/* The CSV guest device failed to produce a report. */
+#define CSV_ERR_DEVICE -6
+
+/* The library panicked, the call didn't complete. */
+#define CSV_ERR_PANIC -7
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Verifies a report page as written by the firmware.
+ *
+ * `page` holds the `REPORT_RSP_LEN` bytes of the report and its signer
+ * evidence, `chain` the `chain_len` bytes of the HSK and CEK as served by
+ * the KDS and `mnonce` the 16 bytes the report was requested with.
+ *
+ * Returns `CSV_OK` if the report verifies up to the builtin HRK, and
+ * `CSV_ERR_PANIC` rather than unwinding if the library panics.
+ */
+int csv_verify_report(const uint8_t *page,
+                      const uint8_t *chain,
+                      size_t chain_len,
+                      const uint8_t *mnonce);
+
+/*
+ * Requests a report page from the CSV guest device.
+ *
+ * `data` holds the 64 bytes to include in the report and `mnonce` the 16
+ * bytes to request it with, either can be null to use zeroes and a random
+ * mnonce respectively. The `REPORT_RSP_LEN` bytes of the page are written
+ * to `out` as the firmware wrote them, ready for `csv_verify_report`.
+ * Without the `rand` feature, a null `mnonce` fails with
+ * `CSV_ERR_NULL_POINTER`. A panic of the library returns `CSV_ERR_PANIC`.
+ */
+int csv_get_report(const uint8_t *data, const uint8_t *mnonce, uint8_t *out);
+
+#ifdef __cplusplus
+} /* extern "C" */
+#endif
+
+#endif /* CSV_RS_H */
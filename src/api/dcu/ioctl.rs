@@ -3,7 +3,8 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use super::types::{AttestationReport, AttestationResponse};
+use super::types::{AttestationReport, AttestationResponse, SecurityAttestationRequestHeader};
+use crate::error::Error;
 use iocuddle::{Group, Ioctl, WriteRead};
 use libc::c_void;
 use log::*;
@@ -20,6 +21,10 @@ pub enum DcuDeviceIoctl {
     _Undefined,
 }
 
+/// The attestation message versions this crate can parse and verify the
+/// reports of
+pub const SUPPORTED_VERSIONS: &[u32] = &[1];
+
 /// IOCTL group identifier for DCU device ('M' magic number)
 const DCU: Group = Group::new(b'M');
 
@@ -34,7 +39,7 @@ pub const DCU_GET_REPORT: Ioctl<WriteRead, &MkfdIoctlSecurityAttestationArgs> =
 pub struct MkfdIoctlSecurityAttestationArgs {
     /// DCU identifier for target device
     pub dcu_id: u32,
-    pub cmd_id :u32,
+    pub cmd_id: u32,
     /// Message version number(default 1)
     pub version: u32,
     /// Request structure address
@@ -55,7 +60,7 @@ impl MkfdIoctlSecurityAttestationArgs {
     pub fn new() -> Self {
         Self {
             dcu_id: 0,
-            ///attestation command
+            // attestation command
             cmd_id: 0,
             version: 1,
             request_data: std::ptr::null_mut(),
@@ -66,6 +71,19 @@ impl MkfdIoctlSecurityAttestationArgs {
         }
     }
 
+    /// Sets the attestation message version to request the report with
+    ///
+    /// Fails with `Error::UnsupportedVersion` if `version` isn't one of
+    /// [`SUPPORTED_VERSIONS`], before any request reaches the firmware.
+    pub fn set_version(&mut self, version: u32) -> Result<(), Error> {
+        if !SUPPORTED_VERSIONS.contains(&version) {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        self.version = version;
+        Ok(())
+    }
+
     /// Configures attestation arguments with DCU ID and nonce
     ///
     /// # Safety
@@ -75,22 +93,53 @@ impl MkfdIoctlSecurityAttestationArgs {
     /// * `dcu_id` - Target DCU device identifier
     /// * `userdata` - 64-byte cryptographic nonce for attestation
     pub fn set_attestation_args(&mut self, dcu_id: u32, userdata: [u8; 64]) -> std::io::Result<()> {
+        self.set_attestation_args_with(dcu_id, userdata, libc::malloc)
+    }
+
+    /// Configures attestation arguments, allocating the buffers with `alloc`
+    ///
+    /// Fails with `ErrorKind::OutOfMemory` if a buffer can't be allocated,
+    /// leaving no buffer allocated.
+    fn set_attestation_args_with(
+        &mut self,
+        dcu_id: u32,
+        userdata: [u8; 64],
+        alloc: unsafe extern "C" fn(usize) -> *mut c_void,
+    ) -> std::io::Result<()> {
+        self.allocate_buffers_with(dcu_id, userdata, PAGE_SIZE, PAGE_SIZE, alloc)
+    }
+
+    /// Configures attestation arguments with buffers of `request_size` and
+    /// `response_size` bytes, allocated with `alloc`
+    ///
+    /// Fails with `ErrorKind::OutOfMemory` if a buffer can't be allocated,
+    /// leaving no buffer allocated.
+    fn allocate_buffers_with(
+        &mut self,
+        dcu_id: u32,
+        userdata: [u8; 64],
+        request_size: usize,
+        response_size: usize,
+        alloc: unsafe extern "C" fn(usize) -> *mut c_void,
+    ) -> std::io::Result<()> {
         unsafe {
             // Allocate page-aligned request buffer
-            self.request_data = libc::malloc(PAGE_SIZE);
+            self.request_data = alloc(request_size);
             if self.request_data.is_null() {
-                return Err(std::io::Error::last_os_error());
+                return Err(io::Error::new(
+                    io::ErrorKind::OutOfMemory,
+                    "Failed to allocate the DCU request buffer",
+                ));
             }
 
             // Initialize request buffer with zeros
-            libc::memset(self.request_data, 0, PAGE_SIZE);
-            self.request_size = PAGE_SIZE as u64;
-
-            // Copy nonce into request buffer
-            std::ptr::copy_nonoverlapping(
-                userdata.as_ptr(),
-                self.request_data as *mut u8,
-                userdata.len(),
+            libc::memset(self.request_data, 0, request_size);
+            self.request_size = request_size as u64;
+
+            // Write the request header at the start of the request buffer
+            std::ptr::write(
+                self.request_data as *mut SecurityAttestationRequestHeader,
+                SecurityAttestationRequestHeader::new(userdata),
             );
 
             // Debug output: hex dump of nonce in request buffer
@@ -98,16 +147,21 @@ impl MkfdIoctlSecurityAttestationArgs {
             hex_dump(self.request_data as *const u8, 64);
 
             // Allocate page-aligned response buffer
-            self.response_data = libc::malloc(PAGE_SIZE);
+            self.response_data = alloc(response_size);
             if self.response_data.is_null() {
-                // Cleanup request buffer on allocation failure
-                libc::free(self.request_data);
-                return Err(std::io::Error::last_os_error());
+                // Cleanup request buffer on allocation failure, so that
+                // neither the caller nor `Drop` sees it again
+                self.free_buffers();
+                self.request_size = 0;
+                return Err(io::Error::new(
+                    io::ErrorKind::OutOfMemory,
+                    "Failed to allocate the DCU response buffer",
+                ));
             }
 
             // Initialize response buffer with zeros
-            libc::memset(self.response_data, 0, PAGE_SIZE);
-            self.response_size = PAGE_SIZE as u64;
+            libc::memset(self.response_data, 0, response_size);
+            self.response_size = response_size as u64;
 
             // Set target DCU identifier
             self.dcu_id = dcu_id;
@@ -135,8 +189,23 @@ impl MkfdIoctlSecurityAttestationArgs {
     ///
     /// Returns:
     /// - Some(AttestationReport) if valid report exists
-    /// - None if response buffer contains no valid report
-    pub fn extract_report(&mut self) -> Result<Option<AttestationReport>, io::Error> {
+    /// - None if no response buffer is allocated
+    /// - `Error::EmptyResponse` if the firmware left the response buffer
+    ///   zero, which would otherwise decode to a zero report
+    pub fn extract_report(&mut self) -> Result<Option<AttestationReport>, Error> {
+        if self.response_data.is_null() {
+            return Ok(None);
+        }
+
+        // SAFETY: the response buffer is allocated with `response_size`
+        // bytes, see `allocate_buffers_with`.
+        let response = unsafe {
+            std::slice::from_raw_parts(self.response_data as *const u8, self.response_size as usize)
+        };
+        if response.iter().all(|&b| b == 0) {
+            return Err(Error::EmptyResponse);
+        }
+
         unsafe {
             if let Some(response) = AttestationResponse::from_raw(self.response_data) {
                 return Ok(Some(response.report.clone()));
@@ -152,6 +221,98 @@ impl Default for MkfdIoctlSecurityAttestationArgs {
     }
 }
 
+/// Builds [`MkfdIoctlSecurityAttestationArgs`] with validated fields and
+/// request and response buffers owned by the arguments
+///
+/// The buffers default to a page each, the message version to 1.
+///
+/// ```no_run
+/// use csv_rs::api::dcu::SecurityAttestationArgsBuilder;
+///
+/// let args = SecurityAttestationArgsBuilder::new(6510)
+///     .userdata([0u8; 64])
+///     .build()
+///     .unwrap();
+/// assert_eq!(args.request_size, 4096);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SecurityAttestationArgsBuilder {
+    dcu_id: u32,
+    version: u32,
+    userdata: [u8; 64],
+    request_size: usize,
+    response_size: usize,
+}
+
+impl SecurityAttestationArgsBuilder {
+    /// Starts arguments requesting the report of the DCU `dcu_id`
+    pub fn new(dcu_id: u32) -> Self {
+        Self {
+            dcu_id,
+            version: 1,
+            userdata: [0u8; 64],
+            request_size: PAGE_SIZE,
+            response_size: PAGE_SIZE,
+        }
+    }
+
+    /// Sets the message version, one of [`SUPPORTED_VERSIONS`]
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the user data to be echoed in the report
+    pub fn userdata(mut self, userdata: [u8; 64]) -> Self {
+        self.userdata = userdata;
+        self
+    }
+
+    /// Sets the size of the request buffer, at least a page
+    pub fn request_size(mut self, size: usize) -> Self {
+        self.request_size = size;
+        self
+    }
+
+    /// Sets the size of the response buffer, at least a page
+    pub fn response_size(mut self, size: usize) -> Self {
+        self.response_size = size;
+        self
+    }
+
+    /// Validates the fields and allocates the buffers
+    ///
+    /// Fails with:
+    /// - `Error::UnsupportedVersion` for a version not in [`SUPPORTED_VERSIONS`]
+    /// - `Error::InvalidLen` for a buffer smaller than a page
+    /// - `Error::IoError` of `ErrorKind::OutOfMemory` if a buffer can't be
+    ///   allocated
+    pub fn build(self) -> Result<MkfdIoctlSecurityAttestationArgs, Error> {
+        self.build_with(libc::malloc)
+    }
+
+    fn build_with(
+        self,
+        alloc: unsafe extern "C" fn(usize) -> *mut c_void,
+    ) -> Result<MkfdIoctlSecurityAttestationArgs, Error> {
+        if self.request_size < PAGE_SIZE || self.response_size < PAGE_SIZE {
+            return Err(Error::InvalidLen);
+        }
+
+        let mut args = MkfdIoctlSecurityAttestationArgs::new();
+        args.set_version(self.version)?;
+        args.fw_err = 0;
+        args.allocate_buffers_with(
+            self.dcu_id,
+            self.userdata,
+            self.request_size,
+            self.response_size,
+            alloc,
+        )?;
+        Ok(args)
+    }
+}
+
 /// Automatic cleanup implementation to prevent memory leaks
 impl Drop for MkfdIoctlSecurityAttestationArgs {
     fn drop(&mut self) {
@@ -178,3 +339,143 @@ fn hex_dump(data: *const u8, len: usize) {
 
     trace!("Memory dump:\n{}", output);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    thread_local!(static CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) });
+
+    /// An allocator allocating the request buffer, then running out of
+    /// memory. Its calls are counted from each call of `alloc_once`, as tests
+    /// may run one after the other on the same thread.
+    fn alloc_once() -> unsafe extern "C" fn(usize) -> *mut c_void {
+        unsafe extern "C" fn alloc(size: usize) -> *mut c_void {
+            if CALLS.with(|calls| calls.replace(calls.get() + 1)) == 0 {
+                libc::malloc(size)
+            } else {
+                std::ptr::null_mut()
+            }
+        }
+
+        CALLS.with(|calls| calls.set(0));
+        alloc
+    }
+
+    #[test]
+    fn set_version() {
+        let mut args = MkfdIoctlSecurityAttestationArgs::new();
+        assert!(SUPPORTED_VERSIONS.contains(&args.version));
+
+        for &version in SUPPORTED_VERSIONS {
+            args.set_version(version).unwrap();
+            assert_eq!(args.version, version);
+        }
+
+        match args.set_version(0) {
+            Err(Error::UnsupportedVersion(0)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(args.version, *SUPPORTED_VERSIONS.last().unwrap());
+    }
+
+    #[test]
+    fn builder_defaults() {
+        let args = SecurityAttestationArgsBuilder::new(6510)
+            .userdata([0x5au8; 64])
+            .build()
+            .unwrap();
+        assert_eq!(args.dcu_id, 6510);
+        assert_eq!(args.version, 1);
+        assert_eq!(args.fw_err, 0);
+        assert_eq!(args.request_size, PAGE_SIZE as u64);
+        assert_eq!(args.response_size, PAGE_SIZE as u64);
+
+        let page = unsafe { std::slice::from_raw_parts(args.request_data as *const u8, PAGE_SIZE) };
+        assert_eq!(page[..64], [0x5au8; 64]);
+        assert!(page[64..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn builder_rejects_small_buffers() {
+        let builder = SecurityAttestationArgsBuilder::new(6510);
+
+        for args in [
+            builder.clone().request_size(PAGE_SIZE - 1).build(),
+            builder.clone().response_size(64).build(),
+        ] {
+            assert!(matches!(args, Err(Error::InvalidLen)));
+        }
+
+        let args = builder.request_size(2 * PAGE_SIZE).build().unwrap();
+        assert_eq!(args.request_size, 2 * PAGE_SIZE as u64);
+    }
+
+    #[test]
+    fn builder_rejects_unsupported_version() {
+        let args = SecurityAttestationArgsBuilder::new(6510).version(0).build();
+        assert!(matches!(args, Err(Error::UnsupportedVersion(0))));
+    }
+
+    #[test]
+    fn builder_out_of_memory() {
+        let args = SecurityAttestationArgsBuilder::new(6510).build_with(alloc_once());
+        match args {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::OutOfMemory),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    /// Zeroes every buffer, as a firmware writing no response leaves it.
+    unsafe extern "C" fn alloc_zeroed(size: usize) -> *mut c_void {
+        libc::calloc(1, size)
+    }
+
+    #[test]
+    fn extract_empty_response() {
+        let mut args = SecurityAttestationArgsBuilder::new(6510)
+            .build_with(alloc_zeroed)
+            .unwrap();
+        assert!(matches!(args.extract_report(), Err(Error::EmptyResponse)));
+
+        // A response the firmware wrote decodes.
+        let response = unsafe { AttestationResponse::from_raw(args.response_data) }.unwrap();
+        response.report_size = std::mem::size_of::<AttestationReport>() as u32;
+        response.report.body.measure = [0xaa; 32];
+        let report = args.extract_report().unwrap().unwrap();
+        assert_eq!(report.body.measure, [0xaa; 32]);
+
+        unsafe { args.free_buffers() };
+        assert!(args.extract_report().unwrap().is_none());
+    }
+
+    #[test]
+    fn request_layout() {
+        let mut userdata = [0u8; 64];
+        for (i, b) in userdata.iter_mut().enumerate() {
+            *b = i as u8 + 1;
+        }
+
+        let mut args = MkfdIoctlSecurityAttestationArgs::new();
+        args.set_attestation_args(6510, userdata).unwrap();
+        assert_eq!(args.dcu_id, 6510);
+        assert_eq!(args.request_size, PAGE_SIZE as u64);
+
+        let page = unsafe { std::slice::from_raw_parts(args.request_data as *const u8, PAGE_SIZE) };
+        assert_eq!(page[..64], userdata);
+        assert!(page[64..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn response_alloc_failure_frees_request() {
+        let mut args = MkfdIoctlSecurityAttestationArgs::new();
+
+        let err = args
+            .set_attestation_args_with(1, [0u8; 64], alloc_once())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+        assert!(args.request_data.is_null());
+        assert!(args.response_data.is_null());
+        assert_eq!(args.request_size, 0);
+    }
+}
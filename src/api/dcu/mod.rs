@@ -7,28 +7,95 @@ use crate::error::*;
 mod ioctl;
 pub use ioctl::*;
 mod types;
-use crate::certs::{builtin::HRK, ca, csv, Verifiable};
-use codicon::Decoder;
+#[cfg(feature = "network")]
+use crate::certs::csv;
+use crate::certs::{kds, Verifiable};
 use log::*;
+use static_assertions::assert_impl_all;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self};
-use std::path::Path;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 pub use types::*;
 
+/// The most threads [`DcuDevice::get_reports_parallel`] requests reports on.
+pub const MAX_PARALLEL_REQUESTS: usize = 8;
+
+/// The outcome of requesting the report of one DCU.
+pub type ReportOutcome = Result<AttestationReport, Error>;
+
+/// The sysfs directory of the KFD topology nodes.
+pub const DEFAULT_TOPOLOGY_PATH: &str = "/sys/devices/virtual/kfd/kfd/topology/nodes";
+
+/// The other locations kernels expose the KFD topology nodes at.
+const FALLBACK_TOPOLOGY_PATHS: &[&str] = &["/sys/class/kfd/kfd/topology/nodes"];
+
 /// Reads the DCU ID from the sysfs topology node.
-fn topology_sysfs_get_dcu_id(sysfs_node_id: u32) -> io::Result<u32> {
-    let path = format!(
-        "/sys/devices/virtual/kfd/kfd/topology/nodes/{}/gpu_id",
-        sysfs_node_id
-    );
-    fs::read_to_string(&path)?
-        .trim()
-        .parse::<u32>()
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to parse DCU ID"))
+///
+/// The ID is read from the node's `gpu_id` attribute, or from the `gpu_id`
+/// line of its `properties` on kernels without the attribute. Whitespace
+/// around the ID is ignored.
+///
+/// An attribute without an ID, as read while the node is being hot-plugged,
+/// fails with [`io::ErrorKind::WouldBlock`] so that the read can be retried.
+/// A node whose attributes the user may not read fails with
+/// [`io::ErrorKind::PermissionDenied`].
+fn topology_sysfs_get_dcu_id(node_dir: &Path) -> io::Result<u32> {
+    let parse = |value: &str| {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("No DCU ID yet in {}", node_dir.display()),
+            ));
+        }
+
+        value
+            .parse::<u32>()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to parse DCU ID"))
+    };
+
+    let mut denied = None;
+
+    let gpu_id = node_dir.join("gpu_id");
+    match fs::read_to_string(&gpu_id) {
+        Ok(value) => return parse(&value),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => denied = Some(&gpu_id),
+        Err(_) => {}
+    }
+
+    let properties = node_dir.join("properties");
+    match fs::read_to_string(&properties) {
+        Ok(value) => {
+            if let Some(id) = value.lines().find_map(|line| line.strip_prefix("gpu_id ")) {
+                return parse(id);
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => denied = Some(&properties),
+        Err(_) => {}
+    }
+
+    if let Some(path) = denied {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Cannot read the DCU ID in {}", path.display()),
+        ));
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "No DCU ID found, tried {} and {}",
+            gpu_id.display(),
+            properties.display()
+        ),
+    ))
 }
 
 /// Counts the number of subdirectories in a given directory with an optional prefix filter.
-fn num_subdirs(dirpath: &str, prefix: &str) -> usize {
+fn num_subdirs(dirpath: &Path, prefix: &str) -> usize {
     fs::read_dir(dirpath)
         .map(|entries| {
             entries
@@ -44,17 +111,293 @@ fn num_subdirs(dirpath: &str, prefix: &str) -> usize {
         .unwrap_or(0)
 }
 
+/// The sysfs topology the DCUs are discovered from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topology {
+    paths: Vec<PathBuf>,
+}
+
+impl Default for Topology {
+    /// Looks the nodes up at [`DEFAULT_TOPOLOGY_PATH`], then at the other
+    /// locations known kernels use.
+    fn default() -> Self {
+        Self {
+            paths: std::iter::once(DEFAULT_TOPOLOGY_PATH)
+                .chain(FALLBACK_TOPOLOGY_PATHS.iter().copied())
+                .map(PathBuf::from)
+                .collect(),
+        }
+    }
+}
+
+impl Topology {
+    /// Looks the nodes up in `path` only.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            paths: vec![path.into()],
+        }
+    }
+
+    /// Also looks the nodes up in `path` if none of the previous paths
+    /// exist.
+    pub fn with_fallback(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Lists the valid DCU IDs of the topology along with their node.
+    ///
+    /// The nodes are read from the first path that exists, it is an error
+    /// if none does. Fails with [`io::ErrorKind::WouldBlock`] if a node is
+    /// being hot-plugged and doesn't have its ID yet, the call can then be
+    /// retried. A node whose ID the user may not read is skipped with a
+    /// warning; if no DCU is left, the call fails with
+    /// [`io::ErrorKind::PermissionDenied`], converted to
+    /// [`Error::PermissionDenied`].
+    pub fn dcu_nodes(&self) -> io::Result<Vec<(usize, u32)>> {
+        let base = self
+            .paths
+            .iter()
+            .find(|path| path.is_dir())
+            .ok_or_else(|| {
+                let tried: Vec<_> = self.paths.iter().map(|p| p.display().to_string()).collect();
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No KFD topology found, tried {}", tried.join(", ")),
+                )
+            })?;
+
+        let num_node = num_subdirs(base, "");
+        collect_nodes((0..num_node).map(|node| {
+            trace!("Processing node {} of {}", node, num_node);
+            (
+                node,
+                topology_sysfs_get_dcu_id(&base.join(node.to_string())),
+            )
+        }))
+    }
+
+    /// Finds the node of the DCU whose ID is `gpu_id`.
+    ///
+    /// Returns [`Error::UnknownGpuId`] if no DCU of the topology has it.
+    pub fn node_of(&self, gpu_id: u32) -> Result<usize, Error> {
+        self.dcu_nodes()?
+            .into_iter()
+            .find(|&(_, dcu_id)| dcu_id == gpu_id)
+            .map(|(node, _)| node)
+            .ok_or(Error::UnknownGpuId(gpu_id))
+    }
+}
+
+/// Lists the valid DCU IDs among the IDs read from each node, see
+/// [`Topology::dcu_nodes`].
+fn collect_nodes(
+    ids: impl ExactSizeIterator<Item = (usize, io::Result<u32>)>,
+) -> io::Result<Vec<(usize, u32)>> {
+    let num_node = ids.len();
+    let mut nodes = Vec::with_capacity(num_node);
+    let mut denied = Vec::new();
+
+    for (node, dcu_id) in ids {
+        match dcu_id {
+            Ok(dcu_id) => {
+                trace!("Found DCU ID: {}", dcu_id);
+
+                // Skip invalid DCU IDs
+                if dcu_id != 0 {
+                    nodes.push((node, dcu_id));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Err(e),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                warn!("Skipping node {}: {}", node, e);
+                denied.push(node);
+            }
+            Err(e) => trace!("Node {} skipped: {}", node, e),
+        }
+    }
+
+    // The denied nodes may be the only DCUs, alongside CPU nodes whose ID
+    // of 0 is readable.
+    if nodes.is_empty() && !denied.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            Error::PermissionDenied { nodes: denied },
+        ));
+    }
+
+    Ok(nodes)
+}
+
+/// The path [`DcuDevice::new`] opens the DCU device at.
+pub const DEFAULT_MKFD_PATH: &str = "/dev/mkfd";
+
+/// How often [`DcuDevice::open_wait`] looks for the device.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The flags [`DcuDevice::new`] opens `/dev/mkfd` with.
+pub const DEFAULT_OPEN_FLAGS: i32 = libc::O_RDWR | libc::O_CLOEXEC;
+
+/// Checks that `path` is a character device.
+fn check_char_device(path: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(path).map_err(|e| {
+        io::Error::new(e.kind(), format!("Cannot access {}: {}", path.display(), e))
+    })?;
+
+    if !metadata.file_type().is_char_device() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a character device", path.display()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Opens the character device at `path` with the `open(2)` `flags`.
+///
+/// The standard library always opens with `O_CLOEXEC`, so it is cleared
+/// again afterwards when `flags` doesn't ask for it.
+fn open_device(path: &Path, flags: i32) -> io::Result<File> {
+    check_char_device(path)?;
+
+    let access = flags & libc::O_ACCMODE;
+    let file = OpenOptions::new()
+        .read(access == libc::O_RDONLY || access == libc::O_RDWR)
+        .write(access == libc::O_WRONLY || access == libc::O_RDWR)
+        .custom_flags(flags & !libc::O_ACCMODE)
+        .open(path)?;
+
+    if flags & libc::O_CLOEXEC == 0 {
+        let fd = file.as_raw_fd();
+        // SAFETY: `fd` is owned by `file` and stays open for both calls.
+        let fd_flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        if fd_flags < 0
+            || unsafe { libc::fcntl(fd, libc::F_SETFD, fd_flags & !libc::FD_CLOEXEC) } < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(file)
+}
+
 /// A handle to the dcu device.
-pub struct DcuDevice(File);
+///
+/// The handle only owns the device's file, its path and its topology, the
+/// ioctl buffers live for the duration of a request.
+///
+/// # Thread safety
+///
+/// With no buffer outliving a request, the handle is `Send` and `Sync`.
+/// Every request takes `&mut self` though, so the ioctls of two requests
+/// can't interleave on the device's file: threads sharing a handle
+/// serialize their requests, e.g. with a `Mutex`, or request in parallel
+/// on handles of their own, see [`Self::get_reports_parallel`].
+///
+/// ```no_run
+/// use csv_rs::api::dcu::DcuDevice;
+/// use std::sync::{Arc, Mutex};
+///
+/// let device = Arc::new(Mutex::new(DcuDevice::new().unwrap()));
+///
+/// let workers: Vec<_> = (0..4u8)
+///     .map(|i| {
+///         let device = device.clone();
+///         std::thread::spawn(move || device.lock().unwrap().get_report([i; 64]))
+///     })
+///     .collect();
+///
+/// for worker in workers {
+///     worker.join().unwrap().unwrap();
+/// }
+/// ```
+pub struct DcuDevice(File, Topology, PathBuf);
+
+assert_impl_all!(DcuDevice: Send, Sync);
+assert_impl_all!(AttestationReport: Send, Sync);
 
 impl DcuDevice {
     /// Opens a handle to the DCU device via `/dev/mkfd`.
-    pub fn new() -> io::Result<DcuDevice> {
-        OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open("/dev/mkfd")
-            .map(DcuDevice)
+    ///
+    /// The device is opened with [`DEFAULT_OPEN_FLAGS`], so the handle is
+    /// closed on `exec` and isn't leaked into spawned programs.
+    pub fn new() -> Result<DcuDevice, Error> {
+        Self::open_with_flags(DEFAULT_OPEN_FLAGS)
+    }
+
+    /// Opens a handle to the DCU device via `/dev/mkfd` with explicit
+    /// `open(2)` flags.
+    ///
+    /// The handle survives `fork` either way. Leaving `O_CLOEXEC` out of
+    /// `flags` additionally keeps it open across `exec`, for services that
+    /// hand the device over to worker programs.
+    pub fn open_with_flags(flags: i32) -> Result<DcuDevice, Error> {
+        Self::open_mkfd_with_flags(DEFAULT_MKFD_PATH, flags)
+    }
+
+    /// Opens a handle to the DCU device at `path` rather than `/dev/mkfd`,
+    /// e.g. where a container bind-mounts it.
+    ///
+    /// Fails if `path` isn't a character device.
+    pub fn open_mkfd(path: impl AsRef<Path>) -> Result<DcuDevice, Error> {
+        Self::open_mkfd_with_flags(path, DEFAULT_OPEN_FLAGS)
+    }
+
+    /// Opens a handle to the DCU device at `path` with explicit `open(2)`
+    /// flags, see [`Self::open_mkfd`] and [`Self::open_with_flags`].
+    ///
+    /// Returns [`Error::DeviceUnavailable`] if the device is missing, e.g.
+    /// without the `mkfd` kernel module, or not accessible.
+    pub fn open_mkfd_with_flags(path: impl AsRef<Path>, flags: i32) -> Result<DcuDevice, Error> {
+        let path = path.as_ref();
+        let file =
+            open_device(path, flags).map_err(|e| Error::device_unavailable(path, e, "mkfd"))?;
+        Ok(DcuDevice(file, Topology::default(), path.into()))
+    }
+
+    /// Opens a handle to the DCU device via `/dev/mkfd` once it and a DCU
+    /// of the default topology appear, e.g. while the `mkfd` kernel module
+    /// is loading at boot.
+    ///
+    /// Returns [`Error::Timeout`] if they haven't appeared within
+    /// `timeout`. Once they have, fails as [`Self::new`] does.
+    pub fn open_wait(timeout: Duration) -> Result<DcuDevice, Error> {
+        Self::open_wait_at(Path::new(DEFAULT_MKFD_PATH), Topology::default(), timeout)
+    }
+
+    fn open_wait_at(
+        path: &Path,
+        topology: Topology,
+        timeout: Duration,
+    ) -> Result<DcuDevice, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if path.exists() && topology.dcu_nodes().is_ok_and(|nodes| !nodes.is_empty()) {
+                return Ok(Self::open_mkfd(path)?.with_topology(topology));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Timeout(timeout));
+            }
+            std::thread::sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Discovers the DCUs from `topology` rather than the default one.
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.1 = topology;
+        self
+    }
+
+    /// The attestation message versions whose reports this crate can parse
+    /// and verify, see [`SUPPORTED_VERSIONS`]
+    ///
+    /// Callers can pick a version to request reports with from these, and
+    /// turn away unsupported firmware before any ioctl.
+    pub fn supported_versions(&self) -> Result<Vec<u32>, Error> {
+        Ok(SUPPORTED_VERSIONS.to_vec())
     }
 
     /// Get attestation reports from all available DCU nodes
@@ -67,54 +410,302 @@ impl DcuDevice {
     /// - `Err(Error)` if:
     ///   - No valid reports are obtained
     ///   - IOCTL operations fail
+    ///   - The firmware leaves a DCU's response empty, `Error::EmptyResponse`
     ///   - DCU node communication fails
     pub fn get_report(&mut self, userdata: [u8; 64]) -> Result<Vec<AttestationReport>, Error> {
         // Discover available DCU nodes
-        let num_node = num_subdirs("/sys/devices/virtual/kfd/kfd/topology/nodes", "");
-        let mut reports: Vec<AttestationReport> = Vec::with_capacity(num_node);
+        let nodes = self.1.dcu_nodes()?;
 
-        // Process each DCU node
-        for node in 0..num_node {
-            trace!("Processing node {} of {}", node, num_node);
+        collect_reports(nodes, |dcu_id| self.request_report(dcu_id, userdata))
+    }
 
-            if let Ok(dcu_id) = topology_sysfs_get_dcu_id(node as u32) {
-                trace!("Found DCU ID: {}", dcu_id);
+    /// Get the attestation report of a single DCU
+    ///
+    /// Only the DCU whose ID is `gpu_id` is asked for a report.
+    ///
+    /// # Arguments
+    /// * `gpu_id` - The ID of the DCU, as found in the topology
+    /// * `userdata` - 64-byte user data value used for attestation request
+    ///
+    /// # Returns
+    /// - `Ok(AttestationReport)` of the DCU
+    /// - `Err(Error::UnknownGpuId)` if no DCU of the topology has `gpu_id`
+    pub fn get_report_for_gpu(
+        &mut self,
+        gpu_id: u32,
+        userdata: [u8; 64],
+    ) -> Result<AttestationReport, Error> {
+        let node = self.1.node_of(gpu_id)?;
 
-                // Skip invalid DCU IDs
-                if dcu_id == 0 {
-                    continue;
-                }
+        let report = self.request_report(gpu_id, userdata)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No attestation report returned")
+        })?;
+        debug!(
+            "Get dcu report succeeded - Node: {}, DCU ID: {}",
+            node, gpu_id
+        );
+
+        Ok(report)
+    }
+
+    /// Get attestation reports from all available DCU nodes, along with
+    /// what each was requested with
+    ///
+    /// # Arguments
+    /// * `userdata` - 64-byte user data value used for attestation request
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(ReportMeta, AttestationReport)>)` for each DCU node that
+    ///   produced a report, see [`get_report`](Self::get_report)
+    /// - `Err(Error)` in the same cases as [`get_report`](Self::get_report)
+    pub fn get_reports_with_meta(
+        &mut self,
+        userdata: [u8; 64],
+    ) -> Result<Vec<(ReportMeta, AttestationReport)>, Error> {
+        let nodes = self.1.dcu_nodes()?;
+
+        collect_reports(nodes, |dcu_id| {
+            self.request_report_with_meta(dcu_id, userdata)
+        })
+    }
+
+    /// Get attestation reports from all available DCU nodes in parallel
+    ///
+    /// The nodes are spread over at most [`MAX_PARALLEL_REQUESTS`] threads,
+    /// each of which opens the device anew so that no two threads issue
+    /// ioctls on the same file descriptor.
+    ///
+    /// # Arguments
+    /// * `userdata` - 64-byte user data value used for attestation request
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(gpu_id, ReportOutcome)>)` with the
+    ///   outcome of each DCU node, in topology order
+    /// - `Err(Error)` if the DCU nodes can't be discovered
+    pub fn get_reports_parallel(
+        &self,
+        userdata: [u8; 64],
+    ) -> Result<Vec<(u32, ReportOutcome)>, Error> {
+        let nodes = self.1.dcu_nodes()?;
+        let workers = nodes.len().clamp(1, MAX_PARALLEL_REQUESTS);
+
+        let mut outcomes: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|worker| {
+                    let nodes: Vec<_> = nodes
+                        .iter()
+                        .copied()
+                        .enumerate()
+                        .skip(worker)
+                        .step_by(workers)
+                        .collect();
+                    scope.spawn(move || self.request_reports_on_own_fd(&nodes, userdata))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("DCU report worker panicked"))
+                .collect()
+        });
+
+        outcomes.sort_by_key(|(index, _, _)| *index);
+        Ok(outcomes
+            .into_iter()
+            .map(|(_, gpu_id, outcome)| (gpu_id, outcome))
+            .collect())
+    }
 
-                // Initialize attestation request
-                let mut args = MkfdIoctlSecurityAttestationArgs::new();
-                args.set_attestation_args(dcu_id, userdata)?;
-
-                // Execute IOCTL request
-                if DCU_GET_REPORT.ioctl(&mut self.0, &mut args)? == 0 {
-                    if let Some(report) = args.extract_report()? {
-                        debug!(
-                            "Get dcu report succeeded - Node: {}, DCU ID: {}",
-                            node, dcu_id
-                        );
-                        // Debug output and storage
-                        report.print_report();
-                        reports.push(report);
-                    }
+    /// Requests the reports of `nodes`, numbered by their index in the
+    /// topology, on a handle of its own.
+    ///
+    /// Every node fails as [`Self::open_mkfd`] does if the handle can't be
+    /// opened.
+    fn request_reports_on_own_fd(
+        &self,
+        nodes: &[(usize, (usize, u32))],
+        userdata: [u8; 64],
+    ) -> Vec<(usize, u32, ReportOutcome)> {
+        let mut device = match open_device(&self.2, DEFAULT_OPEN_FLAGS) {
+            Ok(file) => DcuDevice(file, Topology::default(), self.2.clone()),
+            Err(e) => {
+                return nodes
+                    .iter()
+                    .map(|&(index, (_, gpu_id))| {
+                        let e = io::Error::new(e.kind(), e.to_string());
+                        (
+                            index,
+                            gpu_id,
+                            Err(Error::device_unavailable(&self.2, e, "mkfd")),
+                        )
+                    })
+                    .collect()
+            }
+        };
+
+        nodes
+            .iter()
+            .map(|&(index, (node, gpu_id))| {
+                let outcome = device.request_report(gpu_id, userdata).and_then(|report| {
+                    report.ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "No attestation report returned")
+                            .into()
+                    })
+                });
+                if outcome.is_ok() {
+                    debug!(
+                        "Get dcu report succeeded - Node: {}, DCU ID: {}",
+                        node, gpu_id
+                    );
                 }
+                (index, gpu_id, outcome)
+            })
+            .collect()
+    }
+
+    /// Get the first attestation report that fully verifies from the DCU nodes
+    ///
+    /// # Arguments
+    /// * `userdata` - 64-byte user data value used for attestation request
+    /// * `chain` - Certificate chain endorsing the DCU chip
+    ///
+    /// # Returns
+    /// - `Ok((dcu_id, report))` for the first report passing [`verify_report_with_chain`]
+    /// - `Err(Error::NoDcuVerifies)` with the failure of every node if no
+    ///   report verifies
+    pub fn get_verified_report(
+        &mut self,
+        userdata: [u8; 64],
+        chain: &kds::Chain,
+    ) -> Result<(u32, AttestationReport), Error> {
+        let nodes = self.1.dcu_nodes()?;
+
+        first_verified(nodes, |dcu_id| {
+            let report = self.request_report(dcu_id, userdata)?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "No attestation report returned")
+            })?;
+            verify_report_with_chain(&report, &userdata, chain)?;
+            Ok(report)
+        })
+    }
+
+    /// Requests an attestation report from a single DCU.
+    ///
+    /// Returns `Ok(None)` if the firmware didn't produce a report.
+    fn request_report(
+        &mut self,
+        dcu_id: u32,
+        userdata: [u8; 64],
+    ) -> Result<Option<AttestationReport>, Error> {
+        Ok(self
+            .request_report_with_meta(dcu_id, userdata)?
+            .map(|(_, report)| report))
+    }
+
+    /// Requests an attestation report from a single DCU, along with what
+    /// the request was made with.
+    ///
+    /// Returns `Ok(None)` if the firmware didn't produce a report, and
+    /// [`Error::EmptyResponse`] if the ioctl succeeded without the firmware
+    /// writing the response.
+    fn request_report_with_meta(
+        &mut self,
+        dcu_id: u32,
+        userdata: [u8; 64],
+    ) -> Result<Option<(ReportMeta, AttestationReport)>, Error> {
+        // Initialize attestation request
+        let mut args = SecurityAttestationArgsBuilder::new(dcu_id)
+            .userdata(userdata)
+            .build()?;
+        let requested_at = SystemTime::now();
+
+        // Execute IOCTL request
+        if DCU_GET_REPORT.ioctl(&mut self.0, &mut args)? != 0 {
+            return Ok(None);
+        }
+
+        let Some(report) = args.extract_report()? else {
+            return Ok(None);
+        };
+        // Debug output
+        report.print_report();
+
+        let meta = ReportMeta {
+            fw_version: None,
+            version: args.version,
+            gpu_id: dcu_id,
+            requested_at,
+        };
+        Ok(Some((meta, report)))
+    }
+}
+
+/// Requests the report of each of `nodes` with `request`.
+///
+/// A node whose request buffers can't be allocated is skipped, the others
+/// may still be served. Fails if no node produced a report.
+fn collect_reports<T>(
+    nodes: Vec<(usize, u32)>,
+    mut request: impl FnMut(u32) -> Result<Option<T>, Error>,
+) -> Result<Vec<T>, Error> {
+    let mut reports: Vec<T> = Vec::with_capacity(nodes.len());
+    let mut alloc_failure = None;
+
+    // Process each DCU node
+    for (node, dcu_id) in nodes {
+        match request(dcu_id) {
+            Ok(Some(report)) => {
+                debug!(
+                    "Get dcu report succeeded - Node: {}, DCU ID: {}",
+                    node, dcu_id
+                );
+                reports.push(report);
+            }
+            Ok(None) => {}
+            Err(Error::IoError(e)) if e.kind() == io::ErrorKind::OutOfMemory => {
+                warn!("Skipping DCU node {} (DCU ID {}): {}", node, dcu_id, e);
+                alloc_failure = Some(e);
             }
+            Err(e) => return Err(e),
         }
+    }
 
-        // Validate we got at least one report
-        if reports.is_empty() {
-            Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "No valid attestation reports obtained from any DCU node",
-            )
-            .into())
-        } else {
-            Ok(reports)
+    // Validate we got at least one report
+    if !reports.is_empty() {
+        Ok(reports)
+    } else if let Some(e) = alloc_failure {
+        Err(e.into())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No valid attestation reports obtained from any DCU node",
+        )
+        .into())
+    }
+}
+
+/// Returns the first report of `nodes` that `attempt` obtains and
+/// verifies, along with its DCU ID.
+///
+/// Fails with [`Error::NoDcuVerifies`] listing the error of each DCU if
+/// none does.
+fn first_verified<T>(
+    nodes: Vec<(usize, u32)>,
+    mut attempt: impl FnMut(u32) -> Result<T, Error>,
+) -> Result<(u32, T), Error> {
+    let mut failures = Vec::with_capacity(nodes.len());
+
+    for (node, dcu_id) in nodes {
+        match attempt(dcu_id) {
+            Ok(report) => return Ok((dcu_id, report)),
+            Err(e) => {
+                debug!("Node: {}, DCU ID: {} failed: {:?}", node, dcu_id, e);
+                failures.push((dcu_id, e));
+            }
         }
     }
+
+    Err(Error::NoDcuVerifies(failures))
 }
 
 /// Verifies multiple attestation reports asynchronously.
@@ -130,7 +721,10 @@ impl DcuDevice {
 /// * `Ok(())` if all reports pass verification
 /// * `Err(Error)` containing the first encountered verification failure
 #[cfg(feature = "network")]
-pub async fn verify_reports(reports: &[AttestationReport], userdata: &[u8; 64]) -> Result<(), Error> {
+pub async fn verify_reports(
+    reports: &[AttestationReport],
+    userdata: &[u8; 64],
+) -> Result<(), Error> {
     for report in reports {
         let cert_data = csv::cert::get_certificate_data(&report.body.chip_id).await?;
         verify_report(report, userdata, &cert_data)?;
@@ -165,13 +759,21 @@ pub fn verify_report(
     userdata: &[u8; 64],
     cert_data: &[u8],
 ) -> Result<(), Error> {
-    let mut cert_slice = cert_data;
-
     // Decode certificate chain
-    let hsk = ca::Certificate::decode(&mut cert_slice, ())?;
-    let cek = csv::Certificate::decode(&mut cert_slice, ())?;
-    let hrk = ca::Certificate::decode(&mut &HRK[..], ())?;
+    let chain = kds::Chain::from_der_bundle(cert_data)?;
 
+    verify_report_with_chain(report, userdata, &chain)
+}
+
+/// Verifies a single attestation report against an already decoded chain.
+///
+/// This is [`verify_report`] for callers holding a [`kds::Chain`], e.g. one
+/// restored with [`kds::Chain::from_der_bundle`].
+pub fn verify_report_with_chain(
+    report: &AttestationReport,
+    userdata: &[u8; 64],
+    chain: &kds::Chain,
+) -> Result<(), Error> {
     report.print_report();
 
     // Critical security check: nonce matching
@@ -181,11 +783,9 @@ pub fn verify_report(
         );
     }
 
-    // Validate certificate hierarchy
-    (&hrk, &hrk).verify()?; // HRK self-verification
-    (&hrk, &hsk).verify()?; // HRK → HSK
-    (&hsk, &cek).verify()?; // HSK → CEK
-    (&cek, report).verify()?; // CEK → Report
+    // Validate certificate hierarchy: HRK → HSK → CEK → Report
+    let cek = chain.verify()?;
+    (cek, report).verify()?;
 
     debug!(
         "Successfully verified report for Chip ID: {}",
@@ -195,34 +795,296 @@ pub fn verify_report(
     Ok(())
 }
 
-/// Saves certificates to local files
-fn save_certificates(
-    hsk: &ca::Certificate,
-    cek: &csv::Certificate,
-    hrk: &ca::Certificate,
-    chip_id: [u8; 16],
-) -> Result<(), Error> {
-    // Define certificates directory path
-    let certs_dir = Path::new("/opt/dcu/certs");
+/// Checks that the DCUs of a host, each given by its ID and its report,
+/// all report the same measurement, e.g. for DCUs serving one workload.
+///
+/// The measurements are only compared: the reports are to be verified
+/// beforehand, e.g. with [`verify_report_with_chain`].
+///
+/// Returns the common measurement. Otherwise the measurement most reports
+/// share, the first one on a tie, is the expected one and
+/// [`Error::MeasurementDivergence`] names the first DCU reporting another.
+/// Fails with [`io::ErrorKind::InvalidInput`] without any report.
+pub fn verify_uniform_measurement(reports: &[(u32, AttestationReport)]) -> Result<[u8; 32], Error> {
+    let measurements: Vec<_> = reports.iter().map(|(_, r)| r.body.measure).collect();
+    let count = |measure: &[u8; 32]| measurements.iter().filter(|&m| m == measure).count();
+
+    let mut expected = *measurements
+        .first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No DCU report to compare"))?;
+    for measure in &measurements {
+        if count(measure) > count(&expected) {
+            expected = *measure;
+        }
+    }
 
-    // Create directory recursively if it doesn't exist (similar to mkdir -p)
-    if !certs_dir.exists() {
-        fs::create_dir_all(certs_dir)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    match reports.iter().find(|(_, r)| r.body.measure != expected) {
+        Some(&(gpu_id, _)) => Err(Error::MeasurementDivergence { gpu_id }),
+        None => Ok(expected),
     }
+}
 
-    // Write HSK certificate
-    hsk.write_to_file(&certs_dir.join("hsk.cert"))?;
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    // Convert chip_id to string
-    let chip_id_str = String::from_utf8(chip_id.to_vec())
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fn is_cloexec(file: &File) -> bool {
+        let fd_flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFD) };
+        assert!(fd_flags >= 0);
+        fd_flags & libc::FD_CLOEXEC != 0
+    }
 
-    // Write CEK certificate (with chip_id in filename)
-    cek.write_to_file(&certs_dir.join(format!("{}_cek.cert", chip_id_str)))?;
+    #[test]
+    fn uniform_measurement() {
+        let report = |measure: u8| {
+            let mut report = AttestationReport::default();
+            report.body.measure = [measure; 32];
+            report
+        };
 
-    // Write HRK certificate
-    hrk.write_to_file(&certs_dir.join("hrk.cert"))?;
+        let uniform: Vec<_> = (1..=4).map(|gpu_id| (gpu_id, report(0xaa))).collect();
+        assert_eq!(verify_uniform_measurement(&uniform).unwrap(), [0xaa; 32]);
 
-    Ok(())
+        // The outlier is named even when it comes first.
+        for outlier in 1..=4 {
+            let mut reports = uniform.clone();
+            reports[outlier as usize - 1].1 = report(0xbb);
+            assert!(matches!(
+                verify_uniform_measurement(&reports),
+                Err(Error::MeasurementDivergence { gpu_id }) if gpu_id == outlier
+            ));
+        }
+
+        assert!(matches!(
+            verify_uniform_measurement(&[]),
+            Err(Error::IoError(e)) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+    }
+
+    /// A topology of a single node, `0`, whose `gpu_id` attribute is
+    /// `gpu_id`.
+    fn topology_with_gpu_id(name: &str, gpu_id: &str) -> PathBuf {
+        let nodes = std::env::temp_dir().join(format!("csv-rs-{}-{name}", std::process::id()));
+        fs::create_dir_all(nodes.join("0")).unwrap();
+        fs::write(nodes.join("0/gpu_id"), gpu_id).unwrap();
+        nodes
+    }
+
+    #[test]
+    fn open_wait() {
+        let device = Path::new("/dev/null");
+        let nodes = topology_with_gpu_id("wait", "123");
+        let missing = nodes.join("missing");
+        let timeout = Duration::from_millis(250);
+
+        let opened = DcuDevice::open_wait_at(device, Topology::new(&nodes), timeout)
+            .and_then(|device| Ok(device.1.dcu_nodes()?));
+        let without_device =
+            DcuDevice::open_wait_at(&missing, Topology::new(&nodes), timeout).map(|_| ());
+        let without_topology =
+            DcuDevice::open_wait_at(device, Topology::new(&missing), timeout).map(|_| ());
+
+        // The node appears while waiting.
+        fs::write(nodes.join("0/gpu_id"), "0").unwrap();
+        let late = std::thread::spawn({
+            let nodes = nodes.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(150));
+                fs::write(nodes.join("0/gpu_id"), "123").unwrap();
+            }
+        });
+        let waited = DcuDevice::open_wait_at(device, Topology::new(&nodes), Duration::from_secs(5));
+        late.join().unwrap();
+        fs::remove_dir_all(&nodes).unwrap();
+
+        assert_eq!(opened.unwrap(), [(0, 123)]);
+        assert!(matches!(without_device, Err(Error::Timeout(t)) if t == timeout));
+        assert!(matches!(without_topology, Err(Error::Timeout(_))));
+        assert!(waited.is_ok());
+    }
+
+    #[test]
+    fn dcu_id_surrounding_whitespace() {
+        for (name, gpu_id) in [("newline", "123\n"), ("spaces", " 123 \n")] {
+            let nodes = topology_with_gpu_id(name, gpu_id);
+            let dcu_id = topology_sysfs_get_dcu_id(&nodes.join("0"));
+            fs::remove_dir_all(&nodes).unwrap();
+            assert_eq!(dcu_id.unwrap(), 123, "{gpu_id:?}");
+        }
+    }
+
+    #[test]
+    fn dcu_id_empty() {
+        let nodes = topology_with_gpu_id("empty", "");
+        let dcu_id = topology_sysfs_get_dcu_id(&nodes.join("0"));
+        // The node is being hot-plugged, the topology is to be read again.
+        let dcu_nodes = Topology::new(&nodes).dcu_nodes();
+        fs::remove_dir_all(&nodes).unwrap();
+
+        assert_eq!(dcu_id.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(dcu_nodes.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn nodes_permission_denied() {
+        let denied = || Err(io::ErrorKind::PermissionDenied.into());
+
+        // A node the user may not read is skipped.
+        let nodes = collect_nodes([(0, Ok(0)), (1, denied()), (2, Ok(123))].into_iter());
+        assert_eq!(nodes.unwrap(), [(2, 123)]);
+
+        let nodes = collect_nodes([(0, denied()), (1, denied())].into_iter());
+        match Error::from(nodes.unwrap_err()) {
+            Error::PermissionDenied { nodes } => assert_eq!(nodes, [0, 1]),
+            other => panic!("unexpected error: {other}"),
+        }
+
+        // A CPU node, of ID 0, doesn't hide that every DCU is denied.
+        let nodes = collect_nodes([(0, Ok(0)), (1, denied()), (2, denied())].into_iter());
+        match Error::from(nodes.unwrap_err()) {
+            Error::PermissionDenied { nodes } => assert_eq!(nodes, [1, 2]),
+            other => panic!("unexpected error: {other}"),
+        }
+
+        assert_eq!(collect_nodes([(0, Ok(0))].into_iter()).unwrap(), []);
+        assert_eq!(collect_nodes(std::iter::empty()).unwrap(), []);
+    }
+
+    #[test]
+    fn first_verified_failures() {
+        let nodes = vec![(1, 55872), (2, 6510), (3, 777)];
+
+        let verified = first_verified(nodes.clone(), |dcu_id| match dcu_id {
+            6510 => Ok("report"),
+            _ => Err(Error::BadSignature),
+        });
+        assert_eq!(verified.unwrap(), (6510, "report"));
+
+        let verified = first_verified(nodes, |dcu_id| match dcu_id {
+            55872 => Err::<(), _>(Error::BadSignature),
+            _ => Err(out_of_memory()),
+        });
+        match verified {
+            Err(Error::NoDcuVerifies(failures)) => {
+                let ids: Vec<_> = failures.iter().map(|(dcu_id, _)| *dcu_id).collect();
+                assert_eq!(ids, [55872, 6510, 777]);
+                assert!(matches!(failures[0].1, Error::BadSignature));
+                assert!(
+                    matches!(&failures[1].1, Error::IoError(e) if e.kind() == io::ErrorKind::OutOfMemory)
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn own_fd_unavailable() {
+        let missing = std::env::temp_dir().join(format!("csv-rs-{}-mkfd", std::process::id()));
+        let device = DcuDevice(
+            File::open("/dev/null").unwrap(),
+            Topology::default(),
+            missing.clone(),
+        );
+
+        let outcomes =
+            device.request_reports_on_own_fd(&[(0, (1, 55872)), (1, (2, 6510))], [0; 64]);
+        assert_eq!(outcomes.len(), 2);
+        for (_, _, outcome) in outcomes {
+            match outcome {
+                Err(Error::DeviceUnavailable { path, hint }) => {
+                    assert_eq!(path, missing);
+                    assert!(hint.contains("mkfd"), "{hint}");
+                }
+                other => panic!("unexpected outcome: {:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    fn out_of_memory() -> Error {
+        io::Error::new(io::ErrorKind::OutOfMemory, "mock allocation failure").into()
+    }
+
+    #[test]
+    fn collect_reports_skips_alloc_failure() {
+        let nodes = vec![(1, 55872), (2, 6510)];
+
+        let reports = collect_reports(nodes, |dcu_id| match dcu_id {
+            55872 => Err(out_of_memory()),
+            _ => Ok(Some(AttestationReport::default())),
+        })
+        .unwrap();
+        assert_eq!(reports.len(), 1);
+    }
+
+    #[test]
+    fn collect_reports_all_alloc_failures() {
+        let nodes = vec![(1, 55872), (2, 6510)];
+
+        match collect_reports::<AttestationReport>(nodes, |_| Err(out_of_memory())) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::OutOfMemory),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn collect_reports_other_errors_abort() {
+        let nodes = vec![(1, 55872), (2, 6510)];
+        let mut requested = Vec::new();
+
+        let result = collect_reports::<AttestationReport>(nodes, |dcu_id| {
+            requested.push(dcu_id);
+            Err(Error::InvalidLen)
+        });
+        assert!(matches!(result, Err(Error::InvalidLen)));
+        assert_eq!(requested, [55872]);
+    }
+
+    #[test]
+    fn report_summary() {
+        let mut report = AttestationReport::default();
+        report.body.measure = [0xab; 32];
+        report.body.chip_id[..6].copy_from_slice(b"DCU-01");
+        report.body.sig_algo = 4;
+
+        let line = report.summary().to_string();
+        assert_eq!(
+            line,
+            format!("measure={} chip_id=DCU-01 sig_algo=sm2-sa", "ab".repeat(32))
+        );
+    }
+
+    #[test]
+    fn open_device_cloexec_by_default() {
+        let file = open_device(Path::new("/dev/null"), DEFAULT_OPEN_FLAGS).unwrap();
+        assert!(is_cloexec(&file));
+    }
+
+    #[test]
+    fn open_device_checks_char_device() {
+        let err = open_device(Path::new("/nonexistent/mkfd"), DEFAULT_OPEN_FLAGS).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("/nonexistent/mkfd"));
+
+        let err =
+            open_device(Path::new(env!("CARGO_MANIFEST_DIR")), DEFAULT_OPEN_FLAGS).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("not a character device"));
+    }
+
+    #[test]
+    fn open_mkfd_missing_device() {
+        match DcuDevice::open_mkfd("/nonexistent/mkfd") {
+            Err(Error::DeviceUnavailable { path, hint }) => {
+                assert_eq!(path, Path::new("/nonexistent/mkfd"));
+                assert!(hint.contains("mkfd kernel module"), "{hint}");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn open_device_without_cloexec() {
+        let file = open_device(Path::new("/dev/null"), libc::O_RDWR).unwrap();
+        assert!(!is_cloexec(&file));
+    }
 }
@@ -4,17 +4,17 @@
 //
 
 use crate::{
-    certs::{csv::Certificate, Usage, Verifiable},
+    certs::{csv::Certificate, Algorithm, Usage, Verifiable},
     crypto::{sig::ecdsa, PublicKey, Signature},
     util::*,
 };
 
+use hex::encode;
 use log::*;
 use serde::{Deserialize, Serialize};
+use static_assertions::const_assert_eq;
 use std::ffi::c_void;
 use std::io::Write;
-use serde_bytes::ByteBuf;
-use hex::encode;
 
 /// A structure representing the body of an attestation report.
 /// This is marked with `repr(C)` for C compatibility and can be serialized/deserialized.
@@ -45,9 +45,9 @@ impl Default for Body {
         Self {
             version: Default::default(),
             chip_id: Default::default(),
-            user_data: [0u8;64],
+            user_data: [0u8; 64],
             measure: Default::default(),
-            reserved: [0u8;128],
+            reserved: [0u8; 128],
             sig_usage: Default::default(),
             sig_algo: Default::default(),
         }
@@ -59,7 +59,7 @@ impl Body {
     pub fn print_fields(&self) {
         trace!("Version: {}", self.version);
         trace!("Chip ID: {}", String::from_utf8_lossy(&self.chip_id));
-        trace!("User data: {}", encode(&self.user_data));
+        trace!("User data: {}", encode(self.user_data));
         trace!("Measure: {:?}", self.measure);
         trace!("Signature Usage: {}", self.sig_usage);
         trace!("Signature Algorithm: {}", self.sig_algo);
@@ -84,6 +84,45 @@ impl AttestationReport {
     }
 }
 
+/// The fields of a DCU attestation report identifying what it attests,
+/// see [`AttestationReport::summary`]
+///
+/// Its `Display` renders a single line, e.g.
+/// `measure=… chip_id=… sig_algo=sm2-sa`.
+#[derive(Debug, Copy, Clone)]
+pub struct ReportSummary {
+    /// Measurement of the DCU
+    pub measurement: [u8; 32],
+    /// Identifier of the chip the report comes from
+    pub chip_id: [u8; 16],
+    /// Algorithm the report is signed with
+    pub sig_algo: Algorithm,
+}
+
+impl AttestationReport {
+    /// Summarizes the report for a log line
+    pub fn summary(&self) -> ReportSummary {
+        ReportSummary {
+            measurement: self.body.measure,
+            chip_id: self.body.chip_id,
+            sig_algo: Algorithm::from(self.body.sig_algo),
+        }
+    }
+}
+
+impl std::fmt::Display for ReportSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let len = self.chip_id.iter().position(|&b| b == 0).unwrap_or(16);
+        write!(
+            f,
+            "measure={} chip_id={} sig_algo={}",
+            encode(self.measurement),
+            String::from_utf8_lossy(&self.chip_id[..len]),
+            self.sig_algo
+        )
+    }
+}
+
 impl Default for AttestationReport {
     /// Creates a default AttestationReport with empty body and signature
     fn default() -> Self {
@@ -94,6 +133,56 @@ impl Default for AttestationReport {
     }
 }
 
+/// What an attestation report was requested with, for verifiers applying
+/// version-specific rules
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReportMeta {
+    /// Version of the DCU firmware that produced the report, if known.
+    /// The current firmware reports it neither in the response nor through
+    /// another ioctl, so this is `None`.
+    pub fw_version: Option<u32>,
+    /// Version of the attestation message the request was made with, as
+    /// set in the ioctl arguments. The report format has a version of its
+    /// own, see [`Body::version`].
+    pub version: u32,
+    /// ID of the DCU that produced the report, as found in the topology
+    pub gpu_id: u32,
+    /// When the report was requested, right before the ioctl
+    pub requested_at: std::time::SystemTime,
+}
+
+/// Request structure the firmware reads from the start of `request_data`
+///
+/// The wire layout of the request page is:
+///
+/// | Offset | Size | Field       |
+/// |--------|------|-------------|
+/// | 0      | 64   | `user_data` |
+/// | 64     | -    | zero        |
+///
+/// The page carries no version nor size, both are passed along with it in
+/// the ioctl arguments, see `MkfdIoctlSecurityAttestationArgs`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SecurityAttestationRequestHeader {
+    /// User data value to be echoed in the report (64 bytes)
+    pub user_data: [u8; 64],
+}
+
+const_assert_eq!(std::mem::size_of::<SecurityAttestationRequestHeader>(), 64);
+const_assert_eq!(
+    std::mem::offset_of!(SecurityAttestationRequestHeader, user_data),
+    0
+);
+const_assert_eq!(std::mem::align_of::<SecurityAttestationRequestHeader>(), 1);
+
+impl SecurityAttestationRequestHeader {
+    /// Creates the request for a report echoing `user_data`
+    pub fn new(user_data: [u8; 64]) -> Self {
+        Self { user_data }
+    }
+}
+
 /// Response structure containing the attestation report from the dcu devices
 #[repr(C)]
 #[derive(Debug, serde::Deserialize)]
@@ -164,10 +253,6 @@ impl Verifiable for (&Certificate, &AttestationReport) {
     fn verify(self) -> Result<(), std::io::Error> {
         let key: PublicKey = self.0.try_into()?;
         let sig: Signature = self.1.try_into()?;
-        key.verify(
-            self.1,
-            &self.0.body.data.user_id[..self.0.body.data.uid_size as usize],
-            &sig,
-        )
+        key.verify(self.1, &self.0.sm2_id(), &sig)
     }
 }
@@ -0,0 +1,256 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reports requested through the configfs TSM interface of the kernel, for
+//! kernels providing it rather than the `/dev/csv-guest` device.
+//!
+//! The interface is a directory of configfs: creating an entry under it
+//! creates an `inblob` the report data is written to, and an `outblob` the
+//! report is then read from. Its `generation` counts the writes to the
+//! entry, it goes up by more than one if someone else requested a report
+//! with the same entry meanwhile.
+
+use super::{CsvGuest, ReportRsp, ReportSource, DEFAULT_GUEST_PATH};
+use crate::error::*;
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The path [`ConfigfsBackend::open`] requests reports under.
+pub const DEFAULT_TSM_REPORT_PATH: &str = "/sys/kernel/config/tsm/report";
+
+/// The suffix of the next entry created by this process.
+static NEXT_ENTRY: AtomicUsize = AtomicUsize::new(0);
+
+/// A handle to an entry of the configfs TSM interface.
+///
+/// The interface only takes the report data, the kernel chooses the mnonce
+/// of each report: its [`ReportSource::request`] fails with
+/// [`Error::Unsupported`] for a requested mnonce. The mnonce a report was
+/// requested with is recovered from the report, see
+/// [`super::AttestationReport::freshness_token`].
+pub struct ConfigfsBackend {
+    entry: PathBuf,
+}
+
+impl ConfigfsBackend {
+    /// Creates an entry of the TSM interface at
+    /// `/sys/kernel/config/tsm/report`.
+    ///
+    /// Returns [`Error::DeviceUnavailable`] if the interface is missing,
+    /// e.g. without configfs mounted or the `tsm` kernel module, or not
+    /// writable by the user.
+    pub fn open() -> Result<Self, Error> {
+        Self::open_at(Path::new(DEFAULT_TSM_REPORT_PATH))
+    }
+
+    /// Creates an entry of the TSM interface at `root`, named after the
+    /// process and numbered within it, so that no two handles share an
+    /// entry. Existing entries, e.g. left by a process of the same pid, are
+    /// skipped.
+    pub fn open_at(root: &Path) -> Result<Self, Error> {
+        loop {
+            let suffix = NEXT_ENTRY.fetch_add(1, Ordering::Relaxed);
+            let entry = root.join(format!("csv-rs-{}-{suffix}", std::process::id()));
+            match fs::create_dir(&entry) {
+                Ok(()) => return Ok(Self { entry }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(Error::device_unavailable(root, e, "tsm")),
+            }
+        }
+    }
+
+    fn generation(&self) -> Result<u64, Error> {
+        let generation = fs::read_to_string(self.entry.join("generation"))?;
+        generation
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+    }
+
+    /// Requests the report for `data`, writing it to `inblob` with `write`.
+    fn request_with(
+        &self,
+        data: [u8; 64],
+        write: fn(&Path, [u8; 64]) -> io::Result<()>,
+    ) -> Result<ReportRsp, Error> {
+        // The report is the one of `data` if our write is the only one
+        // counted from before it to after the report was read.
+        let generation = self.generation()?;
+        write(&self.entry.join("inblob"), data)?;
+        let page = fs::read(self.entry.join("outblob"))?;
+        if self.generation()? != generation + 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "Another report was requested through the TSM entry",
+            )
+            .into());
+        }
+
+        ReportRsp::from_page(&page)
+    }
+}
+
+impl ReportSource for ConfigfsBackend {
+    /// Requests the report for `data`.
+    ///
+    /// Returns a [`io::ErrorKind::WouldBlock`] I/O error if another request
+    /// went through the entry meanwhile, the request is to be made again.
+    fn request(&mut self, data: [u8; 64], mnonce: Option<[u8; 16]>) -> Result<ReportRsp, Error> {
+        if mnonce.is_some() {
+            return Err(Error::Unsupported);
+        }
+
+        self.request_with(data, |inblob, data| fs::write(inblob, data))
+    }
+}
+
+impl Drop for ConfigfsBackend {
+    fn drop(&mut self) {
+        // Removing the entry only fails if the interface went away.
+        let _ = fs::remove_dir(&self.entry);
+    }
+}
+
+/// Opens the way reports are requested on this kernel: the CSV guest device
+/// if there is one, the configfs TSM interface otherwise.
+///
+/// Returns the error of the device if neither is available.
+pub fn open_report_source() -> Result<Box<dyn ReportSource + Send>, Error> {
+    open_report_source_at(
+        Path::new(DEFAULT_GUEST_PATH),
+        Path::new(DEFAULT_TSM_REPORT_PATH),
+    )
+}
+
+fn open_report_source_at(guest: &Path, tsm: &Path) -> Result<Box<dyn ReportSource + Send>, Error> {
+    match CsvGuest::open_at(guest) {
+        Ok(guest) => Ok(Box::new(guest)),
+        Err(e) if guest.exists() || !tsm.is_dir() => Err(e),
+        Err(_) => Ok(Box::new(ConfigfsBackend::open_at(tsm)?)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::{golden_response, GOLDEN_EVIDENCE};
+
+    /// A TSM interface for the test `name`.
+    fn fixture(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("csv-rs-tsm-{name}-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    /// Serves the golden evidence from the entry, as configfs would once the
+    /// entry is created.
+    fn populate(entry: &Path) {
+        fs::write(entry.join("outblob"), GOLDEN_EVIDENCE).unwrap();
+        fs::write(entry.join("generation"), "1\n").unwrap();
+    }
+
+    /// Writes `inblob` and counts the write, as configfs does.
+    fn write_counted(inblob: &Path, data: [u8; 64]) -> io::Result<()> {
+        fs::write(inblob, data)?;
+        let generation = inblob.with_file_name("generation");
+        let count: u64 = fs::read_to_string(&generation)?.trim().parse().unwrap();
+        fs::write(generation, format!("{}\n", count + 1))
+    }
+
+    /// Writes `inblob` while another request goes through the entry.
+    fn write_raced(inblob: &Path, data: [u8; 64]) -> io::Result<()> {
+        write_counted(inblob, data)?;
+        write_counted(inblob, [0; 64])
+    }
+
+    #[test]
+    fn request() {
+        let root = fixture("request");
+        let mut backend = ConfigfsBackend::open_at(&root).unwrap();
+        populate(&backend.entry);
+
+        let response = backend.request_with([0x42; 64], write_counted);
+        let inblob = fs::read(backend.entry.join("inblob"));
+        let mnonce = backend.request([0x42; 64], Some([0; 16]));
+        fs::remove_dir_all(&root).unwrap();
+
+        let response = response.unwrap();
+        assert_eq!(response.report.anonce, golden_response().report.anonce);
+        assert_eq!(inblob.unwrap(), [0x42; 64]);
+        assert!(matches!(mnonce, Err(Error::Unsupported)));
+    }
+
+    #[test]
+    fn raced_request() {
+        let root = fixture("raced");
+        let backend = ConfigfsBackend::open_at(&root).unwrap();
+        populate(&backend.entry);
+
+        let response = backend.request_with([0x42; 64], write_raced);
+        fs::remove_dir_all(&root).unwrap();
+
+        match response {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::WouldBlock),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn separate_entries() {
+        let root = fixture("entries");
+        let first = ConfigfsBackend::open_at(&root).unwrap();
+        let second = ConfigfsBackend::open_at(&root).unwrap();
+        let (first_entry, second_entry) = (first.entry.clone(), second.entry.clone());
+        drop((first, second));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_ne!(first_entry, second_entry);
+        assert!(first_entry.starts_with(&root) && second_entry.starts_with(&root));
+    }
+
+    #[test]
+    fn missing_interface() {
+        let root = Path::new("/nonexistent/tsm/report");
+        match ConfigfsBackend::open_at(root) {
+            Err(Error::DeviceUnavailable { path, hint }) => {
+                assert_eq!(path, root);
+                assert!(hint.contains("tsm kernel module"), "{hint}");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn fallback_without_device() {
+        let root = fixture("fallback");
+        let guest = Path::new("/nonexistent/csv-guest");
+
+        let source = open_report_source_at(guest, &root);
+        let entries: Vec<PathBuf> = fs::read_dir(&root)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        entries.iter().for_each(|entry| populate(entry));
+        // The entry doesn't count the write, unlike configfs.
+        let response = source.and_then(|mut source| source.request([0x42; 64], None));
+        let inblob = fs::read(entries[0].join("inblob"));
+        let without_tsm = open_report_source_at(guest, &root.join("missing"));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(inblob.unwrap(), [0x42; 64]);
+        assert!(matches!(
+            response,
+            Err(Error::IoError(e)) if e.kind() == io::ErrorKind::WouldBlock
+        ));
+        assert!(matches!(
+            without_tsm.map(|_| ()),
+            Err(Error::DeviceUnavailable { path, .. }) if path == guest
+        ));
+    }
+}
@@ -0,0 +1,310 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use super::types::*;
+use crate::{
+    certs::{builtin::HRK, ca, csv, describe_link, kds, Verifiable},
+    error::*,
+};
+
+use codicon::{Decoder, Encoder};
+use openssl::hash::{hash, MessageDigest};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Everything a verifier needs to check an attestation report: the report,
+/// its signer evidence as produced by the firmware and the chain endorsing
+/// the chip.
+///
+/// The canonical encoding is the report, the signer evidence, then the HSK
+/// and the CEK, each as encoded by the firmware or the KDS. It carries no
+/// padding nor the builtin HRK.
+#[derive(Serialize, Deserialize)]
+pub struct Evidence {
+    /// The attestation report.
+    pub report: AttestationReport,
+
+    /// The signer evidence of the report, not restored.
+    pub signer: ReportSigner,
+
+    /// The certificate chain endorsing the chip.
+    pub chain: kds::Chain,
+}
+
+impl Evidence {
+    /// Computes the SM3 digest of the canonical encoding, a stable
+    /// reference to the evidence for e.g. transparency logs.
+    ///
+    /// Returns [`Error::UnsupportedBackend`] if the OpenSSL backend lacks
+    /// SM3.
+    ///
+    /// ```
+    /// use codicon::{Decoder, Encoder};
+    /// use csv_rs::api::guest::Evidence;
+    ///
+    /// let mut encoded = include_bytes!("../../../tests/test_data/report.cert").to_vec();
+    /// encoded.extend_from_slice(include_bytes!("../../../tests/test_data/hsk_cek.cert"));
+    ///
+    /// let evidence = Evidence::decode(&mut &encoded[..], ()).unwrap();
+    /// let mut reencoded = Vec::new();
+    /// evidence.encode(&mut reencoded, ()).unwrap();
+    ///
+    /// let again = Evidence::decode(&mut &reencoded[..], ()).unwrap();
+    /// assert_eq!(evidence.digest().unwrap(), again.digest().unwrap());
+    /// ```
+    pub fn digest(&self) -> Result<[u8; 32], Error> {
+        crate::crypto::check_backend()?;
+
+        let mut encoded = Vec::new();
+        self.encode(&mut encoded, ())
+            .expect("encoding into a Vec can't fail");
+
+        let digest = hash(MessageDigest::sm3(), &encoded)?;
+        Ok(digest[..].try_into().unwrap())
+    }
+
+    /// Writes the evidence to `writer` as a stream of its parts, the
+    /// report, the signer evidence, the HSK and the CEK, each in its
+    /// canonical encoding behind its length as a little endian `u32`.
+    ///
+    /// Only a part is held in memory at a time, so that batches of evidence
+    /// are archived in bounded memory. Read the evidence back with
+    /// [`Self::read_from`].
+    ///
+    /// ```
+    /// use csv_rs::{api::guest::Evidence, testutil::{GOLDEN_EVIDENCE, GOLDEN_HSK_CEK}};
+    /// use codicon::Decoder;
+    ///
+    /// let evidence = Evidence::decode(&mut &[GOLDEN_EVIDENCE, GOLDEN_HSK_CEK].concat()[..], ())?;
+    ///
+    /// let mut archive = Vec::new();
+    /// evidence.write_to(&mut archive)?;
+    /// evidence.write_to(&mut archive)?;
+    ///
+    /// let mut reader = &archive[..];
+    /// while !reader.is_empty() {
+    ///     let read = Evidence::read_from(&mut reader)?;
+    ///     assert_eq!(read.digest()?, evidence.digest()?);
+    /// }
+    /// # Ok::<(), csv_rs::error::Error>(())
+    /// ```
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), Error> {
+        let mut part = Vec::new();
+        let mut write = |encode: &dyn Fn(&mut Vec<u8>) -> std::io::Result<()>| {
+            part.clear();
+            encode(&mut part)?;
+            let len = u32::try_from(part.len()).map_err(|_| Error::InvalidLen)?;
+            writer.write_all(&len.to_le_bytes())?;
+            writer.write_all(&part)?;
+            Ok::<(), Error>(())
+        };
+
+        write(&|part| self.report.encode(part, ()))?;
+        write(&|part| self.signer.encode(part, ()))?;
+        write(&|part| self.chain.ca.hsk.encode(part, ()))?;
+        write(&|part| self.chain.cek.encode(part, ()))
+    }
+
+    /// Reads evidence written by [`Self::write_to`] from `reader`, leaving
+    /// it at the evidence that follows, if any.
+    ///
+    /// Returns [`Error::InvalidLen`] if a part isn't as long as its length
+    /// says. A reader ending before the evidence fails with an I/O error of
+    /// kind [`std::io::ErrorKind::UnexpectedEof`].
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, Error> {
+        let report = read_part(reader)?;
+        let signer = read_part(reader)?;
+        let hsk = read_part(reader)?;
+        let cek = read_part(reader)?;
+        let hrk = ca::Certificate::decode(&mut &HRK[..], ())?;
+
+        Ok(Self {
+            report,
+            signer,
+            chain: kds::Chain {
+                ca: ca::Chain { hsk, hrk },
+                cek,
+            },
+        })
+    }
+
+    /// Verifies the report all the way up to the HRK, see
+    /// [`AttestationReport::verify_full`].
+    pub fn verify(&self, mnonce: &[u8; 16]) -> Result<VerifyOutcome, Error> {
+        self.report.verify_full(&self.signer, &self.chain, mnonce)
+    }
+
+    /// Renders the chain endorsing the report, see [`kds::Chain::describe`],
+    /// down to the PEK the report is signed with.
+    pub fn describe(&self) -> String {
+        let mut signer = self.signer.clone();
+        let pek = signer
+            .restore(&self.report.anonce)
+            .and_then(|_| signer.pek_certificate());
+
+        let pek = match pek {
+            Ok(pek) => describe_link(
+                "PEK",
+                &pek.subject(),
+                pek.body.data.pubkey.usage,
+                pek.fingerprint_hex(),
+                "CEK",
+                (&self.chain.cek, &pek).verify(),
+            ),
+            Err(e) => format!("PEK: FAILED ({e})\n"),
+        };
+
+        self.chain.describe() + &pek
+    }
+}
+
+impl Encoder<()> for Evidence {
+    type Error = std::io::Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> Result<(), std::io::Error> {
+        self.report.encode(&mut writer, ())?;
+        self.signer.encode(&mut writer, ())?;
+        self.chain.ca.hsk.encode(&mut writer, ())?;
+        self.chain.cek.encode(&mut writer, ())
+    }
+}
+
+impl Decoder<()> for Evidence {
+    type Error = std::io::Error;
+
+    fn decode(mut reader: impl Read, _: ()) -> Result<Self, std::io::Error> {
+        let report = AttestationReport::decode(&mut reader, ())?;
+        let signer = ReportSigner::decode(&mut reader, ())?;
+        let hsk = ca::Certificate::decode(&mut reader, ())?;
+        let cek = csv::Certificate::decode(&mut reader, ())?;
+        let hrk = ca::Certificate::decode(&mut &HRK[..], ())?;
+
+        Ok(Self {
+            report,
+            signer,
+            chain: kds::Chain {
+                ca: ca::Chain { hsk, hrk },
+                cek,
+            },
+        })
+    }
+}
+
+/// Decodes a part of evidence streamed by [`Evidence::write_to`].
+fn read_part<T: Decoder<(), Error = std::io::Error>>(reader: &mut impl Read) -> Result<T, Error> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+
+    let mut part = reader.take(u32::from_le_bytes(len).into());
+    let decoded = T::decode(&mut part, ())?;
+    if part.limit() != 0 {
+        return Err(Error::InvalidLen);
+    }
+
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::certs::Usage;
+    use crate::testutil::{GOLDEN_EVIDENCE, GOLDEN_HSK_CEK, GOLDEN_MNONCE};
+
+    fn recorded() -> Vec<u8> {
+        [GOLDEN_EVIDENCE, GOLDEN_HSK_CEK].concat()
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let encoded = recorded();
+        let evidence = Evidence::decode(&mut &encoded[..], ()).unwrap();
+
+        let mut reencoded = Vec::new();
+        evidence.encode(&mut reencoded, ()).unwrap();
+        assert_eq!(reencoded, encoded);
+    }
+
+    #[test]
+    fn stream_round_trip() {
+        let encoded = recorded();
+        let evidence = Evidence::decode(&mut &encoded[..], ()).unwrap();
+
+        let mut stream = Vec::new();
+        for _ in 0..3 {
+            evidence.write_to(&mut stream).unwrap();
+        }
+        let parts = 4 * 4 + encoded.len();
+        assert_eq!(stream.len(), 3 * parts);
+
+        let mut reader = &stream[..];
+        for _ in 0..3 {
+            let read = Evidence::read_from(&mut reader).unwrap();
+            let mut reencoded = Vec::new();
+            read.encode(&mut reencoded, ()).unwrap();
+            assert_eq!(reencoded, encoded);
+        }
+        match Evidence::read_from(&mut reader) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+
+        // A part longer than its encoding.
+        let mut stream = Vec::new();
+        evidence.write_to(&mut stream).unwrap();
+        let len = u32::from_le_bytes(stream[..4].try_into().unwrap());
+        stream[..4].copy_from_slice(&(len + 1).to_le_bytes());
+        stream.insert(4 + len as usize, 0);
+        assert!(matches!(
+            Evidence::read_from(&mut &stream[..]),
+            Err(Error::InvalidLen)
+        ));
+    }
+
+    #[test]
+    fn digest_stable() {
+        let encoded = recorded();
+        let evidence = Evidence::decode(&mut &encoded[..], ()).unwrap();
+        let digest = evidence.digest().unwrap();
+
+        let mut reencoded = Vec::new();
+        evidence.encode(&mut reencoded, ()).unwrap();
+        let again = Evidence::decode(&mut &reencoded[..], ()).unwrap();
+        assert_eq!(again.digest().unwrap(), digest);
+
+        let expected = hash(MessageDigest::sm3(), &encoded).unwrap();
+        assert_eq!(digest[..], expected[..]);
+
+        let mut other = Evidence::decode(&mut &encoded[..], ()).unwrap();
+        other.report.anonce ^= 1;
+        assert_ne!(other.digest().unwrap(), digest);
+    }
+
+    #[test]
+    fn verify() {
+        let encoded = recorded();
+        let evidence = Evidence::decode(&mut &encoded[..], ()).unwrap();
+        let outcome = evidence.verify(&GOLDEN_MNONCE).unwrap();
+        assert_eq!(outcome.verified_by.usage, Usage::PEK);
+        assert_eq!(outcome.verified_by, outcome.pek.body.data.pubkey);
+    }
+
+    #[test]
+    fn describe() {
+        let encoded = recorded();
+        let evidence = Evidence::decode(&mut &encoded[..], ()).unwrap();
+
+        let description = evidence.describe();
+        let links: Vec<_> = description
+            .lines()
+            .filter(|line| !line.starts_with(' '))
+            .collect();
+        assert_eq!(links.len(), 4);
+        for (link, role) in links.iter().zip(["HRK", "HSK", "CEK", "PEK"]) {
+            assert!(link.starts_with(&format!("{role}: subject HYGON-SSD-{role}")));
+        }
+        assert!(description
+            .contains("f5a46663059fdb4cdd06d097ed21782142923bb3430b3b938f23d54292094e3a"));
+        assert!(!description.contains("FAILED"), "{}", description);
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A vendor-neutral view of attestation reports, for verifiers shared with
+//! other TEEs such as SEV.
+
+use super::types::*;
+
+/// The fields of an attestation report that TEE reports have in common,
+/// unmasked.
+///
+/// The fields of a CSV report map as follows:
+///
+/// | [`GenericAttestation`] | [`AttestationReport`] |
+/// |------------------------|-----------------------|
+/// | `measurement`          | `body.measure`        |
+/// | `report_data`          | `body.report_data`    |
+/// | `policy`               | `body.policy`         |
+/// | `guest_id`             | `body.vm_id`          |
+/// | `signature`            | `sig`, as r then s    |
+///
+/// `user_pubkey_digest`, `vm_version`, `mnonce`, `sig_usage`, `sig_algo` and
+/// `anonce` have no counterpart and are dropped. The signature is only
+/// carried for logging, it has to be verified on the CSV report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenericAttestation {
+    /// The launch measurement of the guest, 32 bytes for CSV.
+    pub measurement: Vec<u8>,
+
+    /// The data the guest asked to be bound to the report.
+    pub report_data: [u8; 64],
+
+    /// The guest policy, as its raw bits.
+    pub policy: u64,
+
+    /// The ID of the guest.
+    pub guest_id: Vec<u8>,
+
+    /// The raw signature of the report.
+    pub signature: Vec<u8>,
+}
+
+impl AttestationReport {
+    /// Maps the report to the vendor-neutral [`GenericAttestation`].
+    ///
+    /// ```
+    /// use codicon::Decoder;
+    /// use csv_rs::api::guest::AttestationReport;
+    ///
+    /// let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+    /// let report = AttestationReport::decode(&mut evidence, ()).unwrap();
+    ///
+    /// let generic = report.to_generic();
+    /// assert_eq!(generic.measurement.len(), 32);
+    /// ```
+    pub fn to_generic(&self) -> GenericAttestation {
+        let unmask = |data: &[u8]| -> Vec<u8> {
+            let mut data = data.to_vec();
+            xor_with_anonce(&mut data, &self.anonce).expect("masking can't fail");
+            data
+        };
+        let body = &self.body;
+
+        GenericAttestation {
+            measurement: unmask(&body.measure),
+            report_data: unmask(&body.report_data)
+                .try_into()
+                .expect("report_data is 64 bytes"),
+            policy: body.policy.xor(&self.anonce).bits().into(),
+            guest_id: unmask(&body.vm_id),
+            signature: [&self.sig.r[..], &self.sig.s[..]].concat(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::sig::SM2_SIG_COMPONENT_LEN;
+    use crate::testutil::golden_report;
+
+    #[test]
+    fn to_generic() {
+        let report = golden_report();
+        let generic = report.to_generic();
+
+        let masked = |data: &[u8]| -> Vec<u8> {
+            let mut data = data.to_vec();
+            xor_with_anonce(&mut data, &report.anonce).unwrap();
+            data
+        };
+
+        assert_eq!(masked(&generic.measurement), report.body.measure);
+        assert_eq!(masked(&generic.report_data), report.body.report_data);
+        assert_eq!(masked(&generic.guest_id), report.body.vm_id);
+        assert_eq!(
+            generic.policy,
+            u64::from(report.body.policy.bits() ^ report.anonce)
+        );
+        assert_eq!(generic.signature[..SM2_SIG_COMPONENT_LEN], report.sig.r);
+        assert_eq!(generic.signature[SM2_SIG_COMPONENT_LEN..], report.sig.s);
+    }
+}
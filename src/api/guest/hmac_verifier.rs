@@ -0,0 +1,174 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Batch verification of the hmac of signer evidence.
+
+use super::types::*;
+use crate::error::*;
+
+use openssl::{error::ErrorStack, hash::MessageDigest};
+use openssl_sys::{
+    HMAC_CTX_free, HMAC_CTX_new, HMAC_Final, HMAC_Init_ex, HMAC_Update, EVP_MAX_MD_SIZE, HMAC_CTX,
+};
+use std::{ffi::c_uint, ptr};
+
+/// Verifies the hmac of signer evidence like [`ReportSigner::verified`], but
+/// reuses one OpenSSL HMAC context across evidences.
+///
+/// The key of the hmac is the mnonce of each report, so only the context
+/// itself is reused, and the mnonce and the mac are kept on the stack rather
+/// than allocated for each report.
+///
+/// ```
+/// use csv_rs::{
+///     api::guest::{HmacVerifier, ReportRsp},
+///     testutil::{golden_response, GOLDEN_MNONCE},
+/// };
+///
+/// let ReportRsp { report, signer, .. } = golden_response();
+///
+/// let mut verifier = HmacVerifier::new().unwrap();
+/// let restored = verifier
+///     .verified(&signer, &GOLDEN_MNONCE, &report.body.mnonce, &report.anonce)
+///     .unwrap();
+/// assert_eq!(restored.serial_number(), "NZA9T14052605");
+/// ```
+pub struct HmacVerifier {
+    ctx: *mut HMAC_CTX,
+}
+
+// SAFETY: the context is owned by the verifier alone and is only used
+// through `&mut self`.
+unsafe impl Send for HmacVerifier {}
+
+impl HmacVerifier {
+    /// Allocates the HMAC context.
+    pub fn new() -> Result<Self, Error> {
+        // SAFETY: HMAC_CTX_new has no preconditions.
+        let ctx = unsafe { HMAC_CTX_new() };
+        if ctx.is_null() {
+            return Err(ErrorStack::get().into());
+        }
+
+        Ok(Self { ctx })
+    }
+
+    /// Verifies the signer evidence's hmac, see [`ReportSigner::verified`].
+    ///
+    /// On success returns the evidence with its PEK certificate and serial
+    /// number restored, `signer` is left masked so that it verifies again.
+    #[must_use = "the evidence is left masked, the restored one is returned"]
+    pub fn verified(
+        &mut self,
+        signer: &ReportSigner,
+        input_mnonce: &[u8],
+        mnonce: &[u8; 16],
+        anonce: &u32,
+    ) -> Result<ReportSigner, Error> {
+        let mut real_mnonce = *mnonce;
+        xor_with_anonce(&mut real_mnonce, anonce)?;
+
+        if real_mnonce[..] != *input_mnonce {
+            return Err(Error::MnonceMismatch {
+                expected: to_mnonce(input_mnonce),
+                got: real_mnonce,
+            });
+        }
+
+        let mut mac = [0u8; EVP_MAX_MD_SIZE as usize];
+        let mut len: c_uint = 0;
+
+        // SAFETY: `self.ctx` is a valid context, and every buffer is valid
+        // for the length passed along with it. HMAC_Init_ex resets the
+        // context with the new key, whatever it was used for before.
+        let ok = unsafe {
+            HMAC_Init_ex(
+                self.ctx,
+                real_mnonce.as_ptr().cast(),
+                real_mnonce.len() as _,
+                MessageDigest::sm3().as_ptr(),
+                ptr::null_mut(),
+            ) > 0
+                && HMAC_Update(self.ctx, signer.pek_cert.as_ptr(), signer.pek_cert.len()) > 0
+                && HMAC_Update(self.ctx, signer.sn.as_ptr(), signer.sn.len()) > 0
+                && HMAC_Update(self.ctx, signer.reserved.as_ptr(), signer.reserved.len()) > 0
+                && HMAC_Final(self.ctx, mac.as_mut_ptr(), &mut len) > 0
+        };
+        if !ok {
+            return Err(ErrorStack::get().into());
+        }
+
+        if mac[..len as usize] != signer.mac {
+            return Err(Error::BadSignature);
+        }
+
+        // restore pek cert and serial number.
+        let mut restored = signer.clone();
+        restored.restore(anonce)?;
+        Ok(restored)
+    }
+}
+
+impl Drop for HmacVerifier {
+    fn drop(&mut self) {
+        // SAFETY: `self.ctx` was allocated by HMAC_CTX_new and isn't used
+        // anymore.
+        unsafe { HMAC_CTX_free(self.ctx) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::{golden_response, GOLDEN_MNONCE};
+
+    #[test]
+    fn matches_report_signer() {
+        let ReportRsp { report, signer, .. } = golden_response();
+        let mnonce = GOLDEN_MNONCE;
+        let mut verifier = HmacVerifier::new().unwrap();
+
+        // The context is reused across evidences, and the same evidence
+        // verifies again as it is left masked.
+        for _ in 0..3 {
+            let expected = signer
+                .verified(&mnonce, &report.body.mnonce, &report.anonce)
+                .unwrap();
+
+            let actual = verifier
+                .verified(&signer, &mnonce, &report.body.mnonce, &report.anonce)
+                .unwrap();
+            assert_eq!(actual.pek_cert, expected.pek_cert);
+            assert_eq!(actual.sn, expected.sn);
+        }
+        assert_eq!(signer.pek_cert, golden_response().signer.pek_cert);
+    }
+
+    #[test]
+    fn rejects_bad_evidence() {
+        let ReportRsp { report, signer, .. } = golden_response();
+        let mnonce = GOLDEN_MNONCE;
+        let mut verifier = HmacVerifier::new().unwrap();
+
+        let mut tampered = signer.clone();
+        tampered.sn[0] ^= 1;
+        assert!(matches!(
+            verifier.verified(&tampered, &mnonce, &report.body.mnonce, &report.anonce),
+            Err(Error::BadSignature)
+        ));
+
+        // A failure leaves the context usable.
+        match verifier.verified(&signer, &[0u8; 16], &report.body.mnonce, &report.anonce) {
+            Err(Error::MnonceMismatch { expected, got }) => {
+                assert_eq!(expected, [0u8; 16]);
+                assert_eq!(got, mnonce);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+
+        let _restored = verifier
+            .verified(&signer, &mnonce, &report.body.mnonce, &report.anonce)
+            .unwrap();
+    }
+}
@@ -3,68 +3,365 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use crate::error::*;
+use crate::{certs::kds, error::*};
 mod ioctl;
 pub use ioctl::*;
+mod configfs;
+mod evidence;
+mod generic;
+mod hmac_verifier;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "network")]
+mod remote;
+mod report_cache;
+mod report_data;
+#[cfg(feature = "server")]
+mod server;
+mod summary;
 mod types;
+mod verified;
+mod verifier_cache;
+pub use configfs::*;
+pub use evidence::*;
+pub use generic::*;
+pub use hmac_verifier::*;
+#[cfg(feature = "rand")]
 use rand::Rng;
+#[cfg(feature = "network")]
+pub use remote::*;
+pub use report_cache::*;
+pub use report_data::*;
+#[cfg(feature = "server")]
+pub use server::*;
+use static_assertions::assert_impl_all;
 use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+pub use summary::*;
 pub use types::*;
+pub use verified::*;
+pub use verifier_cache::*;
+
+/// The path [`CsvGuest::open`] opens the CSV guest device at.
+pub const DEFAULT_GUEST_PATH: &str = "/dev/csv-guest";
 
 /// A handle to the CSV guest device.
+///
+/// The handle only owns the device's file, requests borrow it mutably and
+/// keep their buffers and RNG to themselves. It can thus be moved to, or
+/// shared behind a `Mutex` with, other threads.
 pub struct CsvGuest(File);
 
+assert_impl_all!(CsvGuest: Send, Sync);
+assert_impl_all!(AttestationReport: Send, Sync);
+assert_impl_all!(ReportSigner: Send, Sync);
+assert_impl_all!(VerifierCache: Send, Sync);
+assert_impl_all!(HmacVerifier: Send);
+assert_impl_all!(VerifiedReport: Send, Sync);
+
 impl CsvGuest {
     /// Generate a handle to the CSV guest platform via `/dev/csv-guest`.
-    pub fn open() -> std::io::Result<CsvGuest> {
-        let file = OpenOptions::new().read(true).open("/dev/csv-guest")?;
+    ///
+    /// The device is opened for reading and writing, as the report request
+    /// ioctl writes the request to the firmware, so that missing
+    /// permissions are reported here rather than by the first request.
+    ///
+    /// Returns [`Error::DeviceUnavailable`] if the device is missing, e.g.
+    /// without the `csv-guest` kernel module, or not readable and writable
+    /// by the user.
+    pub fn open() -> Result<CsvGuest, Error> {
+        Self::open_at(Path::new(DEFAULT_GUEST_PATH))
+    }
+
+    fn open_at(path: &Path) -> Result<CsvGuest, Error> {
+        Self::open_with(path, |path| {
+            OpenOptions::new().read(true).write(true).open(path)
+        })
+    }
+
+    fn open_with(path: &Path, open: fn(&Path) -> io::Result<File>) -> Result<CsvGuest, Error> {
+        let file = open(path).map_err(|e| Error::device_unavailable(path, e, "csv-guest"))?;
         Ok(CsvGuest(file))
     }
 
     /// Requests an attestation report from the HYGON Secure Processor.
+    ///
+    /// The report's `report_data` is computed from `data`, see
+    /// [`ReportDataSource`] for the layout of each source.
+    ///
+    /// A random mnonce is drawn if `mnonce` is `None`. Without the `rand`
+    /// feature, see [`Self::get_report_with_mnonce`].
+    ///
+    /// Returns [`Error::EmptyResponse`] if the request succeeded but the
+    /// firmware didn't write a response.
+    #[cfg(feature = "rand")]
     pub fn get_report(
         &mut self,
-        data: Option<[u8; 64]>,
+        data: impl ReportDataSource,
         mnonce: Option<[u8; 16]>,
     ) -> Result<(AttestationReport, ReportSigner), Error> {
-        let mut mnonce_value = [0u8; 16];
-        if let Some(mnonce) = mnonce {
-            mnonce_value = mnonce;
-        } else {
-            let mut rng = rand::thread_rng();
-            for element in &mut mnonce_value {
-                *element = rng.gen();
+        let (report_response, mnonce_value) =
+            self.request_report(Some(data.report_data()?), mnonce)?;
+
+        check_response(report_response, &mnonce_value)
+    }
+
+    /// Requests an attestation report for `mnonce`, e.g. derived from the
+    /// challenge of a verifier, as [`Self::get_report`] does.
+    ///
+    /// Unlike [`Self::get_report`], this is also available without the
+    /// `rand` feature.
+    pub fn get_report_with_mnonce(
+        &mut self,
+        data: impl ReportDataSource,
+        mnonce: [u8; 16],
+    ) -> Result<(AttestationReport, ReportSigner), Error> {
+        let (report_response, mnonce) =
+            self.request_report(Some(data.report_data()?), Some(mnonce))?;
+
+        check_response(report_response, &mnonce)
+    }
+
+    /// Requests an attestation report and returns the launch measurement
+    /// of the guest, once the report verified against `chain`, the chain of
+    /// the chip, see [`AttestationReport::verify_full`].
+    ///
+    /// This is the trustworthy counterpart of
+    /// [`AttestationReport::measurement`], for callers only after the
+    /// measurement. A `None` mnonce is drawn as for [`Self::get_report`].
+    pub fn get_measurement(
+        &mut self,
+        data: impl ReportDataSource,
+        mnonce: Option<[u8; 16]>,
+        chain: &kds::Chain,
+    ) -> Result<[u8; 32], Error> {
+        let (response, mnonce) = self.request_report(Some(data.report_data()?), mnonce)?;
+
+        verified_measurement(response, chain, &mnonce)
+    }
+
+    /// Requests an attestation report, leaving the response as the firmware
+    /// wrote it.
+    ///
+    /// Returns the response along with the mnonce it was requested with,
+    /// see [`random_mnonce`] for a `None` mnonce.
+    pub(crate) fn request_report(
+        &mut self,
+        data: Option<[u8; 64]>,
+        mnonce: Option<[u8; 16]>,
+    ) -> Result<(ReportRsp, [u8; 16]), Error> {
+        let mnonce_value = match mnonce {
+            Some(mnonce) => mnonce,
+            None => random_mnonce()?,
+        };
+
+        let report_request = ReportReq::new(data, mnonce_value)?;
+
+        let report_response = exchange(&report_request, |page| {
+            let mut guest_report_request = GuestReportRequest::new(page);
+            CSV_GET_REPORT.ioctl(&mut self.0, &mut guest_report_request)?;
+            Ok(())
+        })?;
+
+        Ok((report_response, mnonce_value))
+    }
+}
+
+/// Draws the mnonce of a request made without one.
+#[cfg(feature = "rand")]
+fn random_mnonce() -> Result<[u8; 16], Error> {
+    let mut mnonce = [0u8; 16];
+    rand::thread_rng().fill(&mut mnonce);
+    Ok(mnonce)
+}
+
+/// Without the `rand` feature, requests made without a mnonce fail with
+/// [`Error::Unsupported`].
+#[cfg(not(feature = "rand"))]
+fn random_mnonce() -> Result<[u8; 16], Error> {
+    Err(Error::Unsupported)
+}
+
+/// Hands `request` to `ioctl` in a response page, as the firmware expects
+/// it, and returns the response the firmware wrote over it.
+///
+/// Returns [`Error::EmptyResponse`] if `ioctl` succeeded without the
+/// firmware writing anything past the request, which would otherwise
+/// decode to a zero report.
+fn exchange(
+    request: &ReportReq,
+    ioctl: impl FnOnce(&mut [u8]) -> Result<(), Error>,
+) -> Result<ReportRsp, Error> {
+    let mut report_response = ReportRsp::default();
+
+    // Convert ReportReq to bytes
+    let request_bytes: &[u8] = unsafe {
+        let req_ptr = request as *const ReportReq as *const u8;
+        std::slice::from_raw_parts(req_ptr, std::mem::size_of::<ReportReq>())
+    };
+
+    let response_bytes: &mut [u8] = unsafe {
+        let rsp_ptr = &mut report_response as *mut ReportRsp as *mut u8;
+        std::slice::from_raw_parts_mut(rsp_ptr, std::mem::size_of::<ReportRsp>())
+    };
+
+    // Copy bytes from report_request to report_response
+    response_bytes[..request_bytes.len()].copy_from_slice(request_bytes);
+
+    ioctl(response_bytes)?;
+
+    if response_bytes[request_bytes.len()..]
+        .iter()
+        .all(|&b| b == 0)
+    {
+        return Err(Error::EmptyResponse);
+    }
+
+    Ok(report_response)
+}
+
+/// What reports are requested from, e.g. by a [`ReportCache`] or by the
+/// attestation server of the `server` feature.
+///
+/// [`CsvGuest`] requests them from the firmware, tests may serve recorded
+/// ones instead.
+pub trait ReportSource {
+    /// Requests the report for `data`, with a random mnonce if `mnonce` is
+    /// `None`.
+    ///
+    /// [`CsvGuest`] only draws one with the `rand` feature, it fails with
+    /// [`Error::Unsupported`] otherwise.
+    fn request(&mut self, data: [u8; 64], mnonce: Option<[u8; 16]>) -> Result<ReportRsp, Error>;
+}
+
+impl ReportSource for CsvGuest {
+    fn request(&mut self, data: [u8; 64], mnonce: Option<[u8; 16]>) -> Result<ReportRsp, Error> {
+        Ok(self.request_report(Some(data), mnonce)?.0)
+    }
+}
+
+/// The measurement of the firmware's response to the request for `mnonce`,
+/// once it verified against `chain`.
+fn verified_measurement(
+    response: ReportRsp,
+    chain: &kds::Chain,
+    mnonce: &[u8; 16],
+) -> Result<[u8; 32], Error> {
+    let verified = VerifiedReport::try_from((response, chain, mnonce))?;
+    Ok(verified.report().measurement())
+}
+
+/// Checks that the firmware's response answers the request for `mnonce`
+/// and restores its signer evidence.
+fn check_response(
+    response: ReportRsp,
+    mnonce: &[u8; 16],
+) -> Result<(AttestationReport, ReportSigner), Error> {
+    let signer = response.signer.verified(
+        mnonce,
+        &response.report.body.mnonce,
+        &response.report.anonce,
+    )?;
+
+    Ok((response.report, signer))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::{golden_response, make_report, ReportParams, GOLDEN_MNONCE};
+
+    #[test]
+    fn open_missing_device() {
+        let path = Path::new("/nonexistent/csv-guest");
+        match CsvGuest::open_at(path) {
+            Err(Error::DeviceUnavailable { path: p, hint }) => {
+                assert_eq!(p, path);
+                assert!(hint.contains("csv-guest kernel module"), "{hint}");
             }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
         }
+    }
 
-        let report_request = ReportReq::new(data, mnonce_value)?;
+    #[test]
+    fn open_inaccessible_device() {
+        let path = std::env::temp_dir().join(format!("csv-guest-{}", std::process::id()));
+        File::create(&path).unwrap();
 
-        let mut report_response = ReportRsp::default();
+        // The open fails as for a user without access, which root isn't
+        // denied.
+        let result = CsvGuest::open_with(&path, |_| Err(io::ErrorKind::PermissionDenied.into()));
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(Error::DeviceUnavailable { path: p, hint }) => {
+                assert_eq!(p, path);
+                assert!(hint.contains("read and write access"), "{hint}");
+                assert!(hint.contains("group"), "{hint}");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
 
-        // Convert ReportReq to bytes
-        let request_bytes: &[u8] = unsafe {
-            let req_ptr = &report_request as *const ReportReq as *const u8;
-            std::slice::from_raw_parts(req_ptr, std::mem::size_of::<ReportReq>())
-        };
+    #[test]
+    fn exchange_zero_page() {
+        let request = ReportReq::new(None, GOLDEN_MNONCE).unwrap();
 
-        let response_bytes: &mut [u8] = unsafe {
-            let rsp_ptr = &mut report_response as *mut ReportRsp as *mut u8;
-            std::slice::from_raw_parts_mut(rsp_ptr, std::mem::size_of::<ReportRsp>())
-        };
+        // A stubbed kernel zeroing the page, and one not touching it.
+        let zero = exchange(&request, |page| {
+            page.fill(0);
+            Ok(())
+        });
+        assert!(matches!(zero, Err(Error::EmptyResponse)));
+        assert!(matches!(
+            exchange(&request, |_| Ok(())),
+            Err(Error::EmptyResponse)
+        ));
+
+        let response = exchange(&request, |page| {
+            page.copy_from_slice(&golden_response().to_page());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(response.report.anonce, golden_response().report.anonce);
+    }
+
+    #[test]
+    fn verified_measurement_only() {
+        let (response, chain, mnonce) = make_report(ReportParams {
+            measure: [0x5a; 32],
+            ..Default::default()
+        });
+        let page = response.to_page();
 
-        // Copy bytes from report_request to report_response
-        response_bytes[..request_bytes.len()].copy_from_slice(request_bytes);
+        let measurement =
+            verified_measurement(ReportRsp::from_page(&page).unwrap(), &chain, &mnonce);
+        assert_eq!(measurement.unwrap(), [0x5a; 32]);
 
-        let mut guest_report_request = GuestReportRequest::new(response_bytes.as_ref());
+        let mut forged = ReportRsp::from_page(&page).unwrap();
+        forged.report.body.measure[0] ^= 1;
+        assert!(matches!(
+            verified_measurement(forged, &chain, &mnonce),
+            Err(Error::BadSignature)
+        ));
+    }
 
-        CSV_GET_REPORT.ioctl(&mut self.0, &mut guest_report_request)?;
+    #[test]
+    fn check_response_restores_signer() {
+        let (_, signer) = check_response(golden_response(), &GOLDEN_MNONCE).unwrap();
+        assert!(signer.sn.starts_with(b"NZA9T14052605"));
+    }
 
-        report_response.signer.verify(
-            &mnonce_value,
-            &report_response.report.body.mnonce,
-            &report_response.report.anonce,
-        )?;
+    #[test]
+    fn check_response_mnonce_mismatch() {
+        let requested = [0xa5u8; 16];
 
-        Ok((report_response.report, report_response.signer))
+        match check_response(golden_response(), &requested) {
+            Err(Error::MnonceMismatch { expected, got }) => {
+                assert_eq!(expected, requested);
+                assert_eq!(got, GOLDEN_MNONCE);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
     }
 }
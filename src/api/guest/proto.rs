@@ -0,0 +1,318 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Protobuf messages of the evidence, for sending it over gRPC attestation
+//! services.
+//!
+//! The messages are those of `proto/csv_guest.proto`. Every field is
+//! carried as the firmware or the KDS encoded it: the report is still
+//! masked with its anonce and the signer evidence isn't restored, so that
+//! the receiver verifies exactly what the firmware produced.
+//!
+//! ```
+//! use codicon::Decoder;
+//! use csv_rs::{
+//!     api::guest::{proto, Evidence},
+//!     testutil::{GOLDEN_EVIDENCE, GOLDEN_HSK_CEK, GOLDEN_MNONCE},
+//! };
+//! use prost::Message;
+//!
+//! let evidence = Evidence::decode(&mut &[GOLDEN_EVIDENCE, GOLDEN_HSK_CEK].concat()[..], ())?;
+//! let bytes = evidence.to_proto().encode_to_vec();
+//!
+//! let message = proto::Report::decode(&bytes[..]).expect("a valid message");
+//! let received = Evidence::from_proto(&message)?;
+//! received.verify(&GOLDEN_MNONCE)?;
+//! # Ok::<(), csv_rs::error::Error>(())
+//! ```
+
+use super::{Body, Evidence, GuestPolicy};
+use crate::{
+    certs::{builtin::HRK, ca, kds},
+    crypto::sig::ecdsa,
+    error::*,
+};
+
+use codicon::{Decoder, Encoder};
+use std::io;
+
+/// An [`AttestationReport`](super::AttestationReport), its fields still masked with the anonce.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AttestationReport {
+    #[prost(bytes = "vec", tag = "1")]
+    pub user_pubkey_digest: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub vm_id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub vm_version: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub report_data: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub mnonce: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub measure: Vec<u8>,
+    #[prost(uint32, tag = "7")]
+    pub policy: u32,
+    #[prost(uint32, tag = "8")]
+    pub sig_usage: u32,
+    #[prost(uint32, tag = "9")]
+    pub sig_algo: u32,
+    #[prost(uint32, tag = "10")]
+    pub anonce: u32,
+    /// The little endian `r` of the signature, 72 bytes.
+    #[prost(bytes = "vec", tag = "11")]
+    pub sig_r: Vec<u8>,
+    /// The little endian `s` of the signature, 72 bytes.
+    #[prost(bytes = "vec", tag = "12")]
+    pub sig_s: Vec<u8>,
+}
+
+/// A [`ReportSigner`](super::ReportSigner), not restored.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReportSigner {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pek_cert: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub sn: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub reserved: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub mac: Vec<u8>,
+}
+
+/// A [`kds::Chain`], the HSK and the CEK as the KDS encodes them.
+///
+/// The HRK is left out, as in the canonical encoding of [`Evidence`]: a
+/// chain is converted back with the builtin HRK, lest the sender choose the
+/// root the receiver trusts.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Chain {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hsk: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub cek: Vec<u8>,
+}
+
+/// An [`Evidence`], everything a verifier needs to check a report.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Report {
+    #[prost(message, optional, tag = "1")]
+    pub report: Option<AttestationReport>,
+    #[prost(message, optional, tag = "2")]
+    pub signer: Option<ReportSigner>,
+    #[prost(message, optional, tag = "3")]
+    pub chain: Option<Chain>,
+}
+
+/// Copies a bytes field of a message into its array.
+///
+/// Returns [`Error::InvalidLen`] if the field isn't exactly `N` bytes long.
+fn array<const N: usize>(bytes: &[u8]) -> Result<[u8; N], Error> {
+    bytes.try_into().map_err(|_| Error::InvalidLen)
+}
+
+/// The message of a [`Report`], or an error naming the missing `field`.
+fn required<'a, T>(message: &'a Option<T>, field: &str) -> Result<&'a T, Error> {
+    message.as_ref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("The report message lacks its {field}"),
+        )
+        .into()
+    })
+}
+
+impl super::AttestationReport {
+    /// Converts the report to its protobuf message.
+    pub fn to_proto(&self) -> AttestationReport {
+        let body = &self.body;
+        AttestationReport {
+            user_pubkey_digest: body.user_pubkey_digest.to_vec(),
+            vm_id: body.vm_id.to_vec(),
+            vm_version: body.vm_version.to_vec(),
+            report_data: body.report_data.to_vec(),
+            mnonce: body.mnonce.to_vec(),
+            measure: body.measure.to_vec(),
+            policy: body.policy.bits(),
+            sig_usage: self.sig_usage,
+            sig_algo: self.sig_algo,
+            anonce: self.anonce,
+            sig_r: self.sig.r.to_vec(),
+            sig_s: self.sig.s.to_vec(),
+        }
+    }
+
+    /// Converts the protobuf message of a report back.
+    ///
+    /// Returns [`Error::InvalidLen`] if a bytes field doesn't have the size
+    /// of its field in the report.
+    pub fn from_proto(message: &AttestationReport) -> Result<Self, Error> {
+        Ok(Self {
+            body: Body {
+                user_pubkey_digest: array(&message.user_pubkey_digest)?,
+                vm_id: array(&message.vm_id)?,
+                vm_version: array(&message.vm_version)?,
+                report_data: array(&message.report_data)?,
+                mnonce: array(&message.mnonce)?,
+                measure: array(&message.measure)?,
+                policy: GuestPolicy::from(message.policy),
+            },
+            sig_usage: message.sig_usage,
+            sig_algo: message.sig_algo,
+            anonce: message.anonce,
+            sig: ecdsa::Signature {
+                r: array(&message.sig_r)?,
+                s: array(&message.sig_s)?,
+            },
+        })
+    }
+}
+
+impl super::ReportSigner {
+    /// Converts the signer evidence to its protobuf message.
+    pub fn to_proto(&self) -> ReportSigner {
+        ReportSigner {
+            pek_cert: self.pek_cert.to_vec(),
+            sn: self.sn.to_vec(),
+            reserved: self.reserved.to_vec(),
+            mac: self.mac.to_vec(),
+        }
+    }
+
+    /// Converts the protobuf message of signer evidence back.
+    ///
+    /// Returns [`Error::InvalidLen`] if a bytes field doesn't have the size
+    /// of its field in the evidence.
+    pub fn from_proto(message: &ReportSigner) -> Result<Self, Error> {
+        Ok(Self {
+            pek_cert: array(&message.pek_cert)?,
+            sn: array(&message.sn)?,
+            reserved: array(&message.reserved)?,
+            mac: array(&message.mac)?,
+        })
+    }
+}
+
+impl kds::Chain {
+    /// Converts the chain to its protobuf message, without the HRK.
+    pub fn to_proto(&self) -> Chain {
+        Chain {
+            hsk: encode(&self.ca.hsk),
+            cek: encode(&self.cek),
+        }
+    }
+
+    /// Converts the protobuf message of a chain back, with the builtin HRK
+    /// as its root.
+    ///
+    /// Fails if a certificate doesn't decode, or is followed by other bytes.
+    pub fn from_proto(message: &Chain) -> Result<Self, Error> {
+        Ok(Self {
+            ca: ca::Chain {
+                hsk: decode_all(&message.hsk)?,
+                hrk: ca::Certificate::decode(&mut &HRK[..], ())?,
+            },
+            cek: decode_all(&message.cek)?,
+        })
+    }
+}
+
+/// Encodes a certificate as the KDS does.
+fn encode(cert: &impl Encoder<(), Error = io::Error>) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    cert.encode(&mut encoded, ())
+        .expect("encoding into a Vec can't fail");
+    encoded
+}
+
+/// Decodes a certificate from the whole of `bytes`.
+fn decode_all<T: Decoder<(), Error = io::Error>>(mut bytes: &[u8]) -> Result<T, Error> {
+    let decoded = T::decode(&mut bytes, ())?;
+    if !bytes.is_empty() {
+        return Err(Error::InvalidLen);
+    }
+
+    Ok(decoded)
+}
+
+impl Evidence {
+    /// Converts the evidence to its protobuf message.
+    pub fn to_proto(&self) -> Report {
+        Report {
+            report: Some(self.report.to_proto()),
+            signer: Some(self.signer.to_proto()),
+            chain: Some(self.chain.to_proto()),
+        }
+    }
+
+    /// Converts the protobuf message of evidence back, see the conversions
+    /// of its parts.
+    ///
+    /// Fails with an I/O error of kind [`io::ErrorKind::InvalidData`] if a
+    /// part is missing.
+    pub fn from_proto(message: &Report) -> Result<Self, Error> {
+        Ok(Self {
+            report: super::AttestationReport::from_proto(required(&message.report, "report")?)?,
+            signer: super::ReportSigner::from_proto(required(&message.signer, "signer")?)?,
+            chain: kds::Chain::from_proto(required(&message.chain, "chain")?)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::{GOLDEN_EVIDENCE, GOLDEN_HSK_CEK, GOLDEN_MNONCE};
+    use prost::Message;
+
+    fn golden() -> Evidence {
+        Evidence::decode(&mut &[GOLDEN_EVIDENCE, GOLDEN_HSK_CEK].concat()[..], ()).unwrap()
+    }
+
+    fn canonical(evidence: &Evidence) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        evidence.encode(&mut encoded, ()).unwrap();
+        encoded
+    }
+
+    #[test]
+    fn round_trip() {
+        let evidence = golden();
+
+        let bytes = evidence.to_proto().encode_to_vec();
+        let message = Report::decode(&bytes[..]).unwrap();
+        let received = Evidence::from_proto(&message).unwrap();
+
+        assert_eq!(canonical(&received), canonical(&evidence));
+        assert_eq!(received.report.to_proto(), evidence.report.to_proto());
+        assert_eq!(encode(&received.chain.ca.hrk), HRK);
+        received.verify(&GOLDEN_MNONCE).unwrap();
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let evidence = golden();
+
+        let mut report = evidence.report.to_proto();
+        report.measure.pop();
+        assert!(matches!(
+            super::super::AttestationReport::from_proto(&report),
+            Err(Error::InvalidLen)
+        ));
+
+        let mut chain = evidence.chain.to_proto();
+        chain.cek.push(0);
+        assert!(matches!(
+            kds::Chain::from_proto(&chain),
+            Err(Error::InvalidLen)
+        ));
+
+        let mut message = evidence.to_proto();
+        message.signer = None;
+        match Evidence::from_proto(&message) {
+            Err(Error::IoError(e)) => assert!(e.to_string().contains("signer"), "{e}"),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verification of evidence by a remote verifier service.
+
+use super::evidence::Evidence;
+use crate::error::*;
+
+use codicon::Encoder;
+use hyper::{body::HttpBody as _, header, Body, Client, Method, Request};
+use hyper_tls::HttpsConnector;
+use serde::Deserialize;
+use std::{io, time::Duration};
+
+/// The longest answer of a verifier read, longer verdicts are malformed.
+const MAX_RESPONSE_LEN: usize = 64 * 1024;
+
+/// How long [`verify_remote`] waits on the verifier, see
+/// [`verify_remote_with_timeout`].
+pub const DEFAULT_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The verdict of a remote verifier on some evidence.
+///
+/// The verifier answers with a JSON object such as
+/// `{"verified": false, "reason": "measurement mismatch"}`, `reason` being
+/// optional.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Verdict {
+    /// Whether the evidence verified under the verifier's policy.
+    pub verified: bool,
+
+    /// Why the verifier came to its verdict, if told.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Submits `evidence` to the verifier at `endpoint` and returns its verdict.
+///
+/// The evidence is POSTed in its canonical encoding, see [`Evidence`], as
+/// `application/octet-stream`. The verifier has
+/// [`DEFAULT_REMOTE_TIMEOUT`] to answer.
+///
+/// Fails with:
+/// - [`Error::RemoteHttp`] if the verifier answers with a status other than
+///   a success, its body cut to 64 KiB
+/// - [`Error::MalformedVerdict`] if the answer isn't a [`Verdict`], or is
+///   longer than 64 KiB
+/// - [`Error::IoError`] if the endpoint is invalid or can't be reached, of
+///   kind [`io::ErrorKind::TimedOut`] if the verifier doesn't answer in time
+pub async fn verify_remote(evidence: &Evidence, endpoint: &str) -> Result<Verdict, Error> {
+    verify_remote_with_timeout(evidence, endpoint, DEFAULT_REMOTE_TIMEOUT).await
+}
+
+/// Submits `evidence` to the verifier at `endpoint` as [`verify_remote`]
+/// does, waiting up to `timeout` for the verifier's whole answer.
+pub async fn verify_remote_with_timeout(
+    evidence: &Evidence,
+    endpoint: &str,
+    timeout: Duration,
+) -> Result<Verdict, Error> {
+    tokio::time::timeout(timeout, submit(evidence, endpoint))
+        .await
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("The verifier didn't answer within {timeout:?}"),
+            )
+        })?
+}
+
+async fn submit(evidence: &Evidence, endpoint: &str) -> Result<Verdict, Error> {
+    let mut encoded = Vec::new();
+    evidence.encode(&mut encoded, ())?;
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(endpoint)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .body(Body::from(encoded))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let client = Client::builder().build::<_, Body>(HttpsConnector::new());
+    let mut response = client.request(request).await.map_err(io::Error::other)?;
+
+    // The rest of a longer answer is left unread.
+    let mut body = Vec::new();
+    let mut truncated = false;
+    while let Some(chunk) = response.body_mut().data().await {
+        let chunk = chunk.map_err(io::Error::other)?;
+        let room = MAX_RESPONSE_LEN - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::RemoteHttp {
+            status: status.as_u16(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        });
+    }
+
+    if truncated {
+        return Err(Error::MalformedVerdict(format!(
+            "The verdict is longer than {MAX_RESPONSE_LEN} bytes"
+        )));
+    }
+
+    serde_json::from_slice(&body).map_err(|e| Error::MalformedVerdict(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil;
+    use codicon::Decoder;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    fn recorded() -> Evidence {
+        let encoded = [testutil::GOLDEN_EVIDENCE, testutil::GOLDEN_HSK_CEK].concat();
+        Evidence::decode(&mut &encoded[..], ()).unwrap()
+    }
+
+    /// Serves one request with `status` and `body`, returning the endpoint
+    /// and the request as received.
+    fn serve_once(status: &'static str, body: &str) -> (String, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/verify", listener.local_addr().unwrap());
+        let body = body.to_owned();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let len = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            line.to_ascii_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(str::to_owned)
+                        })
+                        .map_or(0, |len| len.trim().parse::<usize>().unwrap());
+                    if request.len() >= end + 4 + len {
+                        break;
+                    }
+                }
+            }
+
+            // The client may hang up before reading a long body.
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            request
+        });
+
+        (endpoint, server)
+    }
+
+    fn verify(endpoint: &str) -> Result<Verdict, Error> {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(verify_remote(&recorded(), endpoint))
+    }
+
+    #[test]
+    fn verdict() {
+        let (endpoint, server) = serve_once(
+            "200 OK",
+            r#"{"verified": false, "reason": "measurement mismatch"}"#,
+        );
+
+        let verdict = verify(&endpoint).unwrap();
+        assert!(!verdict.verified);
+        assert_eq!(verdict.reason.as_deref(), Some("measurement mismatch"));
+
+        let request = server.join().unwrap();
+        let mut encoded = Vec::new();
+        recorded().encode(&mut encoded, ()).unwrap();
+        assert!(request.starts_with(b"POST /verify "));
+        assert!(request.ends_with(&encoded));
+    }
+
+    #[test]
+    fn http_error() {
+        let (endpoint, server) = serve_once("503 Service Unavailable", "try later");
+
+        match verify(&endpoint) {
+            Err(Error::RemoteHttp { status, body }) => {
+                assert_eq!(status, 503);
+                assert_eq!(body, "try later");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn malformed_verdict() {
+        let (endpoint, server) = serve_once("200 OK", r#"{"verdict": "ok"}"#);
+
+        assert!(matches!(verify(&endpoint), Err(Error::MalformedVerdict(_))));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn long_answer() {
+        let long = format!("{}{{}}", " ".repeat(MAX_RESPONSE_LEN));
+        let (endpoint, server) = serve_once("200 OK", &long);
+        assert!(matches!(verify(&endpoint), Err(Error::MalformedVerdict(_))));
+        server.join().unwrap();
+
+        let (endpoint, server) = serve_once("500 Internal Server Error", &long);
+        match verify(&endpoint) {
+            Err(Error::RemoteHttp { status, body }) => {
+                assert_eq!(status, 500);
+                assert_eq!(body.len(), MAX_RESPONSE_LEN);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn silent_verifier() {
+        // The connection is taken by the backlog, but never answered.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/verify", listener.local_addr().unwrap());
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(verify_remote_with_timeout(
+            &recorded(),
+            &endpoint,
+            Duration::from_millis(100),
+        ));
+        match result {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A cache of the last verified report, for monitors attesting the same
+//! guest repeatedly.
+
+use super::{ReportReq, ReportSource, VerifiedReport};
+use crate::{certs::kds, error::*};
+
+use std::time::{Duration, Instant};
+
+/// Whether [`ReportCache::get_or_attest`] verified the report it returned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// The guest answered with the report verified last, which wasn't
+    /// verified again.
+    Hit,
+    /// The report was verified in full, and cached.
+    Miss,
+}
+
+/// The report verified last, with the request it answered.
+struct Entry {
+    data: [u8; 64],
+    mnonce: [u8; 16],
+    page: Vec<u8>,
+    report: VerifiedReport,
+    verified_at: Instant,
+}
+
+/// Attests a guest against the chain of its chip, skipping the verification
+/// of a report that was already verified.
+///
+/// Every call requests a report from the guest. The firmware masks each new
+/// report with a new anonce, so a report whose anonce hasn't changed since
+/// the last verified one is that very report served again, e.g. by an agent
+/// caching its responses: if it also answers the same report data and
+/// mnonce, byte for byte, and was verified less than `max_age` ago, it isn't
+/// verified again. Any other report, one with a new anonce in particular,
+/// is verified in full and replaces the cached one.
+///
+/// **Freshness:** a cached report proves nothing about the guest past the
+/// moment the firmware produced it, and it is only served again for the
+/// mnonce it was requested with, i.e. for a challenge that isn't fresh. A
+/// hit is thus only acceptable as long as the verifier's policy tolerates a
+/// report that old, which `max_age` bounds. Monitors needing fresh evidence
+/// on every call pass a fresh mnonce, and always miss.
+pub struct ReportCache {
+    chain: kds::Chain,
+    max_age: Duration,
+    entry: Option<Entry>,
+}
+
+impl ReportCache {
+    /// Creates an empty cache verifying reports against `chain`, the chain
+    /// of the guest's chip, and serving them again for at most `max_age`.
+    pub fn new(chain: kds::Chain, max_age: Duration) -> Self {
+        Self {
+            chain,
+            max_age,
+            entry: None,
+        }
+    }
+
+    /// Requests the report for `data` and `mnonce` from `guest`, verifying
+    /// it unless it is the cached one, see [`ReportCache`].
+    ///
+    /// A report is verified as [`VerifiedReport`] does and must answer the
+    /// request, see [`super::AttestationReport::verify_request_binding`].
+    /// Failing reports aren't cached and leave the cache as is.
+    pub fn get_or_attest(
+        &mut self,
+        guest: &mut impl ReportSource,
+        data: [u8; 64],
+        mnonce: [u8; 16],
+    ) -> Result<(&VerifiedReport, CacheStatus), Error> {
+        self.get_or_attest_at(guest, data, mnonce, Instant::now())
+    }
+
+    /// Forgets the cached report, so that the next one is verified.
+    pub fn invalidate(&mut self) {
+        self.entry = None;
+    }
+
+    fn get_or_attest_at(
+        &mut self,
+        guest: &mut impl ReportSource,
+        data: [u8; 64],
+        mnonce: [u8; 16],
+        now: Instant,
+    ) -> Result<(&VerifiedReport, CacheStatus), Error> {
+        let response = guest.request(data, Some(mnonce))?;
+        let page = response.to_page();
+
+        let hit = self.entry.as_ref().is_some_and(|entry| {
+            entry.report.report().anonce == response.report.anonce
+                && entry.data == data
+                && entry.mnonce == mnonce
+                && entry.page == page
+                && now.duration_since(entry.verified_at) < self.max_age
+        });
+        if hit {
+            let entry = self.entry.as_ref().unwrap();
+            return Ok((&entry.report, CacheStatus::Hit));
+        }
+
+        response
+            .report
+            .verify_request_binding(&ReportReq::new(Some(data), mnonce)?)?;
+        let report = VerifiedReport::try_from((response, &self.chain, &mnonce))?;
+
+        let entry = self.entry.insert(Entry {
+            data,
+            mnonce,
+            page,
+            report,
+            verified_at: now,
+        });
+        Ok((&entry.report, CacheStatus::Miss))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::guest::ReportRsp,
+        testutil::{make_report, ReportParams, TestChip},
+    };
+
+    const DATA: [u8; 64] = [0x42; 64];
+
+    /// A guest serving the pages it's given, in turn.
+    struct Recorded {
+        pages: Vec<Vec<u8>>,
+        requests: usize,
+    }
+
+    impl ReportSource for Recorded {
+        fn request(&mut self, _: [u8; 64], _: Option<[u8; 16]>) -> Result<ReportRsp, Error> {
+            let page = &self.pages[self.requests.min(self.pages.len() - 1)];
+            self.requests += 1;
+            ReportRsp::from_page(page)
+        }
+    }
+
+    fn generated(anonce: u32) -> (Vec<u8>, kds::Chain, [u8; 16]) {
+        let (response, chain, mnonce) = make_report(ReportParams {
+            report_data: DATA,
+            anonce,
+            ..Default::default()
+        });
+        (response.to_page(), chain, mnonce)
+    }
+
+    #[test]
+    fn hit_on_same_anonce() {
+        let (page, chain, mnonce) = generated(1);
+        let mut guest = Recorded {
+            pages: vec![page],
+            requests: 0,
+        };
+        let mut cache = ReportCache::new(chain, Duration::from_secs(60));
+
+        let now = Instant::now();
+        let (_, status) = cache
+            .get_or_attest_at(&mut guest, DATA, mnonce, now)
+            .unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+
+        let (report, status) = cache
+            .get_or_attest_at(&mut guest, DATA, mnonce, now + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(status, CacheStatus::Hit);
+        assert_eq!(report.report().anonce, 1);
+        assert_eq!(guest.requests, 2);
+
+        // Too old to be served again.
+        let (_, status) = cache
+            .get_or_attest_at(&mut guest, DATA, mnonce, now + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+
+        cache.invalidate();
+        let (_, status) = cache.get_or_attest(&mut guest, DATA, mnonce).unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+    }
+
+    #[test]
+    fn miss_on_new_anonce() {
+        let chip = TestChip::generate();
+        let params = |anonce| ReportParams {
+            report_data: DATA,
+            anonce,
+            ..Default::default()
+        };
+        let mut guest = Recorded {
+            pages: vec![chip.report(params(1)).to_page()],
+            requests: 0,
+        };
+        let mut cache = ReportCache::new(chip.chain(), Duration::from_secs(60));
+        let mnonce = ReportParams::default().mnonce;
+        cache.get_or_attest(&mut guest, DATA, mnonce).unwrap();
+
+        // A new report of the chip, with an advanced anonce.
+        let advanced = chip.report(params(2)).to_page();
+        guest.pages = vec![advanced.clone()];
+        let (report, status) = cache.get_or_attest(&mut guest, DATA, mnonce).unwrap();
+        assert_eq!(status, CacheStatus::Miss);
+        assert_eq!(report.report().anonce, 2);
+        let (_, status) = cache.get_or_attest(&mut guest, DATA, mnonce).unwrap();
+        assert_eq!(status, CacheStatus::Hit);
+
+        // A report with the cached anonce but another content is verified,
+        // fails, and leaves the verified one cached.
+        let mut forged = chip.report(params(2));
+        forged.report.body.measure[0] ^= 1;
+        guest.pages = vec![forged.to_page()];
+        assert!(cache.get_or_attest(&mut guest, DATA, mnonce).is_err());
+        guest.pages = vec![advanced];
+        let (_, status) = cache.get_or_attest(&mut guest, DATA, mnonce).unwrap();
+        assert_eq!(status, CacheStatus::Hit);
+    }
+
+    #[test]
+    fn miss_on_other_request() {
+        let (page, chain, mnonce) = generated(1);
+        let mut guest = Recorded {
+            pages: vec![page],
+            requests: 0,
+        };
+        let mut cache = ReportCache::new(chain, Duration::from_secs(60));
+        cache.get_or_attest(&mut guest, DATA, mnonce).unwrap();
+
+        // The same report doesn't answer a fresh challenge.
+        assert!(matches!(
+            cache.get_or_attest(&mut guest, DATA, [0u8; 16]),
+            Err(Error::MnonceMismatch { .. })
+        ));
+        assert!(matches!(
+            cache.get_or_attest(&mut guest, [0u8; 64], mnonce),
+            Err(Error::ReportDataMismatch)
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The ways a guest binds its data to the `report_data` of its reports.
+
+use crate::error::*;
+
+use openssl::hash::{Hasher, MessageDigest};
+use std::io::{self, Write};
+
+/// A binding of application data to the 64 bytes of `report_data`.
+///
+/// The verifier recomputes `report_data` from the same source to check the
+/// binding. Applications with their own layout implement the trait, e.g. to
+/// bind a public key along with a nonce:
+///
+/// ```
+/// use csv_rs::api::guest::ReportDataSource;
+///
+/// /// A 32 bytes public key, then a 32 bytes nonce.
+/// struct KeyAndNonce([u8; 32], [u8; 32]);
+///
+/// impl ReportDataSource for KeyAndNonce {
+///     fn report_data(&self) -> Result<[u8; 64], csv_rs::error::Error> {
+///         let mut data = [0u8; 64];
+///         data[..32].copy_from_slice(&self.0);
+///         data[32..].copy_from_slice(&self.1);
+///         Ok(data)
+///     }
+/// }
+///
+/// let data = KeyAndNonce([1; 32], [2; 32]).report_data().unwrap();
+/// assert_eq!(data[31..33], [1, 2]);
+/// ```
+pub trait ReportDataSource {
+    /// The 64 bytes to place in the report's `report_data`.
+    fn report_data(&self) -> Result<[u8; 64], Error>;
+}
+
+/// The raw bytes are the report data, as is.
+impl ReportDataSource for [u8; 64] {
+    fn report_data(&self) -> Result<[u8; 64], Error> {
+        Ok(*self)
+    }
+}
+
+/// `Some` raw bytes are the report data as is, `None` is 64 zero bytes.
+impl ReportDataSource for Option<[u8; 64]> {
+    fn report_data(&self) -> Result<[u8; 64], Error> {
+        Ok(self.unwrap_or([0u8; 64]))
+    }
+}
+
+/// The SM3 digest of a blob of any size.
+///
+/// The layout of the report data is:
+///
+/// | Offset | Size | Content             |
+/// |--------|------|---------------------|
+/// | 0      | 32   | SM3 digest of blob  |
+/// | 32     | 32   | zero                |
+///
+/// Computing it fails with [`Error::UnsupportedBackend`] if the OpenSSL
+/// backend lacks SM3.
+pub struct Sm3Blob<T: AsRef<[u8]>>(pub T);
+
+impl<T: AsRef<[u8]>> ReportDataSource for Sm3Blob<T> {
+    fn report_data(&self) -> Result<[u8; 64], Error> {
+        let mut hasher = ReportDataHasher::new()?;
+        hasher.update(self.0.as_ref())?;
+        hasher.finalize()
+    }
+}
+
+/// Computes the report data of [`Sm3Blob`] from a blob fed in chunks, e.g.
+/// a file too large to be loaded at once.
+///
+/// ```no_run
+/// use csv_rs::api::guest::ReportDataHasher;
+///
+/// let mut hasher = ReportDataHasher::new()?;
+/// std::io::copy(&mut std::fs::File::open("model.bin")?, &mut hasher)?;
+/// let data = hasher.finalize()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ReportDataHasher(Hasher);
+
+impl ReportDataHasher {
+    /// Starts hashing an empty blob.
+    ///
+    /// Returns [`Error::UnsupportedBackend`] if the OpenSSL backend lacks
+    /// SM3.
+    pub fn new() -> Result<Self, Error> {
+        crate::crypto::check_backend()?;
+        Ok(Self(Hasher::new(MessageDigest::sm3())?))
+    }
+
+    /// Appends `chunk` to the blob.
+    pub fn update(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        Ok(self.0.update(chunk)?)
+    }
+
+    /// The report data of the blob, laid out as for [`Sm3Blob`].
+    pub fn finalize(mut self) -> Result<[u8; 64], Error> {
+        let digest = self.0.finish()?;
+
+        let mut data = [0u8; 64];
+        data[..32].copy_from_slice(&digest);
+        Ok(data)
+    }
+}
+
+impl Write for ReportDataHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf).map_err(io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Where the data bound to a report is placed in its 64 bytes of
+/// `report_data`, for guests and verifiers to agree on it.
+///
+/// The firmware has no layout of its own: it reports the 64 bytes the guest
+/// requested the report with as is, i.e. [`Self::Raw`]. The crate binds
+/// nonces [`Self::Sm3LeftAligned`], see [`Sm3Blob`] and
+/// [`super::AttestationReport::verify_nonce`]; other verifiers may expect
+/// the digest right-aligned.
+///
+/// ```
+/// use csv_rs::api::guest::{ReportDataLayout, ReportReq};
+///
+/// let nonce = b"challenge from the verifier";
+/// let layout = ReportDataLayout::Sm3RightAligned;
+///
+/// // The guest requests its report with the data of the layout...
+/// let data = layout.report_data(nonce).unwrap();
+/// assert_eq!(data[..32], [0u8; 32]);
+///
+/// // ...the verifier checks the report against the same request.
+/// let req = ReportReq::with_layout(nonce, layout, [0x42; 16]).unwrap();
+/// assert_eq!(req.data, data);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReportDataLayout {
+    /// The data is the report data as is, zero padded to 64 bytes.
+    Raw,
+    /// The SM3 digest of the data in the first 32 bytes, then 32 zero
+    /// bytes, as for [`Sm3Blob`].
+    Sm3LeftAligned,
+    /// 32 zero bytes, then the SM3 digest of the data in the last 32 bytes.
+    Sm3RightAligned,
+}
+
+impl ReportDataLayout {
+    /// The report data binding `data` in the layout.
+    ///
+    /// Returns [`Error::InvalidLen`] for [`Self::Raw`] data longer than 64
+    /// bytes.
+    pub fn report_data(self, data: &[u8]) -> Result<[u8; 64], Error> {
+        let mut report_data = [0u8; 64];
+        match self {
+            Self::Raw => report_data
+                .get_mut(..data.len())
+                .ok_or(Error::InvalidLen)?
+                .copy_from_slice(data),
+            Self::Sm3LeftAligned => report_data = Sm3Blob(data).report_data()?,
+            Self::Sm3RightAligned => {
+                report_data[32..].copy_from_slice(&Sm3Blob(data).report_data()?[..32])
+            }
+        }
+
+        Ok(report_data)
+    }
+}
+
+/// A type any 64 bytes or fewer decode to, for reading structured
+/// `report_data`, see [`AttestationReport::report_data_as`].
+///
+/// The value is a bitwise copy of the leading `size_of::<T>()` bytes of
+/// the report data, read unaligned, so its alignment doesn't matter.
+///
+/// # Safety
+///
+/// Implementors must be at most 64 bytes, `#[repr(C)]` (or a primitive or
+/// an array of implementors), without padding, and valid for every bit
+/// pattern: fields of integers and byte arrays only, no `bool`, `char`,
+/// enum, reference or pointer. Integers are read in the byte order of the
+/// host.
+///
+/// [`AttestationReport::report_data_as`]: super::AttestationReport::report_data_as
+pub unsafe trait FromBytes: Copy {}
+
+unsafe impl FromBytes for u8 {}
+unsafe impl FromBytes for u16 {}
+unsafe impl FromBytes for u32 {}
+unsafe impl FromBytes for u64 {}
+unsafe impl FromBytes for u128 {}
+unsafe impl FromBytes for i8 {}
+unsafe impl FromBytes for i16 {}
+unsafe impl FromBytes for i32 {}
+unsafe impl FromBytes for i64 {}
+unsafe impl FromBytes for i128 {}
+unsafe impl<T: FromBytes, const N: usize> FromBytes for [T; N] {}
+
+/// Decodes a `T` from the leading bytes of `data`.
+///
+/// Returns [`Error::InvalidLen`] if `T` is longer than `data`.
+pub(super) fn from_bytes<T: FromBytes>(data: &[u8]) -> Result<T, Error> {
+    if std::mem::size_of::<T>() > data.len() {
+        return Err(Error::InvalidLen);
+    }
+
+    // SAFETY: `data` holds at least the bytes of a `T`, and every bit
+    // pattern of them is a valid `T` by the contract of `FromBytes`.
+    Ok(unsafe { std::ptr::read_unaligned(data.as_ptr().cast::<T>()) })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn raw() {
+        let mut raw = [0u8; 64];
+        raw[0] = 1;
+        raw[63] = 2;
+
+        assert_eq!(raw.report_data().unwrap(), raw);
+        assert_eq!(Some(raw).report_data().unwrap(), raw);
+        assert_eq!(None.report_data().unwrap(), [0u8; 64]);
+    }
+
+    #[test]
+    fn sm3_blob() {
+        let data = Sm3Blob(b"abc").report_data().unwrap();
+
+        assert_eq!(
+            hex::encode(&data[..32]),
+            "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0"
+        );
+        assert_eq!(data[32..], [0u8; 32]);
+        assert_eq!(Sm3Blob(b"abc".to_vec()).report_data().unwrap(), data);
+    }
+
+    #[test]
+    fn layouts() {
+        let nonce = b"abc";
+        let digest = &Sm3Blob(nonce).report_data().unwrap()[..32];
+
+        let raw = ReportDataLayout::Raw.report_data(nonce).unwrap();
+        assert_eq!(&raw[..3], nonce);
+        assert_eq!(raw[3..], [0u8; 61]);
+        assert_eq!(
+            ReportDataLayout::Raw.report_data(&[7; 64]).unwrap(),
+            [7; 64]
+        );
+        assert!(matches!(
+            ReportDataLayout::Raw.report_data(&[7; 65]),
+            Err(Error::InvalidLen)
+        ));
+
+        let left = ReportDataLayout::Sm3LeftAligned.report_data(nonce).unwrap();
+        assert_eq!(left, Sm3Blob(nonce).report_data().unwrap());
+
+        let right = ReportDataLayout::Sm3RightAligned
+            .report_data(nonce)
+            .unwrap();
+        assert_eq!(right[..32], [0u8; 32]);
+        assert_eq!(&right[32..], digest);
+    }
+
+    #[test]
+    fn chunked_hasher() {
+        let blob: Vec<u8> = (0..10_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let one_shot = Sm3Blob(&blob).report_data().unwrap();
+
+        for chunk_size in [1, 64, 1000, 4096] {
+            let mut hasher = ReportDataHasher::new().unwrap();
+            for chunk in blob.chunks(chunk_size) {
+                hasher.update(chunk).unwrap();
+            }
+            assert_eq!(hasher.finalize().unwrap(), one_shot);
+        }
+
+        let mut hasher = ReportDataHasher::new().unwrap();
+        io::copy(&mut &blob[..], &mut hasher).unwrap();
+        assert_eq!(hasher.finalize().unwrap(), one_shot);
+
+        assert_eq!(
+            ReportDataHasher::new().unwrap().finalize().unwrap(),
+            Sm3Blob(b"").report_data().unwrap()
+        );
+    }
+}
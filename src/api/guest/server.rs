@@ -0,0 +1,318 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A local attestation agent serving reports over a Unix socket.
+//!
+//! The agent owns the CSV guest device, so that processes without access to
+//! it can still obtain reports. The protocol is, for every request on a
+//! connection:
+//!
+//! | Direction | Size | Content                                        |
+//! |-----------|------|------------------------------------------------|
+//! | request   | 64   | the report data                                |
+//! | request   | 1    | `1` if a mnonce follows, `0` otherwise         |
+//! | request   | 16   | the mnonce, if any                             |
+//! | response  | 1    | `0` on success, `1` on failure                 |
+//! | response  | 4096 | on success, the [`ReportRsp`] as a page        |
+//! | response  | 4    | on failure, the little endian size of...       |
+//! | response  | n    | ...the UTF-8 description of the failure        |
+//!
+//! The response is the one the firmware wrote, its signer evidence isn't
+//! restored.
+//!
+//! A server of a [`DcuDevice`] takes the same requests, without a mnonce,
+//! and answers the reports of all its DCUs on success:
+//!
+//! | Direction | Size | Content                                        |
+//! |-----------|------|------------------------------------------------|
+//! | response  | 4    | the little endian number of reports, then...   |
+//! | response  | 4    | ...for each, the little endian ID of the DCU   |
+//! | response  | 396  | and its [`dcu::AttestationReport`]             |
+//!
+//! See [`request_dcu_reports_from_socket`].
+
+use super::{ReportRsp, ReportSource, REPORT_RSP_LEN};
+use crate::api::dcu::{self, DcuDevice};
+use crate::error::*;
+use crate::util::{TypeLoad, TypeSave};
+
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::time::Duration;
+
+/// The longest description of a failure a client reads.
+const MAX_FAILURE_LEN: u32 = 4096;
+
+/// How long [`Server::serve`] waits on a stalled client by default, see
+/// [`Server::with_timeout`].
+pub const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The size of a DCU report on the wire, its body then its signature.
+const DCU_REPORT_LEN: usize =
+    std::mem::size_of::<dcu::Body>() + std::mem::size_of::<crate::crypto::sig::ecdsa::Signature>();
+
+static_assertions::const_assert_eq!(DCU_REPORT_LEN, 396);
+
+/// What a [`Server`] answers a request with on success, see the module
+/// documentation for the layouts.
+///
+/// Every [`ReportSource`] answers the firmware's [`ReportRsp`] as a page, a
+/// [`DcuDevice`] the reports of its DCUs.
+pub trait ServedSource {
+    /// The payload answering the request for `data` and `mnonce`.
+    fn respond(&mut self, data: [u8; 64], mnonce: Option<[u8; 16]>) -> Result<Vec<u8>, Error>;
+}
+
+impl<S: ReportSource> ServedSource for S {
+    fn respond(&mut self, data: [u8; 64], mnonce: Option<[u8; 16]>) -> Result<Vec<u8>, Error> {
+        Ok(self.request(data, mnonce)?.to_page())
+    }
+}
+
+/// Reports of all the DCUs for `data` as the user data.
+///
+/// The DCUs take no mnonce: the request fails with [`Error::Unsupported`]
+/// for one.
+impl ServedSource for DcuDevice {
+    fn respond(&mut self, data: [u8; 64], mnonce: Option<[u8; 16]>) -> Result<Vec<u8>, Error> {
+        if mnonce.is_some() {
+            return Err(Error::Unsupported);
+        }
+
+        let reports: Vec<_> = self
+            .get_reports_with_meta(data)?
+            .into_iter()
+            .map(|(meta, report)| (meta.gpu_id, report))
+            .collect();
+        encode_dcu_reports(&reports)
+    }
+}
+
+/// A server answering report requests on a Unix socket.
+///
+/// ```no_run
+/// use csv_rs::api::guest::{CsvGuest, Server};
+///
+/// let server = Server::bind("/run/csv-attest.sock", CsvGuest::open()?)?;
+/// server.serve()?;
+/// # Ok::<(), csv_rs::error::Error>(())
+/// ```
+pub struct Server<S> {
+    listener: UnixListener,
+    source: S,
+    timeout: Duration,
+}
+
+impl<S: ServedSource> Server<S> {
+    /// Listens on a socket created at `path`, serving reports from
+    /// `source`.
+    pub fn bind(path: impl AsRef<Path>, source: S) -> Result<Self, Error> {
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            source,
+            timeout: DEFAULT_CLIENT_TIMEOUT,
+        })
+    }
+
+    /// Drops a client that doesn't send or read for `timeout` rather than
+    /// [`DEFAULT_CLIENT_TIMEOUT`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Serves the connections one after the other, which also serializes
+    /// the requests to `source`.
+    ///
+    /// Only returns if accepting a connection fails. A connection failing,
+    /// or stalling for longer than the timeout of the server, is dropped
+    /// without affecting the others.
+    pub fn serve(mut self) -> Result<(), Error> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            let result = stream
+                .set_read_timeout(Some(self.timeout))
+                .and_then(|()| stream.set_write_timeout(Some(self.timeout)))
+                .and_then(|()| self.handle(stream));
+            if let Err(e) = result {
+                log::debug!("Attestation client dropped: {e}");
+            }
+        }
+    }
+
+    /// Answers the requests of a connection until the client closes it.
+    pub fn handle(&mut self, mut stream: UnixStream) -> io::Result<()> {
+        while let Some((data, mnonce)) = read_request(&mut stream)? {
+            match self.source.respond(data, mnonce) {
+                Ok(response) => {
+                    stream.write_all(&[0])?;
+                    stream.write_all(&response)?;
+                }
+                Err(e) => {
+                    let msg = e.to_string();
+                    stream.write_all(&[1])?;
+                    stream.write_all(&(msg.len() as u32).to_le_bytes())?;
+                    stream.write_all(msg.as_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The report data and the optional mnonce of a request.
+type Request = ([u8; 64], Option<[u8; 16]>);
+
+/// Reads a request, or `None` if the client closed the connection.
+fn read_request(stream: &mut impl Read) -> io::Result<Option<Request>> {
+    let mut data = [0u8; 64];
+    match stream.read_exact(&mut data) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+
+    let mut flag = [0u8; 1];
+    stream.read_exact(&mut flag)?;
+    let mnonce = match flag[0] {
+        0 => None,
+        1 => {
+            let mut mnonce = [0u8; 16];
+            stream.read_exact(&mut mnonce)?;
+            Some(mnonce)
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid mnonce flag",
+            ))
+        }
+    };
+
+    Ok(Some((data, mnonce)))
+}
+
+/// Requests a report from the server listening at `path`.
+///
+/// The response is the one the firmware wrote, to be checked e.g. with
+/// [`AttestationReport::verify_full`](super::AttestationReport::verify_full).
+/// Fails with [`Error::Custom`] describing the server's failure if it
+/// couldn't get a report.
+pub fn request_report_from_socket(
+    path: impl AsRef<Path>,
+    data: [u8; 64],
+    mnonce: Option<[u8; 16]>,
+) -> Result<ReportRsp, Error> {
+    let mut stream = send_request(path.as_ref(), data, mnonce)?;
+
+    let mut page = vec![0u8; REPORT_RSP_LEN];
+    stream.read_exact(&mut page)?;
+    ReportRsp::from_page(&page)
+}
+
+/// Requests the reports of the DCUs from the server of a [`DcuDevice`]
+/// listening at `path`, each along with the ID of its DCU.
+///
+/// The reports are to be checked e.g. with
+/// [`dcu::verify_report_with_chain`]. Fails with [`Error::Custom`]
+/// describing the server's failure if it couldn't get the reports.
+pub fn request_dcu_reports_from_socket(
+    path: impl AsRef<Path>,
+    userdata: [u8; 64],
+) -> Result<Vec<(u32, dcu::AttestationReport)>, Error> {
+    let mut stream = send_request(path.as_ref(), userdata, None)?;
+    decode_dcu_reports(&mut stream)
+}
+
+/// Sends a request to the server listening at `path`, returning the
+/// stream at the payload of a successful response.
+fn send_request(
+    path: &Path,
+    data: [u8; 64],
+    mnonce: Option<[u8; 16]>,
+) -> Result<UnixStream, Error> {
+    let mut stream = UnixStream::connect(path)?;
+
+    let mut request = data.to_vec();
+    match mnonce {
+        Some(mnonce) => {
+            request.push(1);
+            request.extend_from_slice(&mnonce);
+        }
+        None => request.push(0),
+    }
+    stream.write_all(&request)?;
+
+    let mut status = [0u8; 1];
+    stream.read_exact(&mut status)?;
+    if status[0] != 0 {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let mut msg = Vec::new();
+        stream
+            .take(u32::from_le_bytes(len).min(MAX_FAILURE_LEN).into())
+            .read_to_end(&mut msg)?;
+        return Err(Error::Custom(format!(
+            "The attestation server failed: {}",
+            String::from_utf8_lossy(&msg)
+        )));
+    }
+
+    Ok(stream)
+}
+
+fn encode_dcu_reports(reports: &[(u32, dcu::AttestationReport)]) -> Result<Vec<u8>, Error> {
+    let mut payload = Vec::with_capacity(4 + reports.len() * (4 + DCU_REPORT_LEN));
+    payload.extend_from_slice(&(reports.len() as u32).to_le_bytes());
+    for (gpu_id, report) in reports {
+        payload.extend_from_slice(&gpu_id.to_le_bytes());
+        payload.save(&report.body)?;
+        payload.save(&report.sig)?;
+    }
+
+    Ok(payload)
+}
+
+fn decode_dcu_reports(reader: &mut impl Read) -> Result<Vec<(u32, dcu::AttestationReport)>, Error> {
+    let count: u32 = u32::from_le(reader.load()?);
+
+    let mut reports = Vec::new();
+    for _ in 0..count {
+        let gpu_id = u32::from_le(reader.load()?);
+        let report = dcu::AttestationReport {
+            body: reader.load()?,
+            sig: reader.load()?,
+        };
+        reports.push((gpu_id, report));
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dcu_reports_round_trip() {
+        let mut report = dcu::AttestationReport::default();
+        report.body.chip_id[..4].copy_from_slice(b"DCU0");
+        report.body.measure = [7; 32];
+        report.sig.r[0] = 1;
+
+        let payload = encode_dcu_reports(&[(6510, report.clone()), (6511, report)]).unwrap();
+        assert_eq!(payload.len(), 4 + 2 * (4 + DCU_REPORT_LEN));
+
+        let reports = decode_dcu_reports(&mut &payload[..]).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].0, 6511);
+        assert_eq!(reports[1].1.body.chip_id[..4], *b"DCU0");
+        assert_eq!(reports[1].1.body.measure, [7; 32]);
+        assert_eq!(reports[1].1.sig, reports[0].1.sig);
+
+        // A payload cut short fails rather than yielding fewer reports.
+        assert!(decode_dcu_reports(&mut &payload[..payload.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! One line summaries of attestation reports, e.g. for logging.
+
+use super::types::*;
+use crate::certs::Algorithm;
+
+use std::fmt;
+
+/// The fields of an attestation report identifying what it attests,
+/// unmasked, see [`AttestationReport::summary`].
+///
+/// Its `Display` renders a single line, e.g.
+/// `measure=… policy=0x00000001(nodbg) vm_id=… sig_algo=sm2-sa`. Guest
+/// reports carry no DCU origin, see the summaries of the DCU reports for
+/// those.
+#[derive(Debug, Copy, Clone)]
+pub struct ReportSummary {
+    /// The launch measurement of the guest.
+    pub measurement: [u8; 32],
+
+    /// The policy the guest was launched with.
+    pub policy: GuestPolicy,
+
+    /// The ID of the guest.
+    pub vm_id: [u8; 16],
+
+    /// The algorithm the report is signed with.
+    pub sig_algo: Algorithm,
+}
+
+impl AttestationReport {
+    /// Summarizes the report for a log line.
+    pub fn summary(&self) -> ReportSummary {
+        let mut measurement = self.body.measure;
+        xor_with_anonce(&mut measurement, &self.anonce).expect("masking can't fail");
+        let mut vm_id = self.body.vm_id;
+        xor_with_anonce(&mut vm_id, &self.anonce).expect("masking can't fail");
+
+        ReportSummary {
+            measurement,
+            policy: self.body.policy.xor(&self.anonce),
+            vm_id,
+            sig_algo: Algorithm::from(self.sig_algo ^ self.anonce),
+        }
+    }
+}
+
+impl fmt::Display for ReportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = &self.policy;
+        let flags: Vec<_> = [
+            (policy.nodbg(), "nodbg"),
+            (policy.noks(), "noks"),
+            (policy.es(), "es"),
+            (policy.nosend(), "nosend"),
+            (policy.domain(), "domain"),
+            (policy.csv(), "csv"),
+            (policy.csv3(), "csv3"),
+            (policy.asid_reuse(), "asid_reuse"),
+        ]
+        .into_iter()
+        .filter(|&(set, _)| set != 0)
+        .map(|(_, name)| name)
+        .collect();
+
+        write!(
+            f,
+            "measure={} policy={:#010x}({}) vm_id={} sig_algo={}",
+            hex::encode(self.measurement),
+            policy.bits(),
+            flags.join(","),
+            hex::encode(self.vm_id),
+            self.sig_algo
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::golden_report;
+
+    #[test]
+    fn summary() {
+        let report = golden_report();
+        let generic = report.to_generic();
+
+        let summary = report.summary();
+        assert_eq!(summary.measurement[..], generic.measurement[..]);
+        assert_eq!(summary.vm_id[..], generic.guest_id[..]);
+        assert_eq!(u64::from(summary.policy.bits()), generic.policy);
+        assert_eq!(summary.sig_algo, Algorithm::SM2_SA);
+
+        let line = summary.to_string();
+        assert!(!line.contains('\n'));
+        assert!(line.contains(&format!("measure={}", hex::encode(generic.measurement))));
+        assert!(line.ends_with("sig_algo=sm2-sa"), "{line}");
+    }
+
+    #[test]
+    fn summary_policy_flags() {
+        let report = AttestationReport {
+            anonce: 0x1291429b,
+            sig_algo: 0x1291429b,
+            body: Body {
+                policy: GuestPolicy::default().xor(&(0x1291429b ^ 0x0000_0305)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let line = report.summary().to_string();
+        assert!(line.contains("policy=0x00000305(nodbg,es)"), "{line}");
+        assert!(line.ends_with("sig_algo=none"), "{line}");
+    }
+}
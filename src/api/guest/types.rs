@@ -3,23 +3,30 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use super::{FromBytes, ReportDataLayout};
 use crate::error::*;
 use crate::{
-    certs::{csv::Certificate, Usage, Verifiable},
-    crypto::{sig::ecdsa, PublicKey, Signature},
+    certs::{
+        csv::{cert::key::PubKey, Certificate},
+        kds, Algorithm, Usage, Verifiable,
+    },
+    crypto::{key::group::Group, sig, sig::ecdsa, sm, PublicKey, Signature},
     util::*,
 };
 
 use openssl::{
-    hash::{Hasher, MessageDigest},
+    hash::{self, Hasher, MessageDigest},
     pkey, sign,
 };
 
+use codicon::Decoder;
 use static_assertions::const_assert;
 
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
-use std::io::Write;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::time::{Duration, Instant};
 
 use bitfield::bitfield;
 
@@ -61,7 +68,44 @@ impl ReportReq {
         Ok(request)
     }
 
+    /// The request binding `data` in the report data as laid out by
+    /// `layout`, the request a guest makes with
+    /// [`ReportDataLayout::report_data`] and the one a verifier checks with
+    /// [`AttestationReport::verify_request_binding`].
+    ///
+    /// Returns [`Error::InvalidLen`] if `data` doesn't fit the layout.
+    pub fn with_layout(
+        data: &[u8],
+        layout: ReportDataLayout,
+        mnonce: [u8; 16],
+    ) -> Result<Self, Error> {
+        Self::new(Some(layout.report_data(data)?), mnonce)
+    }
+
+    /// Decodes report data given as 128 hex digits, e.g. a challenge.
+    ///
+    /// Returns [`Error::InvalidLen`] for any other number of digits.
+    ///
+    /// ```
+    /// use csv_rs::api::guest::ReportReq;
+    ///
+    /// let data = ReportReq::data_from_hex(&"ab".repeat(64)).unwrap();
+    /// assert_eq!(data, [0xab; 64]);
+    /// ```
+    pub fn data_from_hex(hex: &str) -> Result<[u8; 64], Error> {
+        from_hex(hex)
+    }
+
+    /// Decodes a mnonce given as 32 hex digits.
+    ///
+    /// Returns [`Error::InvalidLen`] for any other number of digits.
+    pub fn mnonce_from_hex(hex: &str) -> Result<[u8; 16], Error> {
+        from_hex(hex)
+    }
+
     fn calculate_hash(&mut self) -> Result<(), Error> {
+        crate::crypto::check_backend()?;
+
         let mut hasher = Hasher::new(MessageDigest::sm3())?;
         hasher.update(self.data.as_ref())?;
         hasher.update(self.mnonce.as_ref())?;
@@ -72,6 +116,74 @@ impl ReportReq {
     }
 }
 
+/// Decodes exactly `N` bytes given as hex digits.
+fn from_hex<const N: usize>(hex: &str) -> Result<[u8; N], Error> {
+    if hex.len() != 2 * N {
+        return Err(Error::InvalidLen);
+    }
+
+    let mut bytes = [0u8; N];
+    hex::decode_to_slice(hex, &mut bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok(bytes)
+}
+
+/// The size in bytes of an [`AttestationReport`].
+pub const ATTESTATION_REPORT_LEN: usize = 336;
+
+/// The size in bytes of a [`ReportSigner`].
+pub const REPORT_SIGNER_LEN: usize = 2212;
+
+/// The size in bytes of a [`ReportRsp`], the page the firmware writes the
+/// response into.
+pub const REPORT_RSP_LEN: usize = 4096;
+
+/// The offsets in bytes of the fields of the firmware's structures.
+pub mod offset {
+    /// The offsets of the fields of an [`AttestationReport`](super::AttestationReport).
+    pub mod attestation_report {
+        pub const USER_PUBKEY_DIGEST: usize = 0;
+        pub const VM_ID: usize = 32;
+        pub const VM_VERSION: usize = 48;
+        pub const REPORT_DATA: usize = 64;
+        pub const MNONCE: usize = 128;
+        pub const MEASURE: usize = 144;
+        pub const POLICY: usize = 176;
+        pub const SIG_USAGE: usize = 180;
+        pub const SIG_ALGO: usize = 184;
+        pub const ANONCE: usize = 188;
+        pub const SIG: usize = 192;
+    }
+
+    /// The offsets of the fields of a [`ReportSigner`](super::ReportSigner).
+    pub mod report_signer {
+        pub const PEK_CERT: usize = 0;
+        pub const SN: usize = 2084;
+        pub const RESERVED: usize = 2148;
+        pub const MAC: usize = 2180;
+    }
+
+    /// The offsets of the fields of a [`ReportRsp`](super::ReportRsp).
+    pub mod report_rsp {
+        pub const REPORT: usize = 0;
+        pub const SIGNER: usize = 336;
+    }
+}
+
+/// The size of the response without its padding.
+const REPORT_RSP_CONTENT_SIZE: usize =
+    std::mem::size_of::<AttestationReport>() + std::mem::size_of::<ReportSigner>();
+
+// Fail with a readable error, rather than an overflow in the padding's size,
+// if a field addition makes the response outgrow its page.
+const _: () = assert!(
+    REPORT_RSP_CONTENT_SIZE <= REPORT_RSP_LEN,
+    "AttestationReport and ReportSigner must fit in the 4096 bytes of ReportRsp"
+);
+
+/// The size of the padding of the response.
+const REPORT_RSP_RESERVED_SIZE: usize = REPORT_RSP_LEN.saturating_sub(REPORT_RSP_CONTENT_SIZE);
+
 /// The response from the PSP containing the generated attestation report.
 ///
 /// The Report is padded to exactly 4096 Bytes to make sure the page size
@@ -83,20 +195,254 @@ pub struct ReportRsp {
     /// The evidence to verify the attestation report's signature.
     pub signer: ReportSigner,
     /// Padding bits to meet the memory page alignment.
-    reserved: [u8; 4096
-        - (std::mem::size_of::<AttestationReport>() + std::mem::size_of::<ReportSigner>())],
+    reserved: [u8; REPORT_RSP_RESERVED_SIZE],
 }
 
 // Compile-time check that the size is what is expected.
-const_assert!(std::mem::size_of::<ReportRsp>() == 4096);
+const_assert!(std::mem::size_of::<ReportRsp>() == REPORT_RSP_LEN);
+
+// Compile-time checks that the published layout matches the structures.
+const_assert!(std::mem::size_of::<AttestationReport>() == ATTESTATION_REPORT_LEN);
+const_assert!(std::mem::size_of::<ReportSigner>() == REPORT_SIGNER_LEN);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, body.user_pubkey_digest)
+        == offset::attestation_report::USER_PUBKEY_DIGEST
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, body.vm_id) == offset::attestation_report::VM_ID
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, body.vm_version)
+        == offset::attestation_report::VM_VERSION
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, body.report_data)
+        == offset::attestation_report::REPORT_DATA
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, body.mnonce) == offset::attestation_report::MNONCE
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, body.measure) == offset::attestation_report::MEASURE
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, body.policy) == offset::attestation_report::POLICY
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, sig_usage) == offset::attestation_report::SIG_USAGE
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, sig_algo) == offset::attestation_report::SIG_ALGO
+);
+const_assert!(
+    std::mem::offset_of!(AttestationReport, anonce) == offset::attestation_report::ANONCE
+);
+const_assert!(std::mem::offset_of!(AttestationReport, sig) == offset::attestation_report::SIG);
+const_assert!(std::mem::offset_of!(ReportSigner, pek_cert) == offset::report_signer::PEK_CERT);
+const_assert!(std::mem::offset_of!(ReportSigner, sn) == offset::report_signer::SN);
+const_assert!(std::mem::offset_of!(ReportSigner, reserved) == offset::report_signer::RESERVED);
+const_assert!(std::mem::offset_of!(ReportSigner, mac) == offset::report_signer::MAC);
+const_assert!(std::mem::offset_of!(ReportRsp, report) == offset::report_rsp::REPORT);
+const_assert!(std::mem::offset_of!(ReportRsp, signer) == offset::report_rsp::SIGNER);
+
+/// Detects the version of the layout of a response page, for verifiers to
+/// turn away pages of unknown firmware before decoding them.
+///
+/// The report has no version field of its own: the layout of the page
+/// only varies with the PEK certificate the signer evidence starts with,
+/// whose version is read here, unmasked with the report's anonce. The
+/// versions known so far share the layout [`ReportRsp::from_page`] decodes.
+///
+/// Returns [`Error::InvalidLen`] if `page` ends before the version and
+/// [`Error::UnknownReportVersion`] for versions
+/// [`Certificate::encoded_len`] doesn't know.
+///
+/// ```
+/// use csv_rs::api::guest::detect_report_version;
+///
+/// let page = include_bytes!("../../../tests/test_data/report.cert");
+/// assert_eq!(detect_report_version(page).unwrap(), 1);
+/// ```
+pub fn detect_report_version(page: &[u8]) -> Result<u32, Error> {
+    let at = offset::report_rsp::SIGNER + offset::report_signer::PEK_CERT;
+    let anonce = offset::report_rsp::REPORT + offset::attestation_report::ANONCE;
+    let read = |at: usize| -> Result<[u8; 4], Error> {
+        let bytes = page.get(at..at + 4).ok_or(Error::InvalidLen)?;
+        Ok(bytes.try_into().unwrap())
+    };
+
+    let mut version = read(at)?;
+    xor_with_anonce(&mut version, &u32::from_le_bytes(read(anonce)?))?;
+    let version = u32::from_le_bytes(version);
+
+    match Certificate::encoded_len(version) {
+        Some(_) => Ok(version),
+        None => Err(Error::UnknownReportVersion(version)),
+    }
+}
+
+impl ReportRsp {
+    /// Decodes the response from the page the firmware wrote it into.
+    ///
+    /// The padding is kept as is, whatever it holds, so that pages of newer
+    /// firmwares still decode. A page shorter than the padding leaves the
+    /// rest of it zeroed.
+    ///
+    /// Returns [`Error::InvalidLen`] if `page` ends before the signer
+    /// evidence does.
+    pub fn from_page(page: &[u8]) -> Result<Self, Error> {
+        // The asserts above pin the layout at build time. Should a change
+        // slip past them, the fields read must still end within the page,
+        // and within `page`.
+        let content = offset::report_rsp::SIGNER + REPORT_SIGNER_LEN;
+        if content > REPORT_RSP_LEN || page.len() < content {
+            return Err(Error::InvalidLen);
+        }
+
+        let mut reader = page;
+        let report = AttestationReport::decode(&mut reader, ())?;
+        let signer = ReportSigner::decode(&mut reader, ())?;
+
+        let mut reserved = [0u8; REPORT_RSP_RESERVED_SIZE];
+        let len = reader.len().min(reserved.len());
+        reserved[..len].copy_from_slice(&reader[..len]);
+
+        Ok(Self {
+            report,
+            signer,
+            reserved,
+        })
+    }
+
+    /// Decodes the response like [`Self::from_page`], but only from a
+    /// whole page whose padding is zero.
+    ///
+    /// Returns [`Error::InvalidLen`] if `page` isn't [`REPORT_RSP_LEN`]
+    /// bytes long and [`Error::NonZeroReserved`] if the padding isn't zero,
+    /// which may be a sign of tampering or of a firmware this crate doesn't
+    /// know.
+    pub fn from_page_strict(page: &[u8]) -> Result<Self, Error> {
+        if page.len() != REPORT_RSP_LEN {
+            return Err(Error::InvalidLen);
+        }
+
+        let response = Self::from_page(page)?;
+        if let Some(i) = response.reserved.iter().position(|&b| b != 0) {
+            return Err(Error::NonZeroReserved {
+                offset: REPORT_RSP_CONTENT_SIZE + i,
+            });
+        }
+
+        Ok(response)
+    }
+
+    /// Encodes the response into a page, as the firmware wrote it.
+    ///
+    /// This is the inverse of [`Self::from_page`], the page is
+    /// [`REPORT_RSP_LEN`] bytes long.
+    pub fn to_page(&self) -> Vec<u8> {
+        let mut page = Vec::with_capacity(REPORT_RSP_LEN);
+        codicon::Encoder::encode(&self.report, &mut page, ())
+            .expect("encoding into a Vec can't fail");
+        codicon::Encoder::encode(&self.signer, &mut page, ())
+            .expect("encoding into a Vec can't fail");
+        page.extend_from_slice(&self.reserved);
+        page
+    }
+
+    /// Verifies the response as the firmware wrote it all the way up to the
+    /// HRK of `chain`, for the request of `expected_mnonce`.
+    ///
+    /// The checks run in this order, the first failing one is returned:
+    ///
+    /// 1. the report carries `expected_mnonce`, or [`Error::MnonceMismatch`],
+    /// 2. the signer evidence's hmac, keyed by the mnonce, or
+    ///    [`Error::BadSignature`],
+    /// 3. the chain, the HSK signed by the HRK and the CEK by the HSK, or
+    ///    [`Error::IoError`],
+    /// 4. the PEK embedded in the evidence decodes, or
+    ///    [`Error::InvalidCertificate`], and is signed by the CEK, or
+    ///    [`Error::IoError`],
+    /// 5. the report's signature algorithm works with the PEK's curve, or
+    ///    [`Error::AlgoCurveMismatch`],
+    /// 6. the report's signature by the PEK, or [`Error::BadSignature`].
+    ///
+    /// On success the signer evidence is restored, see
+    /// [`ReportSigner::verified`], on failure the response is left as the
+    /// firmware wrote it. [`AttestationReport::verify_full`] runs the same
+    /// checks on the report and the signer evidence taken apart.
+    pub fn verify(&mut self, chain: &kds::Chain, expected_mnonce: &[u8; 16]) -> Result<(), Error> {
+        crate::crypto::check_backend()?;
+
+        let signer = self.signer.verified(
+            expected_mnonce,
+            &self.report.body.mnonce,
+            &self.report.anonce,
+        )?;
+        self.report.verify_with_cek(&signer, chain.verify()?)?;
+
+        self.signer = signer;
+        Ok(())
+    }
+
+    /// The certificates embedded in the response, in the order they appear.
+    ///
+    /// The response is left as the firmware wrote it, the certificates are
+    /// unmasked with the report's anonce as they're decoded. The firmware
+    /// currently only embeds the PEK in `signer.pek_cert`, certificates
+    /// following it are decoded for as long as they parse.
+    ///
+    /// Returns [`Error::InvalidCertificate`] if not even the PEK parses.
+    pub fn certificates(&self) -> Result<Vec<Certificate>, Error> {
+        let mut embedded = self.signer.pek_cert;
+        xor_with_anonce(&mut embedded, &self.report.anonce)?;
+
+        let certs = decode_certificates(&embedded);
+        if certs.is_empty() {
+            return Err(Error::InvalidCertificate);
+        }
+
+        Ok(certs)
+    }
+}
+
+/// Decodes the certificates concatenated at the start of `bytes`, for as
+/// long as they parse.
+fn decode_certificates(mut bytes: &[u8]) -> Vec<Certificate> {
+    let mut certs = Vec::new();
+    while bytes.len() >= 4 {
+        let ver = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        let Some(len) = Certificate::encoded_len(ver).filter(|&len| len <= bytes.len()) else {
+            break;
+        };
+        let Ok(cert) = Certificate::decode(&mut &bytes[..len], ()) else {
+            break;
+        };
+        certs.push(cert);
+        bytes = &bytes[len..];
+    }
+
+    certs
+}
+
+/// Verifies the certificates embedded in signer evidence, the PEK first,
+/// each signed by the next one and the last one by `cek`.
+fn verify_pek_chain(cek: &Certificate, certs: &[Certificate]) -> Result<(), Error> {
+    let mut signer = cek;
+    for cert in certs.iter().rev() {
+        (signer, cert).verify()?;
+        signer = cert;
+    }
+
+    Ok(())
+}
 
 impl Default for ReportRsp {
     fn default() -> Self {
         Self {
             report: Default::default(),
             signer: Default::default(),
-            reserved: [0u8; 4096
-                - (std::mem::size_of::<AttestationReport>() + std::mem::size_of::<ReportSigner>())],
+            reserved: [0u8; REPORT_RSP_RESERVED_SIZE],
         }
     }
 }
@@ -128,10 +474,28 @@ impl Default for Body {
     }
 }
 
+impl Body {
+    /// The bytes the firmware signs, i.e. every field of the body in the
+    /// order of its layout, as masked in the report.
+    ///
+    /// The signature of the report is checked over these bytes, and test
+    /// reports are signed over them.
+    pub fn to_signed_bytes(&self) -> Vec<u8> {
+        let mut signed = Vec::with_capacity(std::mem::size_of::<Self>());
+        signed.save(self).expect("encoding into a Vec can't fail");
+        signed
+    }
+}
+
 /// Data provieded by the guest owner for requesting an attestation report
 /// from the HYGON Secure Processor.
+///
+/// The firmware only produces these reports for guests launched under CSV,
+/// neither `policy` nor `sig_usage` has a bit telling a guest from bare
+/// metal: a report verifying up to the HRK attests a CSV guest. Reports of
+/// DCUs are the distinct [`crate::api::dcu::AttestationReport`].
 #[repr(C)]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct AttestationReport {
     pub body: Body,
     pub sig_usage: u32,
@@ -140,183 +504,2307 @@ pub struct AttestationReport {
     pub sig: ecdsa::Signature,
 }
 
-impl Default for AttestationReport {
+/// A field whose value differs between two attestation reports, see
+/// [`AttestationReport::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    /// The name of the field, e.g. `"measure"` for `body.measure`.
+    pub field: &'static str,
+    /// The unmasked value of the field in the first report.
+    pub left: Vec<u8>,
+    /// The unmasked value of the field in the second report.
+    pub right: Vec<u8>,
+}
+
+/// What an attestation report verified with, see
+/// [`AttestationReport::verify_full`].
+#[derive(Debug, Clone)]
+pub struct VerifyOutcome {
+    /// The key the report's signature verified with, the PEK.
+    pub verified_by: PubKey,
+
+    /// The certificate of the PEK, endorsed by the CEK of the chain.
+    pub pek: Certificate,
+
+    /// The serial number of the chip, as restored from the signer evidence.
+    pub sn: [u8; 64],
+}
+
+impl VerifyOutcome {
+    /// The version of the report, that of the certificate of the PEK the
+    /// evidence starts with, see [`detect_report_version`].
+    pub fn report_version(&self) -> u32 {
+        self.pek.body.ver
+    }
+}
+
+/// A check of [`AttestationReport::verify_full_traced`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VerifyCheck {
+    /// The mnonce unmasked from the report is the requested one.
+    Mnonce,
+    /// The hmac of the signer evidence, keyed by the mnonce.
+    Hmac,
+    /// A link of the chain, a certificate signed by its issuer: `1` is the
+    /// HRK signed by itself, `2` the HSK by the HRK, `3` the CEK by the HSK
+    /// and `4` the last certificate embedded in the evidence by the CEK,
+    /// the embedded ones being decoded there. Further links are the
+    /// embedded certificates, each signed by the next one, down to the PEK.
+    ChainLink(usize),
+    /// The report's signature algorithm works with the PEK's curve, and the
+    /// report's signature by the PEK.
+    ReportSignature,
+}
+
+/// A check that ran while verifying a report, see
+/// [`AttestationReport::verify_full_traced`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyStep {
+    /// What was checked.
+    pub check: VerifyCheck,
+    /// Whether the check passed.
+    pub passed: bool,
+    /// How long the check took.
+    pub elapsed: Duration,
+}
+
+/// Runs `run`, recording it in `trace` as the step of `check`.
+fn trace_step<T>(
+    trace: &mut Vec<VerifyStep>,
+    check: VerifyCheck,
+    run: impl FnOnce() -> Result<T, Error>,
+) -> Result<T, Error> {
+    let started = Instant::now();
+    let result = run();
+    trace.push(VerifyStep {
+        check,
+        passed: result.is_ok(),
+        elapsed: started.elapsed(),
+    });
+    result
+}
+
+/// How [`AttestationReport::verify_full_with`] verifies a report.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VerifyOptions {
+    /// Whether to check the hmac of the signer evidence against the
+    /// requested mnonce.
+    ///
+    /// **Warning:** the hmac is what binds the evidence to the request of
+    /// the guest. Without it, a report can't be told from a replayed one, so
+    /// only disable it for reports whose freshness is known otherwise, e.g.
+    /// archived ones of which the mnonce didn't survive. The report's
+    /// signature and the chain are verified either way.
+    pub verify_hmac: bool,
+
+    /// Whether to reject reports with a zero anonce with
+    /// [`Error::ZeroAnonce`].
+    ///
+    /// Some firmware configurations don't mask reports, the anonce is then
+    /// zero and the mnonce, the PEK and the serial number are carried as
+    /// is. Such reports verify like any other, but their mnonce is also
+    /// the visible key of the hmac.
+    pub reject_zero_anonce: bool,
+
+    /// The lowest report version to trust, e.g. to turn away the reports
+    /// of firmware with a known vulnerability, see
+    /// [`VerifyOutcome::report_version`].
+    ///
+    /// Reports of a lower version fail with [`Error::ReportVersionTooOld`]
+    /// once verified, the version being only trusted then.
+    pub min_version: Option<u32>,
+}
+
+impl Default for VerifyOptions {
     fn default() -> Self {
         Self {
-            body: Default::default(),
-            sig_usage: Default::default(),
-            sig_algo: Default::default(),
-            anonce: Default::default(),
-            sig: Default::default(),
+            verify_hmac: true,
+            reject_zero_anonce: false,
+            min_version: None,
         }
     }
 }
 
-impl codicon::Encoder<crate::Body> for AttestationReport {
-    type Error = std::io::Error;
+/// The digest algorithms of the digests carried in an attestation report.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    /// SM3, producing 32 bytes digests.
+    Sm3,
+}
 
-    fn encode(&self, mut writer: impl Write, _: crate::Body) -> Result<(), std::io::Error> {
-        writer.save(&self.body)
+impl DigestAlgorithm {
+    /// Hashes `data` with the algorithm.
+    pub fn digest(self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(hash::hash(self.into(), data)?.to_vec())
     }
 }
 
-impl TryFrom<&AttestationReport> for Signature {
-    type Error = std::io::Error;
-
-    #[inline]
-    fn try_from(value: &AttestationReport) -> Result<Self, std::io::Error> {
-        let sig = Vec::try_from(&value.sig)?;
-        Ok(Self {
-            sig,
-            id: None,
-            usage: Usage::PEK.into(),
-            algo: None,
-        })
+impl From<DigestAlgorithm> for MessageDigest {
+    fn from(value: DigestAlgorithm) -> Self {
+        match value {
+            DigestAlgorithm::Sm3 => MessageDigest::sm3(),
+        }
     }
 }
 
-impl Verifiable for (&Certificate, &AttestationReport) {
-    type Output = ();
+impl TryFrom<Algorithm> for DigestAlgorithm {
+    type Error = Error;
 
-    fn verify(self) -> Result<(), std::io::Error> {
-        let key: PublicKey = self.0.try_into()?;
-        let sig: Signature = self.1.try_into()?;
-        key.verify(
-            self.1,
-            &self.0.body.data.user_id[..self.0.body.data.uid_size as usize],
-            &sig,
-        )
+    fn try_from(value: Algorithm) -> Result<Self, Error> {
+        match value {
+            Algorithm::SM2_SA | Algorithm::SM2_DH => Ok(DigestAlgorithm::Sm3),
+            _ => Err(Error::Unsupported),
+        }
     }
 }
 
-bitfield! {
-    /// The firmware associates each guest with a guest policy that the guest owner provides. The
-    /// firmware restricts what actions the hypervisor can take on the guest according to the guest policy.
-    /// The policy also indicates the minimum firmware version to for the guest.
+impl AttestationReport {
+    /// The bytes of an encoded report the firmware signs, the body.
     ///
-    /// The guest owner provides the guest policy to the firmware during launch. The firmware then binds
-    /// the policy to the guest. The policy cannot be changed throughout the lifetime of the guest. The
-    /// policy is also migrated with the guest and enforced by the destination platform firmware.
+    /// Along with [`Self::signature_range`], this lets e.g. an HSM verify
+    /// the raw bytes of a report or of a [`ReportRsp`] page, as the report
+    /// starts the page, without decoding it.
+    pub const fn signed_range() -> Range<usize> {
+        offset::attestation_report::USER_PUBKEY_DIGEST..offset::attestation_report::SIG_USAGE
+    }
+
+    /// The bytes of an encoded report holding its signature, `r` then `s`
+    /// as [`SM2_SIG_COMPONENT_LEN`](crate::crypto::sig::SM2_SIG_COMPONENT_LEN)
+    /// bytes little endian integers each.
+    pub const fn signature_range() -> Range<usize> {
+        offset::attestation_report::SIG..ATTESTATION_REPORT_LEN
+    }
+
+    /// Decodes a report from the start of `buf`, returning it along with
+    /// the bytes following it, e.g. when it is embedded in a container
+    /// format carrying more data.
     ///
-    /// | Bit(s) | Name           | Description                                                                                 >
-    /// |--------|----------------|--------------------------------------------------------------------------------------------->
-    /// | 0      | NODBG          | Debugging of the guest is disallowed when set                                               >
-    /// | 1      | NOKS           | Sharing keys with other guests is disallowed when set                                       >
-    /// | 2      | ES             | CSV2 is required when set                                                                   >
-    /// | 3      | NOSEND         | Sending the guest to another platform is disallowed when set                                >
-    /// | 4      | DOMAIN         | The guest must not be transmitted to another platform that is not in the domain when set.   >
-    /// | 5      | CSV            | The guest must not be transmitted to another platform that is not CSV capable when set.     >
-    /// | 6      | CSV3           | The guest must not be transmitted to another platform that is not CSV3 capable when set.    >
-    /// | 7      | ASID_REUSE     | Sharing asids with other guests owned by same user is allowed when set                      >
-    /// | 11:8   | HSK_VERSION    | The guest must not be transmitted to another platform with a lower HSK version.             >
-    /// | 15:12  | CEK_VERSION    | The guest must not be transmitted to another platform with a lower CEK version.             >
-    /// | 23:16  | API_MAJOR      | The guest must not be transmitted to another platform with a lower platform version.        >
-    /// | 31:24  | API_MINOR      | The guest must not be transmitted to another platform with a lower platform version.        >
-    #[repr(C)]
-    #[derive(Copy, Clone, Serialize, Deserialize, Default)]
-    pub struct GuestPolicy(u32);
-    impl Debug;
-    pub nodbg, _: 0, 0;
-    pub noks, _: 1, 1;
-    pub es, _: 2, 2;
-    pub nosend, _: 3, 3;
-    pub domain, _: 4, 4;
-    pub csv, _: 5, 5;
-    pub csv3, _: 6, 6;
-    pub asid_reuse, _: 7, 7;
-    pub hsk_version, _: 11, 8;
-    pub cek_version, _: 15, 12;
-    pub api_major, _: 23, 16;
-    pub api_minor, _: 31, 24;
-}
+    /// Returns [`Error::InvalidLen`] if `buf` is shorter than
+    /// [`ATTESTATION_REPORT_LEN`].
+    pub fn parse_prefix(buf: &[u8]) -> Result<(AttestationReport, &[u8]), Error> {
+        if buf.len() < ATTESTATION_REPORT_LEN {
+            return Err(Error::InvalidLen);
+        }
 
-impl GuestPolicy {
-    #[allow(dead_code)]
-    pub fn xor(&self, anonce: &u32) -> Self {
-        Self(self.0 ^ anonce)
+        let (report, rest) = buf.split_at(ATTESTATION_REPORT_LEN);
+        Ok((AttestationReport::decode(report, ())?, rest))
     }
-}
 
-#[repr(C)]
-#[derive(Serialize, Deserialize)]
-pub struct ReportSigner {
-    #[serde(with = "BigArray")]
-    pub pek_cert: [u8; 2084],
-    #[serde(with = "BigArray")]
-    pub sn: [u8; 64],
-    pub reserved: [u8; 32],
-    pub mac: [u8; 32],
-}
+    /// Writes the report as encoded by the firmware to the start of `buf`,
+    /// e.g. a memory mapped capture file, returning the number of bytes
+    /// written.
+    ///
+    /// Returns [`Error::InvalidLen`] if `buf` is shorter than
+    /// [`ATTESTATION_REPORT_LEN`], leaving it untouched.
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.len() < ATTESTATION_REPORT_LEN {
+            return Err(Error::InvalidLen);
+        }
 
-fn xor_with_anonce(data: &mut [u8], anonce: &u32) -> Result<(), Error> {
-    let mut anonce_array = [0u8; 4];
-    anonce_array[..].copy_from_slice(&anonce.to_le_bytes());
+        codicon::Encoder::encode(self, &mut buf[..ATTESTATION_REPORT_LEN], ())?;
+        Ok(ATTESTATION_REPORT_LEN)
+    }
 
-    for (index, item) in data.iter_mut().enumerate() {
-        *item ^= anonce_array[index % 4];
+    /// Returns the algorithm of the report's `user_pubkey_digest`.
+    ///
+    /// The digest follows the report's signature algorithm, reports signed
+    /// with SM2 carry an SM3 digest.
+    pub fn user_pubkey_digest_algo(&self) -> Result<DigestAlgorithm, Error> {
+        Algorithm::from(self.sig_algo ^ self.anonce).try_into()
     }
 
-    Ok(())
-}
+    /// Checks that the report's `user_pubkey_digest` is the digest of
+    /// `pubkey`, as hashed by [`Self::user_pubkey_digest_algo`].
+    pub fn expect_user_pubkey(&self, pubkey: &[u8]) -> Result<(), Error> {
+        let digest = self.user_pubkey_digest_algo()?.digest(pubkey)?;
 
-impl ReportSigner {
-    /// Verifies the signature evidence's hmac.
-    pub fn verify(
-        &mut self,
-        input_mnonce: &[u8],
-        mnonce: &[u8],
-        anonce: &u32,
-    ) -> Result<(), Error> {
-        let mut real_mnonce = Vec::from(mnonce);
-        xor_with_anonce(&mut real_mnonce, anonce)?;
+        let mut user_pubkey_digest = self.body.user_pubkey_digest;
+        xor_with_anonce(&mut user_pubkey_digest, &self.anonce)?;
 
-        if real_mnonce != input_mnonce {
-            return Err(Error::BadSignature);
+        if digest != user_pubkey_digest {
+            return Err(Error::InvalidKey);
         }
 
-        let key = pkey::PKey::hmac(&real_mnonce)?;
-        let mut sig = sign::Signer::new(MessageDigest::sm3(), &key)?;
+        Ok(())
+    }
 
-        sig.update(&self.pek_cert)?;
-        sig.update(&self.sn)?;
-        sig.update(&self.reserved)?;
+    /// Verifies that `sig` signs `msg` with `pubkey`, the key the report
+    /// attests, see [`PubKey::verify_data`].
+    ///
+    /// The key is attested by the report's `user_pubkey_digest` of its
+    /// encoding, i.e. its usage, its algorithm and its ECC point as laid out
+    /// in certificates. Returns [`Error::InvalidKey`] if it isn't the
+    /// attested key.
+    pub fn verify_data(&self, pubkey: &PubKey, msg: &[u8], sig: &[u8]) -> Result<(), Error> {
+        let mut encoded = Vec::new();
+        encoded.save(pubkey)?;
+        self.expect_user_pubkey(&encoded)?;
 
-        if sig.sign_to_vec()? != self.mac {
-            return Err(Error::BadSignature);
-        }
+        pubkey.verify_data(msg, sig)
+    }
 
-        // restore pek cert and serial number.
-        self.restore(anonce)?;
+    /// The signature of the report, by the PEK on the SM2 curve.
+    pub fn signature(&self) -> sig::Signature {
+        sig::Signature::sm2(self.sig)
+    }
 
-        Ok(())
+    /// The digest the report's signature is over, for verifiers that only
+    /// take a pre-computed digest, such as an external HSM: the SM3 of the
+    /// ZA of the `pek`'s key and SM2 user ID followed by the signed bytes,
+    /// see [`Body::to_signed_bytes`].
+    ///
+    /// The `pek` is that of the restored signer evidence, see
+    /// [`ReportSigner::pek_certificate`].
+    pub fn signing_digest(&self, pek: &Certificate) -> Result<[u8; 32], Error> {
+        let key = PublicKey::try_from(pek)?.key;
+        Ok(sm::SM2::digest(
+            &key,
+            &pek.sm2_id(),
+            &self.body.to_signed_bytes(),
+        )?)
     }
 
-    fn restore(&mut self, anonce: &u32) -> Result<(), Error> {
-        xor_with_anonce(&mut self.pek_cert, anonce)?;
-        xor_with_anonce(&mut self.sn, anonce)?;
+    /// The anonce the firmware masked the report with.
+    ///
+    /// The firmware draws a new anonce for every report of a guest, see
+    /// [`check_anonce_advanced`] for tracking it across reports.
+    pub fn anonce(&self) -> u32 {
+        self.anonce
+    }
 
-        // reset reserved to 0.
-        self.reserved.fill(0);
+    /// The unmasked mnonce followed by the little endian anonce, a compact
+    /// value to keep track of the reports already seen, e.g. to detect
+    /// replays.
+    ///
+    /// The mnonce is chosen by the guest when requesting the report, the
+    /// anonce by the firmware when producing it.
+    pub fn freshness_token(&self) -> [u8; 20] {
+        let mut mnonce = self.body.mnonce;
+        xor_with_anonce(&mut mnonce, &self.anonce).expect("masking can't fail");
 
-        Ok(())
+        let mut token = [0u8; 20];
+        token[..16].copy_from_slice(&mnonce);
+        token[16..].copy_from_slice(&self.anonce.to_le_bytes());
+        token
     }
-}
 
-impl Default for ReportSigner {
-    fn default() -> Self {
-        Self {
-            pek_cert: [0u8; 2084],
-            sn: [0u8; 64],
-            reserved: Default::default(),
-            mac: Default::default(),
+    /// Checks that the report's `policy` is `expected_policy`.
+    ///
+    /// Some firmware binds the policy into the measurement, so that a
+    /// measurement is only as trustworthy as the policy it was taken under:
+    /// check the policy before trusting [`Self::expect_measure`], lest a
+    /// guest launched with a downgraded policy, e.g. allowing debugging,
+    /// passes for the expected one.
+    ///
+    /// Returns [`Error::PolicyMismatch`] unless every bit of the unmasked
+    /// policy is the expected one.
+    pub fn verify_policy_binding(&self, expected_policy: GuestPolicy) -> Result<(), Error> {
+        let policy = self.body.policy.xor(&self.anonce);
+        if policy.bits() != expected_policy.bits() {
+            return Err(Error::PolicyMismatch {
+                expected: expected_policy.bits(),
+                got: policy.bits(),
+            });
         }
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod test {
-    mod report_req {
-        use crate::api::guest::types::ReportReq;
+    /// The launch measurement of the guest, the report's `measure`
+    /// unmasked.
+    ///
+    /// **Warning:** this only reads the field. Unless the report verified,
+    /// e.g. with [`Self::verify_full`] or as a
+    /// [`VerifiedReport`](super::VerifiedReport), anyone could have written
+    /// it: the value is only good for a quick look, such as a liveness
+    /// check, not for trusting the guest. See
+    /// [`CsvGuest::get_measurement`](super::CsvGuest::get_measurement) for
+    /// the measurement of a verified report.
+    pub fn measurement(&self) -> [u8; 32] {
+        let mut measure = self.body.measure;
+        xor_with_anonce(&mut measure, &self.anonce).expect("masking can't fail");
+        measure
+    }
+
+    /// The report's `report_data` unmasked and decoded as a `T`, for
+    /// deployments packing their own structure in it.
+    ///
+    /// `T` is read from the leading bytes, see [`FromBytes`] for what it
+    /// may be. Returns [`Error::InvalidLen`] if it is over 64 bytes.
+    ///
+    /// **Warning:** as for [`Self::measurement`], the value is only to be
+    /// trusted once the report verified.
+    ///
+    /// ```
+    /// use csv_rs::{
+    ///     api::guest::FromBytes,
+    ///     testutil::{make_report, ReportParams},
+    /// };
+    ///
+    /// #[repr(C)]
+    /// #[derive(Clone, Copy)]
+    /// struct Binding {
+    ///     version: [u8; 4],
+    ///     config: [u8; 32],
+    /// }
+    ///
+    /// unsafe impl FromBytes for Binding {}
+    ///
+    /// let mut report_data = [0u8; 64];
+    /// report_data[..4].copy_from_slice(b"v1.0");
+    /// let (response, _, _) = make_report(ReportParams {
+    ///     report_data,
+    ///     ..Default::default()
+    /// });
+    ///
+    /// let binding: Binding = response.report.report_data_as()?;
+    /// assert_eq!(&binding.version, b"v1.0");
+    /// # Ok::<(), csv_rs::error::Error>(())
+    /// ```
+    pub fn report_data_as<T: FromBytes>(&self) -> Result<T, Error> {
+        let mut report_data = self.body.report_data;
+        xor_with_anonce(&mut report_data, &self.anonce)?;
+        super::report_data::from_bytes(&report_data)
+    }
+
+    /// Checks that the report's `measure` is `expected`, e.g. as computed by
+    /// [`crate::measure::expected`].
+    ///
+    /// Returns [`Error::BadMeasurement`] on mismatch.
+    pub fn expect_measure(&self, expected: &[u8; 32]) -> Result<(), Error> {
+        let mut measure = self.body.measure;
+        xor_with_anonce(&mut measure, &self.anonce)?;
+
+        if &measure != expected {
+            return Err(Error::BadMeasurement);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the firmware answered `req`, i.e. that the unmasked
+    /// `report_data` and `mnonce` of the report are the requested ones.
+    ///
+    /// `req.data` is the report data as computed by the guest, so this
+    /// covers any [`ReportDataSource`](super::ReportDataSource), raw or hashed. Returns
+    /// [`Error::ReportDataMismatch`] if the firmware ignored or altered the
+    /// data and [`Error::MnonceMismatch`] if it answered another mnonce.
+    pub fn verify_request_binding(&self, req: &ReportReq) -> Result<(), Error> {
+        let mut report_data = self.body.report_data;
+        xor_with_anonce(&mut report_data, &self.anonce)?;
+        if report_data != req.data {
+            return Err(Error::ReportDataMismatch);
+        }
+
+        let mut mnonce = self.body.mnonce;
+        xor_with_anonce(&mut mnonce, &self.anonce)?;
+        if mnonce != req.mnonce {
+            return Err(Error::MnonceMismatch {
+                expected: req.mnonce,
+                got: mnonce,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the report was requested for the challenge `nonce`, for
+    /// verifiers of TPM-style quotes.
+    ///
+    /// The nonce is bound as the [`Sm3Blob`](super::Sm3Blob) of the nonce,
+    /// i.e. the unmasked `report_data` must be the SM3 digest of `nonce`
+    /// followed by 32 zero bytes. A guest binds it with
+    /// `get_report(Sm3Blob(nonce), None)`. See [`Self::verify_nonce_with`]
+    /// for nonces bound in another layout.
+    ///
+    /// Returns [`Error::ReportDataMismatch`] if the report wasn't requested
+    /// for `nonce`.
+    pub fn verify_nonce(&self, nonce: &[u8]) -> Result<(), Error> {
+        self.verify_nonce_with(nonce, ReportDataLayout::Sm3LeftAligned)
+    }
+
+    /// Checks that the report was requested for the challenge `nonce`, as
+    /// bound in the report data by `layout`.
+    ///
+    /// Returns [`Error::ReportDataMismatch`] if the report wasn't requested
+    /// for `nonce`, and [`Error::InvalidLen`] if `nonce` doesn't fit the
+    /// layout.
+    pub fn verify_nonce_with(&self, nonce: &[u8], layout: ReportDataLayout) -> Result<(), Error> {
+        let expected = layout.report_data(nonce)?;
+
+        let mut report_data = self.body.report_data;
+        xor_with_anonce(&mut report_data, &self.anonce)?;
+
+        if !openssl::memcmp::eq(&report_data, &expected) {
+            return Err(Error::ReportDataMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Verifies the report's signature with `key`, e.g. the PEK of a chain
+    /// the caller verified beforehand.
+    ///
+    /// The signature is checked under the SM2 user ID
+    /// [`sm2_id`](crate::crypto::sm::sm2_id), as keys carry none of their
+    /// own. See [`Self::verify_with_key_id`] for keys whose certificate has
+    /// a user ID, and [`Self::verify_full`] to verify the chain as well.
+    pub fn verify_with_key(&self, key: &PubKey) -> Result<(), Error> {
+        self.verify_with_key_id(key, &crate::crypto::sm::sm2_id())
+    }
+
+    /// Verifies the report's signature with `key` under the SM2 user ID
+    /// `id`, e.g. [`Certificate::sm2_id`] of the key's certificate.
+    ///
+    /// Returns [`Error::BadSignature`] if the report isn't signed by `key`.
+    pub fn verify_with_key_id(&self, key: &PubKey, id: &[u8]) -> Result<(), Error> {
+        self.check_sig_algo(key)?;
+
+        let sig: Signature = self.try_into()?;
+        let key = PublicKey {
+            id: None,
+            key: key.key,
+            usage: key.usage,
+            algo: Some(key.algo),
+        };
+
+        key.verify(self, id, &sig).map_err(|_| Error::BadSignature)
+    }
+
+    /// Checks that the signature algorithm the report claims works on the
+    /// curve of `key`, before verifying the signature with it.
+    ///
+    /// Returns [`Error::AlgoCurveMismatch`] if it doesn't.
+    fn check_sig_algo(&self, key: &PubKey) -> Result<(), Error> {
+        match (Algorithm::from(self.sig_algo ^ self.anonce), key.key.g) {
+            (Algorithm::SM2_SA | Algorithm::SM2_DH, Group::SM2_256) => Ok(()),
+            _ => Err(Error::AlgoCurveMismatch),
+        }
+    }
+
+    /// Verifies the attestation report all the way up to the HRK.
+    ///
+    /// On success, returns the PEK the report verified with and the serial
+    /// number of the chip, e.g. for logging.
+    ///
+    /// The signer evidence's hmac is checked against `mnonce` first, then
+    /// `chain` is verified and the PEK embedded in the evidence is checked
+    /// against its CEK. Finally the report's signature is verified with the
+    /// PEK.
+    ///
+    /// `signer` must be the evidence as produced by the firmware, i.e. not
+    /// yet restored by [`ReportSigner::verified`].
+    ///
+    /// ```
+    /// use codicon::Decoder;
+    /// use csv_rs::{
+    ///     api::guest::{AttestationReport, ReportSigner},
+    ///     certs::{builtin::HRK, ca, csv, kds},
+    /// };
+    ///
+    /// let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+    /// let mut report = AttestationReport::decode(&mut evidence, ()).unwrap();
+    /// let signer = ReportSigner::decode(&mut evidence, ()).unwrap();
+    ///
+    /// let mut bundle = &include_bytes!("../../../tests/test_data/hsk_cek.cert")[..];
+    /// let chain = kds::Chain {
+    ///     ca: ca::Chain {
+    ///         hsk: ca::Certificate::decode(&mut bundle, ()).unwrap(),
+    ///         hrk: ca::Certificate::decode(&mut &HRK[..], ()).unwrap(),
+    ///     },
+    ///     cek: csv::Certificate::decode(&mut bundle, ()).unwrap(),
+    /// };
+    ///
+    /// // The mnonce requested by the guest, the report carries it masked
+    /// // with the anonce.
+    /// let mut mnonce = report.body.mnonce;
+    /// for (i, b) in mnonce.iter_mut().enumerate() {
+    ///     *b ^= report.anonce.to_le_bytes()[i % 4];
+    /// }
+    ///
+    /// let outcome = report.verify_full(&signer, &chain, &mnonce).unwrap();
+    /// assert!(outcome.sn.starts_with(b"NZA9T14052605"));
+    ///
+    /// // Any change to the signed body breaks the report's signature.
+    /// report.body.measure[0] ^= 1;
+    /// assert!(report.verify_full(&signer, &chain, &mnonce).is_err());
+    /// ```
+    pub fn verify_full(
+        &self,
+        signer: &ReportSigner,
+        chain: &kds::Chain,
+        mnonce: &[u8; 16],
+    ) -> Result<VerifyOutcome, Error> {
+        self.verify_full_with(signer, chain, mnonce, &VerifyOptions::default())
+    }
+
+    /// Verifies the attestation report all the way up to the HRK, as
+    /// [`Self::verify_full`] does, with `options`.
+    ///
+    /// With [`VerifyOptions::verify_hmac`] disabled, `mnonce` is ignored
+    /// and the signer evidence is only restored, see the warning there.
+    pub fn verify_full_with(
+        &self,
+        signer: &ReportSigner,
+        chain: &kds::Chain,
+        mnonce: &[u8; 16],
+        options: &VerifyOptions,
+    ) -> Result<VerifyOutcome, Error> {
+        crate::crypto::check_backend()?;
+
+        if options.reject_zero_anonce && self.anonce == 0 {
+            return Err(Error::ZeroAnonce);
+        }
+
+        let outcome = self.verify_traced(signer, chain, mnonce, options, &mut Vec::new())?;
+        if let Some(min) = options.min_version {
+            let got = outcome.report_version();
+            if got < min {
+                return Err(Error::ReportVersionTooOld { got, min });
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Verifies the attestation report as [`Self::verify_full`] does,
+    /// recording each check along the way.
+    ///
+    /// The trace lists the checks that ran, in order, with how long each
+    /// took: on failure, the last one is the check that failed, with the
+    /// error of the result. Checks before the first of the trace, e.g. of
+    /// the OpenSSL backend, don't appear.
+    ///
+    /// ```
+    /// use csv_rs::{
+    ///     api::guest::{VerifyCheck, VerifyStep},
+    ///     testutil::{make_report, ReportParams},
+    /// };
+    ///
+    /// let (response, chain, mnonce) = make_report(ReportParams::default());
+    /// let (report, signer) = (&response.report, &response.signer);
+    ///
+    /// let (trace, result) = report.verify_full_traced(signer, &chain, &mnonce);
+    /// assert!(result.is_ok());
+    /// assert!(trace.iter().all(|step: &VerifyStep| step.passed));
+    ///
+    /// // A mnonce the guest never asked for fails the first check.
+    /// let (trace, result) = report.verify_full_traced(signer, &chain, &[0u8; 16]);
+    /// assert!(result.is_err());
+    /// let checks: Vec<_> = trace.iter().map(|step| (step.check, step.passed)).collect();
+    /// assert_eq!(checks, [(VerifyCheck::Mnonce, false)]);
+    /// ```
+    pub fn verify_full_traced(
+        &self,
+        signer: &ReportSigner,
+        chain: &kds::Chain,
+        mnonce: &[u8; 16],
+    ) -> (Vec<VerifyStep>, Result<VerifyOutcome, Error>) {
+        let mut trace = Vec::new();
+        let result = crate::crypto::check_backend().and_then(|()| {
+            self.verify_traced(signer, chain, mnonce, &VerifyOptions::default(), &mut trace)
+        });
+
+        (trace, result)
+    }
+
+    fn verify_traced(
+        &self,
+        signer: &ReportSigner,
+        chain: &kds::Chain,
+        mnonce: &[u8; 16],
+        options: &VerifyOptions,
+        trace: &mut Vec<VerifyStep>,
+    ) -> Result<VerifyOutcome, Error> {
+        let mut signer = signer.clone();
+        if options.verify_hmac {
+            let real_mnonce = trace_step(trace, VerifyCheck::Mnonce, || {
+                recover_mnonce(mnonce, &self.body.mnonce, &self.anonce)
+            })?;
+            trace_step(trace, VerifyCheck::Hmac, || signer.check_hmac(&real_mnonce))?;
+        }
+        signer.restore(&self.anonce)?;
+
+        let ca = &chain.ca;
+        trace_step(trace, VerifyCheck::ChainLink(1), || {
+            Ok((&ca.hrk, &ca.hrk).verify()?)
+        })?;
+        trace_step(trace, VerifyCheck::ChainLink(2), || {
+            Ok((&ca.hrk, &ca.hsk).verify()?)
+        })?;
+        trace_step(trace, VerifyCheck::ChainLink(3), || {
+            Ok((&ca.hsk, &chain.cek).verify()?)
+        })?;
+
+        // The certificates embedded in the evidence, the PEK first, each
+        // signed by the next one and the last one by the CEK.
+        let mut certs = trace_step(trace, VerifyCheck::ChainLink(4), || {
+            let certs = signer.pek_certificates()?;
+            (&chain.cek, certs.last().unwrap()).verify()?;
+            Ok(certs)
+        })?;
+        for (link, pair) in (5..).zip(certs.windows(2).rev()) {
+            trace_step(trace, VerifyCheck::ChainLink(link), || {
+                Ok((&pair[1], &pair[0]).verify()?)
+            })?;
+        }
+
+        let pek = certs.swap_remove(0);
+        trace_step(trace, VerifyCheck::ReportSignature, || {
+            self.check_sig_algo(&pek.body.data.pubkey)?;
+            (&pek, self).verify().map_err(|_| Error::BadSignature)
+        })?;
+
+        Ok(VerifyOutcome {
+            verified_by: pek.body.data.pubkey,
+            pek,
+            sn: signer.sn,
+        })
+    }
+
+    /// Verifies the report as [`Self::verify_full`] does against each of
+    /// `chains` in turn, for verifiers of mixed fleets not knowing which
+    /// HSK endorses the chip.
+    ///
+    /// Returns the index of the first chain that verifies the report. The
+    /// hmac doesn't depend on the chain, if it's wrong the error is returned
+    /// as is. Otherwise, if no chain verifies the report, returns
+    /// [`Error::NoChainVerifies`] with the error of each chain.
+    pub fn verify_any(
+        &self,
+        signer: &ReportSigner,
+        chains: &[kds::Chain],
+        mnonce: &[u8; 16],
+    ) -> Result<usize, Error> {
+        crate::crypto::check_backend()?;
+
+        let signer = signer.verified(mnonce, &self.body.mnonce, &self.anonce)?;
+
+        let mut errors = Vec::with_capacity(chains.len());
+        for (i, chain) in chains.iter().enumerate() {
+            match chain
+                .verify()
+                .map_err(Error::from)
+                .and_then(|cek| self.verify_with_cek(&signer, cek))
+            {
+                Ok(_) => return Ok(i),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        Err(Error::NoChainVerifies(errors))
+    }
+
+    /// Verifies the report with the PEK of restored signer evidence, and the
+    /// PEK with `cek`, the leaf of a chain verified beforehand.
+    pub(crate) fn verify_with_cek(
+        &self,
+        signer: &ReportSigner,
+        cek: &Certificate,
+    ) -> Result<VerifyOutcome, Error> {
+        let mut certs = signer.pek_certificates()?;
+        verify_pek_chain(cek, &certs)?;
+        let pek = certs.swap_remove(0);
+        self.check_sig_algo(&pek.body.data.pubkey)?;
+        (&pek, self).verify().map_err(|_| Error::BadSignature)?;
+
+        Ok(VerifyOutcome {
+            verified_by: pek.body.data.pubkey,
+            pek,
+            sn: signer.sn,
+        })
+    }
+
+    /// Lists the fields that differ between two reports, e.g. two reports
+    /// of the same VM taken while re-attesting it.
+    ///
+    /// The fields masked with the anonce are compared unmasked, so two
+    /// reports of an unchanged VM only differ in `anonce`, `mnonce` and
+    /// `sig`.
+    pub fn diff(&self, other: &AttestationReport) -> Vec<FieldDiff> {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .filter(|((_, left), (_, right))| left != right)
+            .map(|((field, left), (_, right))| FieldDiff { field, left, right })
+            .collect()
+    }
+
+    /// The fields of the report as compared by [`Self::diff`].
+    fn fields(&self) -> [(&'static str, Vec<u8>); 11] {
+        let unmask = |data: &[u8]| -> Vec<u8> {
+            let mut data = data.to_vec();
+            xor_with_anonce(&mut data, &self.anonce).expect("masking can't fail");
+            data
+        };
+        let body = &self.body;
+
+        [
+            ("user_pubkey_digest", unmask(&body.user_pubkey_digest)),
+            ("vm_id", unmask(&body.vm_id)),
+            ("vm_version", unmask(&body.vm_version)),
+            ("report_data", unmask(&body.report_data)),
+            ("mnonce", unmask(&body.mnonce)),
+            ("measure", unmask(&body.measure)),
+            ("policy", unmask(&body.policy.0.to_le_bytes())),
+            ("sig_usage", unmask(&self.sig_usage.to_le_bytes())),
+            ("sig_algo", unmask(&self.sig_algo.to_le_bytes())),
+            ("anonce", self.anonce.to_le_bytes().to_vec()),
+            ("sig", [&self.sig.r[..], &self.sig.s[..]].concat()),
+        ]
+    }
+}
+
+impl codicon::Decoder<()> for AttestationReport {
+    type Error = std::io::Error;
+
+    fn decode(mut reader: impl Read, _: ()) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            body: reader.load()?,
+            sig_usage: reader.load()?,
+            sig_algo: reader.load()?,
+            anonce: reader.load()?,
+            sig: reader.load()?,
+        })
+    }
+}
+
+impl codicon::Encoder<()> for AttestationReport {
+    type Error = std::io::Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> Result<(), std::io::Error> {
+        writer.save(&self.body)?;
+        writer.save(&self.sig_usage)?;
+        writer.save(&self.sig_algo)?;
+        writer.save(&self.anonce)?;
+        writer.save(&self.sig)
+    }
+}
+
+impl codicon::Encoder<crate::Body> for AttestationReport {
+    type Error = std::io::Error;
+
+    fn encode(&self, mut writer: impl Write, _: crate::Body) -> Result<(), std::io::Error> {
+        writer.write_all(&self.body.to_signed_bytes())
+    }
+}
+
+impl TryFrom<&AttestationReport> for Signature {
+    type Error = std::io::Error;
+
+    #[inline]
+    fn try_from(value: &AttestationReport) -> Result<Self, std::io::Error> {
+        let sig = value.signature().to_der()?;
+        Ok(Self {
+            sig,
+            id: None,
+            usage: Usage::PEK,
+            algo: None,
+        })
+    }
+}
+
+impl Verifiable for (&Certificate, &AttestationReport) {
+    type Output = ();
+
+    fn verify(self) -> Result<(), std::io::Error> {
+        let key: PublicKey = self.0.try_into()?;
+        let sig: Signature = self.1.try_into()?;
+        key.verify(self.1, &self.0.sm2_id(), &sig)
+    }
+}
+
+bitfield! {
+    /// The firmware associates each guest with a guest policy that the guest owner provides. The
+    /// firmware restricts what actions the hypervisor can take on the guest according to the guest policy.
+    /// The policy also indicates the minimum firmware version to for the guest.
+    ///
+    /// The guest owner provides the guest policy to the firmware during launch. The firmware then binds
+    /// the policy to the guest. The policy cannot be changed throughout the lifetime of the guest. The
+    /// policy is also migrated with the guest and enforced by the destination platform firmware.
+    ///
+    /// | Bit(s) | Name           | Description                                                                                 >
+    /// |--------|----------------|--------------------------------------------------------------------------------------------->
+    /// | 0      | NODBG          | Debugging of the guest is disallowed when set                                               >
+    /// | 1      | NOKS           | Sharing keys with other guests is disallowed when set                                       >
+    /// | 2      | ES             | CSV2 is required when set                                                                   >
+    /// | 3      | NOSEND         | Sending the guest to another platform is disallowed when set                                >
+    /// | 4      | DOMAIN         | The guest must not be transmitted to another platform that is not in the domain when set.   >
+    /// | 5      | CSV            | The guest must not be transmitted to another platform that is not CSV capable when set.     >
+    /// | 6      | CSV3           | The guest must not be transmitted to another platform that is not CSV3 capable when set.    >
+    /// | 7      | ASID_REUSE     | Sharing asids with other guests owned by same user is allowed when set                      >
+    /// | 11:8   | HSK_VERSION    | The guest must not be transmitted to another platform with a lower HSK version.             >
+    /// | 15:12  | CEK_VERSION    | The guest must not be transmitted to another platform with a lower CEK version.             >
+    /// | 23:16  | API_MAJOR      | The guest must not be transmitted to another platform with a lower platform version.        >
+    /// | 31:24  | API_MINOR      | The guest must not be transmitted to another platform with a lower platform version.        >
+    #[repr(C)]
+    #[derive(Copy, Clone, Serialize, Deserialize, Default)]
+    pub struct GuestPolicy(u32);
+    impl Debug;
+    pub nodbg, _: 0, 0;
+    pub noks, _: 1, 1;
+    pub es, _: 2, 2;
+    pub nosend, _: 3, 3;
+    pub domain, _: 4, 4;
+    pub csv, _: 5, 5;
+    pub csv3, _: 6, 6;
+    pub asid_reuse, _: 7, 7;
+    pub hsk_version, _: 11, 8;
+    pub cek_version, _: 15, 12;
+    pub api_major, _: 23, 16;
+    pub api_minor, _: 31, 24;
+}
+
+impl GuestPolicy {
+    #[allow(dead_code)]
+    pub fn xor(&self, anonce: &u32) -> Self {
+        Self(self.0 ^ anonce)
+    }
+
+    /// The raw bits of the policy.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for GuestPolicy {
+    fn from(bits: u32) -> Self {
+        Self(bits)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReportSigner {
+    #[serde(with = "BigArray")]
+    pub pek_cert: [u8; 2084],
+    #[serde(with = "BigArray")]
+    pub sn: [u8; 64],
+    pub reserved: [u8; 32],
+    pub mac: [u8; 32],
+}
+
+/// Whether `current` is an anonce issued after `prev`, for monitors that
+/// attest the same guest repeatedly.
+///
+/// Anonces are compared in serial number arithmetic (RFC 1982): `current`
+/// is ahead of `prev` if it is at most 2^31 - 1 steps past it, wrapping
+/// around `u32::MAX`. So `0` advances `u32::MAX`, while an anonce equal to
+/// `prev`, or exactly 2^31 steps away from it, doesn't advance it.
+///
+/// ```
+/// use csv_rs::api::guest::check_anonce_advanced;
+///
+/// assert!(check_anonce_advanced(1, 2));
+/// assert!(check_anonce_advanced(u32::MAX, 0));
+/// assert!(!check_anonce_advanced(2, 1));
+/// ```
+pub fn check_anonce_advanced(prev: u32, current: u32) -> bool {
+    let distance = current.wrapping_sub(prev);
+    distance != 0 && distance < 1 << 31
+}
+
+/// The `user_pubkey_digest` of an owner key given as a DER
+/// SubjectPublicKeyInfo, as exported by e.g. `openssl pkey -pubout -outform
+/// DER`.
+///
+/// The firmware doesn't hash the key: the guest owner hands the digest over
+/// with the launch session, see
+/// [`crate::api::launch::SessionBody::pubkey_digest`], and the
+/// firmware reports it as is. The digest is thus of whatever encoding the
+/// owner's tooling hashed, the same key gives another digest in another
+/// encoding: the DER SubjectPublicKeyInfo is the usual one, see
+/// [`user_pubkey_digest_from_sec1`] for tooling hashing the bare point, and
+/// [`AttestationReport::verify_data`] for keys in the layout of certificates.
+///
+/// The digest is the SM3 of `der` as is, which isn't parsed. Fails with
+/// [`Error::UnsupportedBackend`] if the OpenSSL backend lacks SM3.
+pub fn user_pubkey_digest_from_der(der: &[u8]) -> Result<[u8; 32], Error> {
+    sm3(der)
+}
+
+/// The `user_pubkey_digest` of an owner key given as a SEC1 encoded point,
+/// e.g. `04 || x || y` with big endian coordinates for an uncompressed one.
+///
+/// The digest is the SM3 of `point` as is, so a compressed and an
+/// uncompressed point of the same key give different digests; see
+/// [`user_pubkey_digest_from_der`] for choosing the encoding.
+pub fn user_pubkey_digest_from_sec1(point: &[u8]) -> Result<[u8; 32], Error> {
+    sm3(point)
+}
+
+fn sm3(data: &[u8]) -> Result<[u8; 32], Error> {
+    crate::crypto::check_backend()?;
+    let digest = hash::hash(MessageDigest::sm3(), data)?;
+
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&digest);
+    Ok(out)
+}
+
+pub(crate) fn xor_with_anonce(data: &mut [u8], anonce: &u32) -> Result<(), Error> {
+    let mut anonce_array = [0u8; 4];
+    anonce_array[..].copy_from_slice(&anonce.to_le_bytes());
+
+    for (index, item) in data.iter_mut().enumerate() {
+        *item ^= anonce_array[index % 4];
+    }
+
+    Ok(())
+}
+
+/// Unmasks the `mnonce` of a report with its `anonce` and checks that it is
+/// `input_mnonce`, the one requested by the guest.
+fn recover_mnonce(input_mnonce: &[u8], mnonce: &[u8], anonce: &u32) -> Result<Vec<u8>, Error> {
+    let mut real_mnonce = Vec::from(mnonce);
+    xor_with_anonce(&mut real_mnonce, anonce)?;
+
+    if real_mnonce != input_mnonce {
+        return Err(Error::MnonceMismatch {
+            expected: to_mnonce(input_mnonce),
+            got: to_mnonce(&real_mnonce),
+        });
+    }
+
+    Ok(real_mnonce)
+}
+
+pub(super) fn to_mnonce(data: &[u8]) -> [u8; 16] {
+    let mut mnonce = [0u8; 16];
+    let len = data.len().min(mnonce.len());
+    mnonce[..len].copy_from_slice(&data[..len]);
+    mnonce
+}
+
+impl ReportSigner {
+    /// Verifies the signature evidence's hmac.
+    ///
+    /// `input_mnonce` is the mnonce requested by the guest, `mnonce` and
+    /// `anonce` are the ones carried by the attestation report. On success
+    /// returns the evidence with its PEK certificate and serial number
+    /// restored, the reserved bytes as the firmware wrote them. The evidence
+    /// itself is left masked, so that it verifies again: use the returned
+    /// one for its PEK certificate and serial number.
+    ///
+    /// Returns [`Error::MnonceMismatch`] if the report doesn't carry the
+    /// requested mnonce and [`Error::BadSignature`] if the hmac is wrong.
+    ///
+    /// A zero `anonce` masks nothing: the report's mnonce must then equal
+    /// `input_mnonce` and the evidence is left as is. See
+    /// [`VerifyOptions::reject_zero_anonce`] to refuse such reports.
+    ///
+    /// ```
+    /// use codicon::Decoder;
+    /// use csv_rs::api::guest::{AttestationReport, ReportSigner};
+    ///
+    /// let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+    /// let report = AttestationReport::decode(&mut evidence, ()).unwrap();
+    /// let signer = ReportSigner::decode(&mut evidence, ()).unwrap();
+    ///
+    /// let mut mnonce = report.body.mnonce;
+    /// for (i, b) in mnonce.iter_mut().enumerate() {
+    ///     *b ^= report.anonce.to_le_bytes()[i % 4];
+    /// }
+    ///
+    /// // A mnonce the guest never asked for is rejected.
+    /// assert!(signer
+    ///     .verified(&[0u8; 16], &report.body.mnonce, &report.anonce)
+    ///     .is_err());
+    ///
+    /// let restored = signer
+    ///     .verified(&mnonce, &report.body.mnonce, &report.anonce)
+    ///     .unwrap();
+    /// assert!(restored.sn.starts_with(b"NZA9T14052605"));
+    /// ```
+    #[must_use = "the evidence is left masked, the restored one is returned"]
+    pub fn verified(
+        &self,
+        input_mnonce: &[u8],
+        mnonce: &[u8],
+        anonce: &u32,
+    ) -> Result<ReportSigner, Error> {
+        let real_mnonce = recover_mnonce(input_mnonce, mnonce, anonce)?;
+        self.check_hmac(&real_mnonce)?;
+
+        // restore pek cert and serial number.
+        let mut restored = self.clone();
+        restored.restore(anonce)?;
+
+        Ok(restored)
+    }
+
+    /// Checks the hmac of the evidence as the firmware wrote it, keyed by
+    /// the unmasked mnonce.
+    fn check_hmac(&self, real_mnonce: &[u8]) -> Result<(), Error> {
+        let key = pkey::PKey::hmac(real_mnonce)?;
+        let mut sig = sign::Signer::new(MessageDigest::sm3(), &key)?;
+
+        sig.update(&self.pek_cert)?;
+        sig.update(&self.sn)?;
+        sig.update(&self.reserved)?;
+
+        if sig.sign_to_vec()? != self.mac {
+            return Err(Error::BadSignature);
+        }
+
+        Ok(())
+    }
+
+    /// The reserved bytes of the evidence, as the firmware wrote them.
+    ///
+    /// They are covered by the hmac, and kept by [`Self::verify`].
+    pub fn reserved(&self) -> &[u8; 32] {
+        &self.reserved
+    }
+
+    /// The serial number of the chip, from restored signer evidence.
+    ///
+    /// The serial number is read up to its first NUL byte.
+    pub fn serial_number(&self) -> String {
+        let len = self
+            .sn
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.sn.len());
+        String::from_utf8_lossy(&self.sn[..len]).into_owned()
+    }
+
+    /// Whether two restored signer evidences come from the same chip, i.e.
+    /// carry the same serial number and PEK.
+    ///
+    /// The serial numbers are compared in constant time, the PEKs by their
+    /// encoding. Evidence whose PEK certificate doesn't decode matches
+    /// nothing.
+    pub fn same_chip_as(&self, other: &ReportSigner) -> bool {
+        if !openssl::memcmp::eq(&self.sn, &other.sn) {
+            return false;
+        }
+
+        match (self.pek_certificate(), other.pek_certificate()) {
+            (Ok(pek), Ok(other)) => pek.to_der() == other.to_der(),
+            _ => false,
+        }
+    }
+
+    /// Decodes the PEK certificate of restored signer evidence.
+    ///
+    /// The certificate is decoded from as many bytes as its version says,
+    /// whatever the bytes padding `pek_cert` are. Nothing is trimmed, so a
+    /// certificate legitimately ending in zero bytes decodes intact.
+    pub fn pek_certificate(&self) -> Result<Certificate, Error> {
+        let ver = u32::from_le_bytes(self.pek_cert[..4].try_into().unwrap());
+        let len = Certificate::encoded_len(ver)
+            .filter(|&len| len <= self.pek_cert.len())
+            .ok_or(Error::InvalidCertificate)?;
+
+        Ok(Certificate::decode(&mut &self.pek_cert[..len], ())?)
+    }
+
+    /// Decodes the certificates embedded in restored signer evidence: the
+    /// PEK, followed by the certificates endorsing it up to the one the CEK
+    /// signs, for firmware embedding them.
+    ///
+    /// The firmware currently embeds the PEK only, it fills `pek_cert`, see
+    /// [`Self::pek_certificate`] for that common case. Returns
+    /// [`Error::InvalidCertificate`] if not even the PEK decodes.
+    pub fn pek_certificates(&self) -> Result<Vec<Certificate>, Error> {
+        let certs = decode_certificates(&self.pek_cert);
+        if certs.is_empty() {
+            return Err(Error::InvalidCertificate);
+        }
+
+        Ok(certs)
+    }
+
+    pub(crate) fn restore(&mut self, anonce: &u32) -> Result<(), Error> {
+        xor_with_anonce(&mut self.pek_cert, anonce)?;
+        xor_with_anonce(&mut self.sn, anonce)?;
+
+        Ok(())
+    }
+}
+
+impl codicon::Decoder<()> for ReportSigner {
+    type Error = std::io::Error;
+
+    fn decode(mut reader: impl Read, _: ()) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            pek_cert: reader.load()?,
+            sn: reader.load()?,
+            reserved: reader.load()?,
+            mac: reader.load()?,
+        })
+    }
+}
+
+impl codicon::Encoder<()> for ReportSigner {
+    type Error = std::io::Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> Result<(), std::io::Error> {
+        writer.write_all(&self.pek_cert)?;
+        writer.write_all(&self.sn)?;
+        writer.write_all(&self.reserved)?;
+        writer.write_all(&self.mac)
+    }
+}
+
+impl Default for ReportSigner {
+    fn default() -> Self {
+        Self {
+            pek_cert: [0u8; 2084],
+            sn: [0u8; 64],
+            reserved: Default::default(),
+            mac: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    mod attestation_report {
+        use crate::api::guest::types::*;
+        use crate::api::guest::{ReportDataSource, Sm3Blob};
+        use codicon::Decoder;
+
+        const ANONCE: u32 = 0x1291429b;
+
+        #[test]
+        pub fn test_user_pubkey_digest_algo() {
+            let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let report = AttestationReport::decode(&mut evidence, ()).unwrap();
+            assert_eq!(
+                report.user_pubkey_digest_algo().unwrap(),
+                DigestAlgorithm::Sm3
+            );
+
+            let report = AttestationReport {
+                sig_algo: 0xffff ^ ANONCE,
+                anonce: ANONCE,
+                ..Default::default()
+            };
+            assert!(matches!(
+                report.user_pubkey_digest_algo(),
+                Err(Error::Unsupported)
+            ));
+        }
+
+        #[test]
+        pub fn test_expect_user_pubkey() {
+            let pubkey = b"guest owner public key";
+
+            let mut report = AttestationReport {
+                sig_algo: 4 ^ ANONCE,
+                anonce: ANONCE,
+                ..Default::default()
+            };
+
+            let digest = DigestAlgorithm::Sm3.digest(pubkey).unwrap();
+            report.body.user_pubkey_digest.copy_from_slice(&digest);
+            xor_with_anonce(&mut report.body.user_pubkey_digest, &ANONCE).unwrap();
+
+            report.expect_user_pubkey(pubkey).unwrap();
+            assert!(matches!(
+                report.expect_user_pubkey(b"someone else's key"),
+                Err(Error::InvalidKey)
+            ));
+        }
+
+        #[test]
+        pub fn test_user_pubkey_digest_encodings() {
+            // The generator of the SM2 curve, as an uncompressed SEC1 point
+            // and as a DER SubjectPublicKeyInfo.
+            let point = hex::decode(
+                "0432c4ae2c1f1981195f9904466a39c9948fe30bbff2660be1715a4589334c74c7\
+                 bc3736a2f4f6779c59bdcee36b692153d0a9877cc62a474002df32e52139f0a0",
+            )
+            .unwrap();
+            let mut der =
+                hex::decode("3059301306072a8648ce3d020106082a811ccf5501822d034200").unwrap();
+            der.extend_from_slice(&point);
+
+            let from_sec1 = user_pubkey_digest_from_sec1(&point).unwrap();
+            let from_der = user_pubkey_digest_from_der(&der).unwrap();
+            assert_eq!(
+                hex::encode(from_sec1),
+                "1a8ebc45db0c198888a115984193ff485315b84e2967092ddc5814274448410c"
+            );
+            assert_eq!(
+                hex::encode(from_der),
+                "169d8f42e18d9bfadbdf445f48e035736b43f4f75137d5577fe524d66c8ce6e8"
+            );
+
+            let mut report = AttestationReport {
+                sig_algo: 4 ^ ANONCE,
+                anonce: ANONCE,
+                ..Default::default()
+            };
+            report.body.user_pubkey_digest = from_der;
+            xor_with_anonce(&mut report.body.user_pubkey_digest, &ANONCE).unwrap();
+            report.expect_user_pubkey(&der).unwrap();
+            assert!(report.expect_user_pubkey(&point).is_err());
+        }
+
+        #[test]
+        pub fn test_verify_full_traced() {
+            use crate::testutil::{make_report, ReportParams};
+
+            let checks = |trace: &[VerifyStep]| -> Vec<_> {
+                trace.iter().map(|step| (step.check, step.passed)).collect()
+            };
+            let (mut response, chain, mnonce) = make_report(ReportParams::default());
+
+            let (trace, result) =
+                response
+                    .report
+                    .verify_full_traced(&response.signer, &chain, &mnonce);
+            assert!(result.is_ok());
+            assert_eq!(
+                checks(&trace),
+                [
+                    (VerifyCheck::Mnonce, true),
+                    (VerifyCheck::Hmac, true),
+                    (VerifyCheck::ChainLink(1), true),
+                    (VerifyCheck::ChainLink(2), true),
+                    (VerifyCheck::ChainLink(3), true),
+                    (VerifyCheck::ChainLink(4), true),
+                    (VerifyCheck::ReportSignature, true),
+                ]
+            );
+
+            // The trace ends with the failing check, with its error.
+            response.signer.mac[0] ^= 1;
+            let (trace, result) =
+                response
+                    .report
+                    .verify_full_traced(&response.signer, &chain, &mnonce);
+            assert!(matches!(result, Err(Error::BadSignature)));
+            assert_eq!(
+                checks(&trace),
+                [(VerifyCheck::Mnonce, true), (VerifyCheck::Hmac, false)]
+            );
+            response.signer.mac[0] ^= 1;
+
+            response.report.body.measure[0] ^= 1;
+            let (trace, result) =
+                response
+                    .report
+                    .verify_full_traced(&response.signer, &chain, &mnonce);
+            assert!(matches!(result, Err(Error::BadSignature)));
+            assert_eq!(
+                trace.last().map(|step| (step.check, step.passed)),
+                Some((VerifyCheck::ReportSignature, false))
+            );
+            assert_eq!(trace.len(), 7);
+        }
+
+        #[test]
+        pub fn test_verify_data() {
+            use crate::crypto::sm;
+
+            let (pubkey, prv) = PubKey::generate(Usage::PEK, None).unwrap();
+            let (other, _) = PubKey::generate(Usage::PEK, None).unwrap();
+            let msg = b"tls key of the guest";
+            let sig = sm::SM2::sign(prv.key, &sm::sm2_id(), msg).unwrap();
+
+            let mut report = AttestationReport {
+                sig_algo: 4 ^ ANONCE,
+                anonce: ANONCE,
+                ..Default::default()
+            };
+
+            let mut encoded = Vec::new();
+            encoded.save(&pubkey).unwrap();
+            let digest = DigestAlgorithm::Sm3.digest(&encoded).unwrap();
+            report.body.user_pubkey_digest.copy_from_slice(&digest);
+            xor_with_anonce(&mut report.body.user_pubkey_digest, &ANONCE).unwrap();
+
+            report.verify_data(&pubkey, msg, &sig).unwrap();
+            assert!(matches!(
+                report.verify_data(&pubkey, b"something else", &sig),
+                Err(Error::BadSignature)
+            ));
+            assert!(matches!(
+                report.verify_data(&other, msg, &sig),
+                Err(Error::InvalidKey)
+            ));
+        }
+
+        #[test]
+        pub fn test_signature() {
+            let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let report = AttestationReport::decode(&mut evidence, ()).unwrap();
+
+            let sig = report.signature();
+            assert_eq!(sig.r(), &report.sig.r[..32]);
+            assert_eq!(sig.s(), &report.sig.s[..32]);
+            assert_eq!(sig.to_der().unwrap(), Vec::try_from(&report.sig).unwrap());
+
+            let again = sig::Signature::from_der(&sig.to_der().unwrap(), Group::SM2_256).unwrap();
+            assert_eq!(again, sig);
+        }
+
+        #[test]
+        pub fn test_anonce() {
+            let report = AttestationReport {
+                anonce: ANONCE,
+                ..Default::default()
+            };
+            assert_eq!(report.anonce(), ANONCE);
+        }
+
+        #[test]
+        pub fn test_check_anonce_advanced() {
+            assert!(check_anonce_advanced(ANONCE, ANONCE + 1));
+            assert!(check_anonce_advanced(ANONCE, ANONCE + (1 << 31) - 1));
+            assert!(!check_anonce_advanced(ANONCE, ANONCE));
+            assert!(!check_anonce_advanced(ANONCE + 1, ANONCE));
+        }
+
+        #[test]
+        pub fn test_check_anonce_advanced_wraparound() {
+            assert!(check_anonce_advanced(u32::MAX, 0));
+            assert!(check_anonce_advanced(u32::MAX - 1, 5));
+            assert!(!check_anonce_advanced(0, u32::MAX));
+            assert!(!check_anonce_advanced(0, 1 << 31));
+            assert!(check_anonce_advanced(1 << 31, u32::MAX));
+        }
+
+        #[test]
+        pub fn test_verify_with_key() {
+            let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let report = AttestationReport::decode(&mut evidence, ()).unwrap();
+            let signer = ReportSigner::decode(&mut evidence, ()).unwrap();
+
+            let mut mnonce = report.body.mnonce;
+            xor_with_anonce(&mut mnonce, &report.anonce).unwrap();
+            let signer = signer
+                .verified(&mnonce, &report.body.mnonce, &report.anonce)
+                .unwrap();
+            let pek = signer.pek_certificate().unwrap();
+
+            report
+                .verify_with_key_id(&pek.body.data.pubkey, &pek.sm2_id())
+                .unwrap();
+
+            // The PEK signs under the user ID of its certificate.
+            assert!(matches!(
+                report.verify_with_key(&pek.body.data.pubkey),
+                Err(Error::BadSignature)
+            ));
+
+            let (other, _) = PubKey::generate(Usage::PEK, None).unwrap();
+            assert!(matches!(
+                report.verify_with_key_id(&other, &pek.sm2_id()),
+                Err(Error::BadSignature)
+            ));
+        }
+
+        #[test]
+        pub fn test_check_sig_algo() {
+            let evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let report = AttestationReport::decode(&mut &evidence[..], ()).unwrap();
+            let (key, _) = PubKey::generate(Usage::PEK, None).unwrap();
+            report.check_sig_algo(&key).unwrap();
+
+            // A report claiming an algorithm of no SM2 curve.
+            let mut ecdsa = AttestationReport::decode(&mut &evidence[..], ()).unwrap();
+            ecdsa.sig_algo = 0x0001 ^ ecdsa.anonce;
+            assert!(matches!(
+                ecdsa.verify_with_key(&key),
+                Err(Error::AlgoCurveMismatch)
+            ));
+
+            // An SM2 report checked against a key of another curve.
+            let mut other = key;
+            other.key.g = bincode::deserialize(&7u32.to_le_bytes()).unwrap();
+            assert!(matches!(
+                report.verify_with_key(&other),
+                Err(Error::AlgoCurveMismatch)
+            ));
+        }
+
+        #[test]
+        pub fn test_freshness_token() {
+            let mut report = AttestationReport {
+                anonce: ANONCE,
+                ..Default::default()
+            };
+            let mnonce: [u8; 16] = std::array::from_fn(|i| i as u8);
+            report.body.mnonce = mnonce;
+            xor_with_anonce(&mut report.body.mnonce, &ANONCE).unwrap();
+
+            let token = report.freshness_token();
+            assert_eq!(token[..16], mnonce);
+            assert_eq!(token[16..], [0x9b, 0x42, 0x91, 0x12]);
+        }
+
+        #[test]
+        pub fn test_write_to() {
+            let evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let report = AttestationReport::decode(&mut &evidence[..], ()).unwrap();
+
+            let mut buf = [0xa5u8; ATTESTATION_REPORT_LEN + 8];
+            assert_eq!(report.write_to(&mut buf).unwrap(), ATTESTATION_REPORT_LEN);
+            assert_eq!(
+                buf[..ATTESTATION_REPORT_LEN],
+                evidence[..ATTESTATION_REPORT_LEN]
+            );
+            assert_eq!(buf[ATTESTATION_REPORT_LEN..], [0xa5u8; 8]);
+
+            let mut small = [0u8; ATTESTATION_REPORT_LEN - 1];
+            assert!(matches!(
+                report.write_to(&mut small),
+                Err(Error::InvalidLen)
+            ));
+            assert!(small.iter().all(|&b| b == 0));
+        }
+
+        #[test]
+        pub fn test_parse_prefix() {
+            let evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let expected = AttestationReport::decode(&mut &evidence[..], ()).unwrap();
+
+            let (report, rest) = AttestationReport::parse_prefix(evidence).unwrap();
+            assert_eq!(rest, &evidence[ATTESTATION_REPORT_LEN..]);
+            assert_eq!(report.anonce, expected.anonce);
+            assert_eq!(report.signature(), expected.signature());
+
+            let (_, rest) =
+                AttestationReport::parse_prefix(&evidence[..ATTESTATION_REPORT_LEN]).unwrap();
+            assert!(rest.is_empty());
+
+            assert!(matches!(
+                AttestationReport::parse_prefix(&evidence[..ATTESTATION_REPORT_LEN - 1]),
+                Err(Error::InvalidLen)
+            ));
+        }
+
+        #[test]
+        pub fn test_ranges() {
+            use crate::crypto::sig::SM2_SIG_COMPONENT_LEN;
+            use std::mem::{offset_of, size_of};
+
+            let signed = AttestationReport::signed_range();
+            assert_eq!(signed.start, offset_of!(AttestationReport, body));
+            assert_eq!(signed.len(), size_of::<Body>());
+
+            let signature = AttestationReport::signature_range();
+            assert_eq!(signature.start, offset_of!(AttestationReport, sig));
+            assert_eq!(signature.len(), size_of::<ecdsa::Signature>());
+
+            let page = include_bytes!("../../../tests/test_data/report.cert");
+            let report = AttestationReport::decode(&mut &page[..], ()).unwrap();
+            assert_eq!(page[signed], report.body.to_signed_bytes()[..]);
+            assert_eq!(
+                page[signature.start..][..SM2_SIG_COMPONENT_LEN],
+                report.sig.r
+            );
+            assert_eq!(
+                page[signature.start + SM2_SIG_COMPONENT_LEN..signature.end],
+                report.sig.s
+            );
+        }
+
+        #[test]
+        pub fn test_signed_bytes() {
+            use crate::crypto::sm;
+
+            let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let recorded = AttestationReport::decode(&mut evidence, ()).unwrap();
+            let signed = recorded.body.to_signed_bytes();
+            assert_eq!(signed.len(), std::mem::size_of::<Body>());
+            assert_eq!(
+                signed[..],
+                include_bytes!("../../../tests/test_data/report.cert")[..signed.len()]
+            );
+
+            let (key, prv) = PubKey::generate(Usage::PEK, None).unwrap();
+            let sig = sm::SM2::sign(prv.key, sm::DEFAULT_SM2_ID, &signed).unwrap();
+
+            let report = AttestationReport {
+                body: recorded.body,
+                sig_usage: recorded.sig_usage,
+                sig_algo: recorded.sig_algo,
+                anonce: recorded.anonce,
+                sig: ecdsa::Signature::try_from(&sig[..]).unwrap(),
+            };
+            report.verify_with_key_id(&key, sm::DEFAULT_SM2_ID).unwrap();
+
+            let mut other = report;
+            other.body.report_data[0] ^= 1;
+            assert!(matches!(
+                other.verify_with_key_id(&key, sm::DEFAULT_SM2_ID),
+                Err(Error::BadSignature)
+            ));
+        }
+
+        #[test]
+        pub fn test_expect_measure() {
+            let expected = crate::measure::expected(&[b"OVMF", b"kernel"]).unwrap();
+
+            let mut report = AttestationReport {
+                anonce: ANONCE,
+                ..Default::default()
+            };
+            report.body.measure = expected;
+            xor_with_anonce(&mut report.body.measure, &ANONCE).unwrap();
+
+            report.expect_measure(&expected).unwrap();
+            assert!(matches!(
+                report.expect_measure(&[0; 32]),
+                Err(Error::BadMeasurement)
+            ));
+        }
+
+        #[test]
+        pub fn test_verify_policy_binding() {
+            use crate::testutil::{make_report, ReportParams};
+
+            // NODBG, NOKS and ES.
+            let expected = GuestPolicy::from(0b111);
+            let (response, ..) = make_report(ReportParams {
+                policy: expected.bits(),
+                ..Default::default()
+            });
+            response.report.verify_policy_binding(expected).unwrap();
+
+            // The same policy, allowing debugging.
+            let downgraded = GuestPolicy::from(0b110);
+            match response.report.verify_policy_binding(downgraded) {
+                Err(Error::PolicyMismatch { expected, got }) => {
+                    assert_eq!(expected, 0b110);
+                    assert_eq!(got, 0b111);
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        #[test]
+        pub fn test_verify_request_binding() {
+            let report = crate::testutil::golden_report();
+            let mut data = report.body.report_data;
+            xor_with_anonce(&mut data, &report.anonce).unwrap();
+
+            let req = ReportReq::new(Some(data), crate::testutil::GOLDEN_MNONCE).unwrap();
+            report.verify_request_binding(&req).unwrap();
+
+            let mut altered = data;
+            altered[63] ^= 1;
+            let req = ReportReq::new(Some(altered), crate::testutil::GOLDEN_MNONCE).unwrap();
+            assert!(matches!(
+                report.verify_request_binding(&req),
+                Err(Error::ReportDataMismatch)
+            ));
+
+            let req = ReportReq::new(Some(data), [0u8; 16]).unwrap();
+            assert!(matches!(
+                report.verify_request_binding(&req),
+                Err(Error::MnonceMismatch { expected, .. }) if expected == [0u8; 16]
+            ));
+        }
+
+        #[test]
+        pub fn test_verify_nonce() {
+            let nonce = b"challenge from the verifier";
+
+            let mut report = AttestationReport {
+                anonce: ANONCE,
+                ..Default::default()
+            };
+            report.body.report_data = Sm3Blob(nonce).report_data().unwrap();
+            xor_with_anonce(&mut report.body.report_data, &ANONCE).unwrap();
+
+            report.verify_nonce(nonce).unwrap();
+            assert!(matches!(
+                report.verify_nonce(b"another challenge"),
+                Err(Error::ReportDataMismatch)
+            ));
+
+            // The nonce itself isn't the binding.
+            report.body.report_data = [0u8; 64];
+            report.body.report_data[..nonce.len()].copy_from_slice(nonce);
+            xor_with_anonce(&mut report.body.report_data, &ANONCE).unwrap();
+            assert!(matches!(
+                report.verify_nonce(nonce),
+                Err(Error::ReportDataMismatch)
+            ));
+        }
+
+        #[test]
+        pub fn test_measurement() {
+            use crate::testutil::{make_report, ReportParams};
+
+            let (response, _, _) = make_report(ReportParams {
+                measure: [0x5a; 32],
+                ..Default::default()
+            });
+            assert_eq!(response.report.measurement(), [0x5a; 32]);
+            response.report.expect_measure(&[0x5a; 32]).unwrap();
+            assert_ne!(response.report.body.measure, [0x5a; 32]);
+        }
+
+        #[test]
+        pub fn test_report_data_as() {
+            use crate::testutil::{make_report, ReportParams};
+
+            #[repr(C)]
+            #[derive(Clone, Copy, Debug, PartialEq)]
+            struct Binding {
+                version: u32,
+                reserved: [u8; 28],
+                hash: [u8; 32],
+            }
+            unsafe impl FromBytes for Binding {}
+
+            let mut report_data = [0u8; 64];
+            report_data[..4].copy_from_slice(&7u32.to_ne_bytes());
+            report_data[32..].copy_from_slice(&[0xab; 32]);
+            let (response, _, _) = make_report(ReportParams {
+                report_data,
+                ..Default::default()
+            });
+            let report = response.report;
+
+            let binding: Binding = report.report_data_as().unwrap();
+            assert_eq!(
+                binding,
+                Binding {
+                    version: 7,
+                    reserved: [0; 28],
+                    hash: [0xab; 32],
+                }
+            );
+            assert_eq!(report.report_data_as::<[u8; 64]>().unwrap(), report_data);
+            assert_eq!(report.report_data_as::<u32>().unwrap(), 7);
+            assert!(matches!(
+                report.report_data_as::<[u8; 65]>(),
+                Err(Error::InvalidLen)
+            ));
+        }
+
+        #[test]
+        pub fn test_signing_digest() {
+            use crate::testutil::{golden_report, golden_restored_signer};
+
+            let report = golden_report();
+            let pek = golden_restored_signer().pek_certificate().unwrap();
+            let digest = report.signing_digest(&pek).unwrap();
+
+            let key = PublicKey::try_from(&pek).unwrap().key;
+            let sig = report.signature().to_der().unwrap();
+            assert!(sm::SM2::verify_digest(key, &sig, &digest).unwrap());
+
+            let mut tampered = report;
+            tampered.body.measure[0] ^= 1;
+            let digest = tampered.signing_digest(&pek).unwrap();
+            assert!(!sm::SM2::verify_digest(key, &sig, &digest).unwrap());
+        }
+
+        #[test]
+        pub fn test_verify_nonce_with() {
+            use crate::api::guest::ReportDataLayout::*;
+
+            let nonce = b"challenge from the verifier";
+            for layout in [Raw, Sm3LeftAligned, Sm3RightAligned] {
+                let mut report = AttestationReport {
+                    anonce: ANONCE,
+                    ..Default::default()
+                };
+                report.body.report_data = layout.report_data(nonce).unwrap();
+                xor_with_anonce(&mut report.body.report_data, &ANONCE).unwrap();
+                xor_with_anonce(&mut report.body.mnonce, &ANONCE).unwrap();
+
+                report.verify_nonce_with(nonce, layout).unwrap();
+                let req = ReportReq::with_layout(nonce, layout, [0u8; 16]).unwrap();
+                report.verify_request_binding(&req).unwrap();
+
+                for other in [Raw, Sm3LeftAligned, Sm3RightAligned] {
+                    if other != layout {
+                        assert!(matches!(
+                            report.verify_nonce_with(nonce, other),
+                            Err(Error::ReportDataMismatch)
+                        ));
+                    }
+                }
+                assert_eq!(report.verify_nonce(nonce).is_ok(), layout == Sm3LeftAligned);
+            }
+        }
+
+        #[test]
+        pub fn test_diff() {
+            let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let report = AttestationReport::decode(&mut evidence, ()).unwrap();
+            assert!(report.diff(&report).is_empty());
+
+            // The same VM attested again, with a new anonce and mnonce.
+            let mut evidence = &include_bytes!("../../../tests/test_data/report.cert")[..];
+            let mut other = AttestationReport::decode(&mut evidence, ()).unwrap();
+            let remask = ANONCE ^ 0x5a5a5a5a;
+            other.anonce ^= remask;
+            for data in [
+                &mut other.body.user_pubkey_digest[..],
+                &mut other.body.vm_id[..],
+                &mut other.body.vm_version[..],
+                &mut other.body.report_data[..],
+                &mut other.body.mnonce[..],
+                &mut other.body.measure[..],
+            ] {
+                xor_with_anonce(data, &remask).unwrap();
+            }
+            other.body.policy = other.body.policy.xor(&remask);
+            other.sig_usage ^= remask;
+            other.sig_algo ^= remask;
+            other.body.mnonce[0] ^= 1;
+
+            let diff = report.diff(&other);
+            let fields: Vec<_> = diff.iter().map(|d| d.field).collect();
+            assert_eq!(fields, ["mnonce", "anonce"]);
+            assert_eq!(diff[1].right, 0x5a5a5a5au32.to_le_bytes());
+        }
+
+        #[test]
+        pub fn test_verify_generated() {
+            use crate::testutil::{make_report, ReportParams, TEST_SN};
+
+            let params = ReportParams {
+                report_data: [0x11; 64],
+                measure: [0x22; 32],
+                ..Default::default()
+            };
+            let (response, chain, mnonce) = make_report(params.clone());
+            let report = &response.report;
+
+            let outcome = report
+                .verify_full(&response.signer, &chain, &mnonce)
+                .unwrap();
+            assert_eq!(outcome.pek.subject(), "HYGON-SSD-PEK");
+            assert!(outcome.sn.starts_with(TEST_SN.as_bytes()));
+
+            assert_eq!(report.anonce, params.anonce);
+            let mut measure = report.body.measure;
+            xor_with_anonce(&mut measure, &report.anonce).unwrap();
+            assert_eq!(measure, params.measure);
+            let mut report_data = report.body.report_data;
+            xor_with_anonce(&mut report_data, &report.anonce).unwrap();
+            assert_eq!(report_data, params.report_data);
+
+            // The chain of another call doesn't endorse the report.
+            let (_, other, _) = make_report(ReportParams::default());
+            assert!(report
+                .verify_full(&response.signer, &other, &mnonce)
+                .is_err());
+            assert!(matches!(
+                report.verify_full(&response.signer, &chain, &[0u8; 16]),
+                Err(Error::MnonceMismatch { .. })
+            ));
+        }
+    }
+
+    mod verify_options {
+        use crate::api::guest::types::*;
+        use crate::testutil::{golden_chain, golden_report, golden_signer};
+
+        fn recorded() -> (AttestationReport, ReportSigner, kds::Chain) {
+            (golden_report(), golden_signer(), golden_chain())
+        }
+
+        #[test]
+        pub fn test_hmac_on_by_default() {
+            let (report, signer, chain) = recorded();
+            assert!(VerifyOptions::default().verify_hmac);
+
+            // Without the requested mnonce, nothing verifies by default.
+            assert!(matches!(
+                report.verify_full(&signer, &chain, &[0u8; 16]),
+                Err(Error::MnonceMismatch { .. })
+            ));
+        }
+
+        #[test]
+        pub fn test_skip_hmac() {
+            let (mut report, mut signer, chain) = recorded();
+            let options = VerifyOptions {
+                verify_hmac: false,
+                ..Default::default()
+            };
+
+            signer.mac[0] ^= 1;
+            let outcome = report
+                .verify_full_with(&signer, &chain, &[0u8; 16], &options)
+                .unwrap();
+            assert!(outcome.sn.starts_with(b"NZA9T14052605"));
+
+            // The signature is still verified.
+            report.body.measure[0] ^= 1;
+            assert!(report
+                .verify_full_with(&signer, &chain, &[0u8; 16], &options)
+                .is_err());
+        }
+
+        #[test]
+        pub fn test_verify_any() {
+            let (report, signer, chain) = recorded();
+
+            // The chain of another chip, whose CEK the HSK didn't sign.
+            let mut other = kds::Chain::from_der_bundle(crate::testutil::GOLDEN_HSK_CEK).unwrap();
+            other.cek.body.data.pubkey.key.x[0] ^= 1;
+
+            let chains = [other, chain];
+            assert_eq!(
+                report
+                    .verify_any(&signer, &chains, &crate::testutil::GOLDEN_MNONCE)
+                    .unwrap(),
+                1
+            );
+
+            match report.verify_any(&signer, &chains[..1], &crate::testutil::GOLDEN_MNONCE) {
+                Err(Error::NoChainVerifies(errors)) => assert_eq!(errors.len(), 1),
+                other => panic!("unexpected result: {:?}", other),
+            }
+            assert!(matches!(
+                report.verify_any(&signer, &[], &crate::testutil::GOLDEN_MNONCE),
+                Err(Error::NoChainVerifies(errors)) if errors.is_empty()
+            ));
+
+            // A wrong mnonce fails whatever the chains.
+            assert!(matches!(
+                report.verify_any(&signer, &chains, &[0u8; 16]),
+                Err(Error::MnonceMismatch { .. })
+            ));
+        }
+
+        #[test]
+        pub fn test_reject_zero_anonce() {
+            let (mut report, signer, chain) = recorded();
+            assert!(!VerifyOptions::default().reject_zero_anonce);
+            let options = VerifyOptions {
+                reject_zero_anonce: true,
+                ..Default::default()
+            };
+
+            let mut mnonce = report.body.mnonce;
+            xor_with_anonce(&mut mnonce, &report.anonce).unwrap();
+            report
+                .verify_full_with(&signer, &chain, &mnonce, &options)
+                .unwrap();
+
+            report.anonce = 0;
+            assert!(matches!(
+                report.verify_full_with(&signer, &chain, &mnonce, &options),
+                Err(Error::ZeroAnonce)
+            ));
+        }
+
+        #[test]
+        pub fn test_min_version() {
+            let (report, signer, chain) = recorded();
+            let verify = |min_version| {
+                let options = VerifyOptions {
+                    min_version,
+                    ..Default::default()
+                };
+                report.verify_full_with(&signer, &chain, &crate::testutil::GOLDEN_MNONCE, &options)
+            };
+
+            assert_eq!(verify(None).unwrap().report_version(), 1);
+            assert!(verify(Some(0)).is_ok());
+            assert!(verify(Some(1)).is_ok());
+            assert!(matches!(
+                verify(Some(2)),
+                Err(Error::ReportVersionTooOld { got: 1, min: 2 })
+            ));
+        }
+    }
+
+    mod report_signer {
+        use crate::api::guest::types::*;
+        use crate::testutil::golden_restored_signer;
+        use codicon::Encoder;
+
+        #[test]
+        pub fn test_verify_zero_anonce() {
+            // Evidence as produced by firmware that doesn't mask reports.
+            let mut signer = golden_restored_signer();
+            let mnonce = [0x5au8; 16];
+            let key = pkey::PKey::hmac(&mnonce).unwrap();
+            let mut sig = sign::Signer::new(MessageDigest::sm3(), &key).unwrap();
+            sig.update(&signer.pek_cert).unwrap();
+            sig.update(&signer.sn).unwrap();
+            sig.update(&signer.reserved).unwrap();
+            signer.mac = sig.sign_to_vec().unwrap().try_into().unwrap();
+            let expected = signer.clone();
+
+            let restored = signer.verified(&mnonce, &mnonce, &0).unwrap();
+            assert_eq!(restored.pek_cert, expected.pek_cert);
+            assert_eq!(restored.serial_number(), "NZA9T14052605");
+
+            assert!(matches!(
+                signer.verified(&[0u8; 16], &mnonce, &0),
+                Err(Error::MnonceMismatch { .. })
+            ));
+        }
+
+        #[test]
+        pub fn test_verify_keeps_reserved() {
+            let report = crate::testutil::golden_report();
+            let signer = crate::testutil::golden_signer();
+            assert_ne!(signer.reserved(), &[0u8; 32]);
+
+            let verify = || {
+                signer.verified(
+                    &crate::testutil::GOLDEN_MNONCE,
+                    &report.body.mnonce,
+                    &report.anonce,
+                )
+            };
+
+            // The evidence is left as is, it verifies again.
+            let (first, second) = (verify().unwrap(), verify().unwrap());
+            assert_eq!(signer.pek_cert, crate::testutil::golden_signer().pek_cert);
+            assert_eq!(first.reserved(), signer.reserved());
+            assert_eq!(first.reserved(), second.reserved());
+            assert_eq!(first.pek_cert, second.pek_cert);
+            assert_eq!(first.sn, second.sn);
+        }
+
+        #[test]
+        pub fn test_serial_number() {
+            assert_eq!(golden_restored_signer().serial_number(), "NZA9T14052605");
+        }
+
+        #[test]
+        pub fn test_same_chip_as() {
+            let signer = golden_restored_signer();
+            assert!(signer.same_chip_as(&golden_restored_signer()));
+
+            let mut other = golden_restored_signer();
+            other.sn[0] ^= 1;
+            assert!(!signer.same_chip_as(&other));
+
+            // Another PEK of the same serial number.
+            let mut other = golden_restored_signer();
+            other.pek_cert[100] ^= 1;
+            assert!(other.pek_certificate().is_ok());
+            assert!(!signer.same_chip_as(&other));
+
+            // A PEK certificate that doesn't decode.
+            let mut other = golden_restored_signer();
+            other.pek_cert[..4].copy_from_slice(&0xffu32.to_le_bytes());
+            assert!(!signer.same_chip_as(&other));
+            assert!(!other.same_chip_as(&other));
+        }
+
+        #[test]
+        pub fn test_pek_certificate_ending_in_zero() {
+            let signer = golden_restored_signer();
+            assert_eq!(signer.pek_cert[signer.pek_cert.len() - 1], 0);
+
+            let pek = signer.pek_certificate().unwrap();
+            assert_eq!(Usage::try_from(&pek).unwrap(), Usage::PEK);
+
+            let mut encoded = Vec::new();
+            pek.encode(&mut encoded, ()).unwrap();
+            assert_eq!(encoded, signer.pek_cert);
+        }
+
+        #[test]
+        pub fn test_pek_certificates() {
+            let signer = golden_restored_signer();
+            let certs = signer.pek_certificates().unwrap();
+            assert_eq!(certs.len(), 1);
+            assert_eq!(
+                certs[0].to_der(),
+                signer.pek_certificate().unwrap().to_der()
+            );
+        }
+
+        #[test]
+        pub fn test_concatenated_certificates() {
+            let pek = golden_restored_signer().pek_certificate().unwrap();
+            let cek = crate::testutil::golden_chain().cek;
+
+            let mut bytes = [pek.to_der(), cek.to_der()].concat();
+            bytes.extend_from_slice(&[0xff; 16]);
+            let certs = decode_certificates(&bytes);
+            assert_eq!(certs.len(), 2);
+            assert_eq!(certs[0].to_der(), pek.to_der());
+            assert_eq!(certs[1].to_der(), cek.to_der());
+
+            verify_pek_chain(&cek, &certs[..1]).unwrap();
+            // The CEK is signed by the HSK, not by itself.
+            assert!(verify_pek_chain(&cek, &certs).is_err());
+            assert!(verify_pek_chain(&cek, &[pek, pek]).is_err());
+        }
+
+        #[test]
+        pub fn test_pek_certificate_unknown_version() {
+            let mut signer = golden_restored_signer();
+            signer.pek_cert[..4].copy_from_slice(&0xffu32.to_le_bytes());
+            assert!(matches!(
+                signer.pek_certificate(),
+                Err(Error::InvalidCertificate)
+            ));
+        }
+    }
+
+    mod report_rsp {
+        use crate::api::guest::types::*;
+
+        fn recorded_page() -> Vec<u8> {
+            let mut page = include_bytes!("../../../tests/test_data/report.cert").to_vec();
+            page.resize(REPORT_RSP_LEN, 0);
+            page
+        }
+
+        #[test]
+        pub fn test_from_page() {
+            let mut page = recorded_page();
+            page[REPORT_RSP_LEN - 1] = 0xff;
+
+            let response = ReportRsp::from_page(&page).unwrap();
+            assert_eq!(response.report.anonce, 0x1291429b);
+            assert_eq!(response.reserved[REPORT_RSP_RESERVED_SIZE - 1], 0xff);
+
+            // The recorded evidence lacks the padding altogether.
+            let response = ReportRsp::from_page(&page[..REPORT_RSP_CONTENT_SIZE]).unwrap();
+            assert!(response.reserved.iter().all(|&b| b == 0));
+        }
+
+        #[test]
+        pub fn test_detect_report_version() {
+            let mut page = recorded_page();
+            assert_eq!(detect_report_version(&page).unwrap(), 1);
+
+            // The version is masked like the rest of the PEK.
+            let mask = |version: u32| {
+                let mut bytes = version.to_le_bytes();
+                xor_with_anonce(&mut bytes, &0x1291429b).unwrap();
+                bytes
+            };
+            let at = offset::report_rsp::SIGNER;
+            for (version, detected) in [(1, Ok(1)), (2, Ok(2)), (0, Err(0)), (3, Err(3))] {
+                page[at..at + 4].copy_from_slice(&mask(version));
+                match (detect_report_version(&page), detected) {
+                    (Ok(got), Ok(expected)) => assert_eq!(got, expected),
+                    (Err(Error::UnknownReportVersion(got)), Err(expected)) => {
+                        assert_eq!(got, expected)
+                    }
+                    (got, _) => panic!("{version}: unexpected result {got:?}"),
+                }
+            }
+
+            assert!(matches!(
+                detect_report_version(&page[..at + 3]),
+                Err(Error::InvalidLen)
+            ));
+        }
+
+        #[test]
+        pub fn test_from_page_truncated() {
+            let page = recorded_page();
+
+            for len in [0, ATTESTATION_REPORT_LEN, REPORT_RSP_CONTENT_SIZE - 1] {
+                assert!(
+                    matches!(ReportRsp::from_page(&page[..len]), Err(Error::InvalidLen)),
+                    "{len}"
+                );
+            }
+        }
+
+        #[test]
+        pub fn test_from_page_strict() {
+            let mut page = recorded_page();
+            ReportRsp::from_page_strict(&page).unwrap();
+
+            assert!(matches!(
+                ReportRsp::from_page_strict(&page[..REPORT_RSP_CONTENT_SIZE]),
+                Err(Error::InvalidLen)
+            ));
+
+            page[REPORT_RSP_CONTENT_SIZE + 10] = 1;
+            assert!(matches!(
+                ReportRsp::from_page_strict(&page),
+                Err(Error::NonZeroReserved { offset }) if offset == REPORT_RSP_CONTENT_SIZE + 10
+            ));
+        }
+
+        #[test]
+        pub fn test_to_page() {
+            let mut page = recorded_page();
+            page[REPORT_RSP_LEN - 1] = 0xff;
+
+            let response = ReportRsp::from_page(&page).unwrap();
+            assert_eq!(response.to_page(), page);
+        }
+
+        /// Recomputes the hmac of the evidence of a generated response
+        /// after tampering with it.
+        fn remac(response: &mut ReportRsp, mnonce: &[u8; 16]) {
+            let key = pkey::PKey::hmac(mnonce).unwrap();
+            let mut mac = sign::Signer::new(MessageDigest::sm3(), &key).unwrap();
+            mac.update(&response.signer.pek_cert).unwrap();
+            mac.update(&response.signer.sn).unwrap();
+            mac.update(response.signer.reserved()).unwrap();
+            response.signer.mac = mac.sign_to_vec().unwrap().try_into().unwrap();
+        }
+
+        #[test]
+        pub fn test_verify() {
+            use crate::testutil::{make_report, ReportParams};
+
+            let (mut response, chain, mnonce) = make_report(ReportParams::default());
+            let written = response.signer.clone();
+            response.verify(&chain, &mnonce).unwrap();
+            assert_eq!(response.signer.serial_number(), crate::testutil::TEST_SN);
+            assert_ne!(response.signer.pek_cert, written.pek_cert);
+
+            // The restored evidence doesn't verify again.
+            assert!(response.verify(&chain, &mnonce).is_err());
+        }
+
+        #[test]
+        pub fn test_verify_failures() {
+            use crate::testutil::{make_report, ReportParams};
+
+            let (response, chain, mnonce) = make_report(ReportParams::default());
+            let (other, other_chain, _) = make_report(ReportParams::default());
+            let page = response.to_page();
+            let generated = || ReportRsp::from_page(&page).unwrap();
+
+            let mut response = generated();
+            assert!(matches!(
+                response.verify(&chain, &[0u8; 16]),
+                Err(Error::MnonceMismatch { .. })
+            ));
+            // Nothing is restored on failure.
+            assert_eq!(response.to_page(), page);
+
+            let mut response = generated();
+            response.signer.mac[0] ^= 1;
+            assert!(matches!(
+                response.verify(&chain, &mnonce),
+                Err(Error::BadSignature)
+            ));
+
+            let mut broken = kds::Chain {
+                ca: crate::certs::ca::Chain {
+                    hsk: other_chain.ca.hsk,
+                    hrk: chain.ca.hrk,
+                },
+                cek: chain.cek,
+            };
+            assert!(matches!(
+                generated().verify(&broken, &mnonce),
+                Err(Error::IoError(_))
+            ));
+            broken.ca.hsk = chain.ca.hsk;
+            broken.cek = other_chain.cek;
+            assert!(matches!(
+                generated().verify(&broken, &mnonce),
+                Err(Error::IoError(_))
+            ));
+
+            let mut response = generated();
+            response.signer.pek_cert[..4].copy_from_slice(&[0xff; 4]);
+            remac(&mut response, &mnonce);
+            assert!(matches!(
+                response.verify(&chain, &mnonce),
+                Err(Error::InvalidCertificate)
+            ));
+
+            // The PEK of another chip, with the same anonce.
+            let mut response = generated();
+            response.signer.pek_cert = other.signer.pek_cert;
+            remac(&mut response, &mnonce);
+            assert!(matches!(
+                response.verify(&chain, &mnonce),
+                Err(Error::IoError(_))
+            ));
+
+            let mut response = generated();
+            response.report.sig_algo ^= 0xff;
+            assert!(matches!(
+                response.verify(&chain, &mnonce),
+                Err(Error::AlgoCurveMismatch)
+            ));
+
+            let mut response = generated();
+            response.report.body.measure[0] ^= 1;
+            assert!(matches!(
+                response.verify(&chain, &mnonce),
+                Err(Error::BadSignature)
+            ));
+        }
+
+        #[test]
+        pub fn test_certificates() {
+            let mut response = ReportRsp::from_page(&recorded_page()).unwrap();
+            let certs = response.certificates().unwrap();
+            assert_eq!(certs.len(), 1);
+            assert_eq!(Usage::try_from(&certs[0]).unwrap(), Usage::PEK);
+
+            // The same PEK as the restored evidence carries.
+            let mut mnonce = response.report.body.mnonce;
+            xor_with_anonce(&mut mnonce, &response.report.anonce).unwrap();
+            let signer = response
+                .signer
+                .verified(
+                    &mnonce,
+                    &response.report.body.mnonce,
+                    &response.report.anonce,
+                )
+                .unwrap();
+            assert_eq!(
+                certs[0].to_der(),
+                signer.pek_certificate().unwrap().to_der()
+            );
+
+            response.signer.pek_cert[..4].fill(0xff);
+            assert!(matches!(
+                response.certificates(),
+                Err(Error::InvalidCertificate)
+            ));
+        }
+    }
+
+    mod report_req {
+        use crate::api::guest::types::ReportReq;
+        use crate::error::Error;
+
+        #[test]
+        pub fn test_from_hex() {
+            let hex = "67c6697351ff4aec29cdbaabf2fbe346";
+            let mnonce = ReportReq::mnonce_from_hex(hex).unwrap();
+            assert_eq!(mnonce, crate::testutil::GOLDEN_MNONCE);
+            assert_eq!(
+                ReportReq::mnonce_from_hex(&hex.to_uppercase()).unwrap(),
+                mnonce
+            );
+
+            let data = ReportReq::data_from_hex(&hex.repeat(4)).unwrap();
+            assert_eq!(data[..16], mnonce);
+            assert_eq!(data[48..], mnonce);
+        }
+
+        #[test]
+        pub fn test_from_hex_invalid() {
+            for hex in ["", "ab", &"ab".repeat(63), &"ab".repeat(65)] {
+                assert!(
+                    matches!(ReportReq::data_from_hex(hex), Err(Error::InvalidLen)),
+                    "{hex}"
+                );
+            }
+            assert!(matches!(
+                ReportReq::mnonce_from_hex(&"ab".repeat(64)),
+                Err(Error::InvalidLen)
+            ));
+
+            assert!(matches!(
+                ReportReq::data_from_hex(&"zz".repeat(64)),
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::InvalidData
+            ));
+        }
+
         #[test]
         pub fn test_new() {
             let data: [u8; 64] = [
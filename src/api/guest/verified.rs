@@ -0,0 +1,112 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Attestation reports that are known to have been verified.
+
+use super::{AttestationReport, ReportRsp, VerifyOutcome};
+use crate::{certs::kds, error::Error};
+
+/// An attestation report that verified all the way up to the HRK.
+///
+/// The only way to get one is to verify a response with its `TryFrom`
+/// implementation, so APIs taking a `VerifiedReport` can't be handed a
+/// report nobody verified.
+///
+/// ```
+/// use csv_rs::{api::guest::VerifiedReport, testutil};
+///
+/// let chain = testutil::golden_chain();
+/// let response = testutil::golden_response();
+/// let verified =
+///     VerifiedReport::try_from((response, &chain, &testutil::GOLDEN_MNONCE)).unwrap();
+/// assert!(verified.outcome().sn.starts_with(b"NZA9T14052605"));
+/// ```
+///
+/// It can't be built back from its parts, the fields are private:
+///
+/// ```compile_fail,E0451
+/// use csv_rs::{api::guest::VerifiedReport, testutil};
+///
+/// let chain = testutil::golden_chain();
+/// let response = testutil::golden_response();
+/// let (report, outcome) =
+///     VerifiedReport::try_from((response, &chain, &testutil::GOLDEN_MNONCE))
+///         .unwrap()
+///         .into_parts();
+/// let verified = VerifiedReport { report, outcome };
+/// ```
+#[derive(Debug)]
+pub struct VerifiedReport {
+    report: AttestationReport,
+    outcome: VerifyOutcome,
+}
+
+impl VerifiedReport {
+    /// The verified report.
+    pub fn report(&self) -> &AttestationReport {
+        &self.report
+    }
+
+    /// What the report verified with.
+    pub fn outcome(&self) -> &VerifyOutcome {
+        &self.outcome
+    }
+
+    /// Gives up the guarantee of verification for the report and its
+    /// outcome.
+    pub fn into_parts(self) -> (AttestationReport, VerifyOutcome) {
+        (self.report, self.outcome)
+    }
+}
+
+/// Verifies a response as the firmware wrote it against the chain of the
+/// chip and the mnonce it was requested with, see
+/// [`AttestationReport::verify_full`].
+impl TryFrom<(ReportRsp, &kds::Chain, &[u8; 16])> for VerifiedReport {
+    type Error = Error;
+
+    fn try_from(
+        (response, chain, mnonce): (ReportRsp, &kds::Chain, &[u8; 16]),
+    ) -> Result<Self, Self::Error> {
+        let outcome = response
+            .report
+            .verify_full(&response.signer, chain, mnonce)?;
+
+        Ok(Self {
+            report: response.report,
+            outcome,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::{golden_chain, golden_response, GOLDEN_MNONCE};
+
+    #[test]
+    fn verified() {
+        let chain = golden_chain();
+        let verified =
+            VerifiedReport::try_from((golden_response(), &chain, &GOLDEN_MNONCE)).unwrap();
+
+        let (report, outcome) = verified.into_parts();
+        assert_eq!(report.anonce, golden_response().report.anonce);
+        assert!(outcome.sn.starts_with(b"NZA9T14052605"));
+    }
+
+    #[test]
+    fn unverified() {
+        let chain = golden_chain();
+
+        let mut response = golden_response();
+        response.report.body.measure[0] ^= 1;
+        assert!(VerifiedReport::try_from((response, &chain, &GOLDEN_MNONCE)).is_err());
+
+        assert!(matches!(
+            VerifiedReport::try_from((golden_response(), &chain, &[0u8; 16])),
+            Err(Error::MnonceMismatch { .. })
+        ));
+    }
+}
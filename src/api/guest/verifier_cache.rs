@@ -0,0 +1,250 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A cache of verified chains for verifiers seeing the same chips
+//! repeatedly.
+
+use super::types::*;
+use crate::{certs::kds, certs::Verifiable, error::*};
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A chain verified at some point in time.
+struct Entry {
+    chain: Arc<kds::Chain>,
+    verified_at: Instant,
+}
+
+/// Verifies reports with the chains of their chips, verifying each chip's
+/// chain only once in a while.
+///
+/// The chains are keyed by the serial number of the chip, see
+/// [`ReportSigner::serial_number`]. A chain is verified when first seen and
+/// trusted without verification for `ttl` afterwards. Each report is still
+/// verified in full otherwise: the hmac of its signer evidence with the
+/// mnonce, its PEK with the chain's CEK and its signature with the PEK.
+///
+/// The cache holds at most `capacity` chains, the oldest verified one is
+/// evicted to make room for another.
+pub struct VerifierCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl VerifierCache {
+    /// Creates an empty cache of at most `capacity` chains, trusted for
+    /// `ttl` after their verification.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The number of chains in the cache, expired ones included.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Whether the cache holds no chain.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The chain verified for the chip of serial number `sn`, unless it
+    /// expired or was never verified.
+    pub fn get(&self, sn: &str) -> Option<Arc<kds::Chain>> {
+        self.get_at(sn, Instant::now())
+    }
+
+    /// Verifies `report` as [`AttestationReport::verify_full`] does, with
+    /// the cached chain of its chip.
+    ///
+    /// On a miss, the chain is obtained with `fetch` from the chip's serial
+    /// number, e.g. from the KDS, then verified and cached.
+    pub fn verify(
+        &self,
+        report: &AttestationReport,
+        signer: &ReportSigner,
+        mnonce: &[u8; 16],
+        fetch: impl FnOnce(&str) -> Result<kds::Chain, Error>,
+    ) -> Result<VerifyOutcome, Error> {
+        self.verify_at(report, signer, mnonce, fetch, Instant::now())
+    }
+
+    fn get_at(&self, sn: &str, now: Instant) -> Option<Arc<kds::Chain>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(sn)
+            .filter(|entry| now.duration_since(entry.verified_at) < self.ttl)
+            .map(|entry| entry.chain.clone())
+    }
+
+    fn verify_at(
+        &self,
+        report: &AttestationReport,
+        signer: &ReportSigner,
+        mnonce: &[u8; 16],
+        fetch: impl FnOnce(&str) -> Result<kds::Chain, Error>,
+        now: Instant,
+    ) -> Result<VerifyOutcome, Error> {
+        let signer = signer.verified(mnonce, &report.body.mnonce, &report.anonce)?;
+
+        let sn = signer.serial_number();
+        let chain = match self.get_at(&sn, now) {
+            Some(chain) => chain,
+            None => {
+                let chain = fetch(&sn)?;
+                chain.verify()?;
+
+                let chain = Arc::new(chain);
+                self.insert(sn, chain.clone(), now);
+                chain
+            }
+        };
+
+        report.verify_with_cek(&signer, chain.leaf())
+    }
+
+    fn insert(&self, sn: String, chain: Arc<kds::Chain>, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| now.duration_since(entry.verified_at) < self.ttl);
+
+        if !entries.contains_key(&sn) && entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.verified_at)
+                .map(|(sn, _)| sn.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        if self.capacity > 0 {
+            entries.insert(
+                sn,
+                Entry {
+                    chain,
+                    verified_at: now,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::{golden_response, GOLDEN_HSK_CEK, GOLDEN_MNONCE};
+    use std::cell::Cell;
+
+    const SN: &str = "NZA9T14052605";
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn recorded() -> (AttestationReport, ReportSigner, [u8; 16]) {
+        let response = golden_response();
+        (response.report, response.signer, GOLDEN_MNONCE)
+    }
+
+    /// Verifies the recorded report at `now`, counting the chain fetches.
+    fn verify(cache: &VerifierCache, fetches: &Cell<usize>, now: Instant) -> Result<(), Error> {
+        let (report, signer, mnonce) = recorded();
+        let fetch = |sn: &str| {
+            assert_eq!(sn, SN);
+            fetches.set(fetches.get() + 1);
+            Ok(kds::Chain::from_der_bundle(GOLDEN_HSK_CEK)?)
+        };
+        cache
+            .verify_at(&report, &signer, &mnonce, fetch, now)
+            .map(|_| ())
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let cache = VerifierCache::new(4, TTL);
+        let fetches = Cell::new(0);
+        let now = Instant::now();
+
+        assert!(cache.get_at(SN, now).is_none());
+        verify(&cache, &fetches, now).unwrap();
+        assert_eq!(fetches.get(), 1);
+        assert!(cache.get_at(SN, now).is_some());
+
+        verify(&cache, &fetches, now + TTL / 2).unwrap();
+        assert_eq!(fetches.get(), 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn expiry() {
+        let cache = VerifierCache::new(4, TTL);
+        let fetches = Cell::new(0);
+        let now = Instant::now();
+
+        verify(&cache, &fetches, now).unwrap();
+        assert!(cache.get_at(SN, now + TTL).is_none());
+
+        verify(&cache, &fetches, now + TTL).unwrap();
+        assert_eq!(fetches.get(), 2);
+        assert!(cache.get_at(SN, now + TTL).is_some());
+    }
+
+    #[test]
+    fn hit_still_checks_report() {
+        let cache = VerifierCache::new(4, TTL);
+        let fetches = Cell::new(0);
+        let now = Instant::now();
+        verify(&cache, &fetches, now).unwrap();
+
+        let (report, signer, _) = recorded();
+        let unfetched = |_: &str| -> Result<kds::Chain, Error> { unreachable!() };
+        assert!(matches!(
+            cache.verify_at(&report, &signer, &[0; 16], unfetched, now),
+            Err(Error::MnonceMismatch { .. })
+        ));
+
+        let (mut report, signer, mnonce) = recorded();
+        report.body.measure[0] ^= 1;
+        assert!(cache
+            .verify_at(&report, &signer, &mnonce, unfetched, now)
+            .is_err());
+    }
+
+    #[test]
+    fn unverified_chain_not_cached() {
+        let cache = VerifierCache::new(4, TTL);
+        let (report, signer, mnonce) = recorded();
+
+        let broken = |_: &str| {
+            let mut chain = kds::Chain::from_der_bundle(GOLDEN_HSK_CEK)?;
+            chain.ca.hrk = chain.ca.hsk;
+            Ok(chain)
+        };
+        assert!(cache.verify(&report, &signer, &mnonce, broken).is_err());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn size_bound() {
+        let cache = VerifierCache::new(2, TTL);
+        let chain = Arc::new(kds::Chain::from_der_bundle(GOLDEN_HSK_CEK).unwrap());
+        let now = Instant::now();
+
+        cache.insert("a".into(), chain.clone(), now);
+        cache.insert("b".into(), chain.clone(), now + Duration::from_secs(1));
+        cache.insert("c".into(), chain, now + Duration::from_secs(2));
+
+        assert_eq!(cache.len(), 2);
+        let later = now + Duration::from_secs(2);
+        assert!(cache.get_at("a", later).is_none());
+        assert!(cache.get_at("b", later).is_some());
+        assert!(cache.get_at("c", later).is_some());
+    }
+}
@@ -13,7 +13,10 @@ use types::*;
 
 use crate::{
     certs::{csv::Certificate, Signer, Usage, Verifiable},
-    crypto::{sig::ecdsa, sm, PrivateKey, PublicKey, Signature},
+    crypto::{
+        sig::{ecdsa, SM2_SIG_COMPONENT_LEN},
+        sm, PrivateKey, PublicKey, Signature,
+    },
     util::*,
     Version,
 };
@@ -285,7 +288,21 @@ pub struct Policy {
     pub minfw: Version,
 }
 
+impl Policy {
+    /// Whether the policy sets any flag this crate doesn't know of.
+    ///
+    /// Policies decoded from the firmware keep such bits, while
+    /// [`Policy::from`] drops them. A strict verifier rejects them rather
+    /// than ignore a policy it can't interpret, see
+    /// [`AttestationReport::verify_policy`].
+    pub fn has_unknown_bits(&self) -> bool {
+        self.flags.bits() & !PolicyFlags::all().bits() != 0
+    }
+}
+
 /// Convert a policy represented as a u32 to a Policy struct.
+///
+/// Flags this crate doesn't know of are dropped.
 impl From<u32> for Policy {
     fn from(p: u32) -> Self {
         let flags = p as u16;
@@ -377,8 +394,8 @@ impl From<ecdsa::Signature> for SessionSig {
 impl From<SessionSig> for ecdsa::Signature {
     #[inline]
     fn from(value: SessionSig) -> Self {
-        let mut r = [0u8; 72];
-        let mut s = [0u8; 72];
+        let mut r = [0u8; SM2_SIG_COMPONENT_LEN];
+        let mut s = [0u8; SM2_SIG_COMPONENT_LEN];
         for (i, b) in value.r.iter().cloned().enumerate() {
             r[i] = b;
         }
@@ -409,7 +426,7 @@ impl Signer<Session> for PrivateKey<Usage> {
         let mut msg: Vec<u8> = Vec::new();
         msg.save(&target.body)?;
 
-        let sig = sm::SM2::sign(self.key, &uid.as_bytes().to_vec(), &msg)?;
+        let sig = sm::SM2::sign(self.key, uid.as_bytes(), &msg)?;
 
         let ecdsa_sig = ecdsa::Signature::try_from(&sig[..])?;
 
@@ -429,7 +446,7 @@ impl TryFrom<&Session> for Signature {
         Ok(Self {
             sig,
             id: None,
-            usage: Usage::PDH.into(),
+            usage: Usage::PDH,
             algo: None,
         })
     }
@@ -538,18 +555,73 @@ pub struct Measurement {
 #[repr(C)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct AttestationReport {
-    ///
     pub mnonce: [u8; 16],
-    ///
     pub digest: [u8; 32],
-    ///
     pub policy: Policy,
-    ///
     pub sig_usage: [u8; 4],
-    ///
     pub sig_algo: [u8; 4],
-    ///
     reserved: [u8; 4],
-    ///
     pub sig1: [[u8; 16]; 9],
 }
+
+impl AttestationReport {
+    /// Checks the policy the guest was launched with.
+    ///
+    /// With `strict`, a policy setting flags this crate doesn't know of is
+    /// rejected with `ErrorKind::InvalidData`, as a newer firmware may give
+    /// them a meaning this crate would otherwise ignore.
+    pub fn verify_policy(&self, strict: bool) -> Result<()> {
+        if strict && self.policy.has_unknown_bits() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "The policy sets unknown flags",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report_with_flags(flags: u16) -> AttestationReport {
+        AttestationReport {
+            mnonce: [0u8; 16],
+            digest: [0u8; 32],
+            policy: Policy {
+                flags: bincode::deserialize(&flags.to_le_bytes()).unwrap(),
+                minfw: Default::default(),
+            },
+            sig_usage: [0u8; 4],
+            sig_algo: [0u8; 4],
+            reserved: [0u8; 4],
+            sig1: [[0u8; 16]; 9],
+        }
+    }
+
+    #[test]
+    fn known_policy_bits() {
+        for flags in [0, PolicyFlags::all().bits(), PolicyFlags::NO_DEBUG.bits()] {
+            let report = report_with_flags(flags);
+            assert!(!report.policy.has_unknown_bits());
+            report.verify_policy(true).unwrap();
+        }
+    }
+
+    #[test]
+    fn unknown_policy_bits() {
+        for flags in [1 << 6, 1 << 15, PolicyFlags::all().bits() | 1 << 8] {
+            let report = report_with_flags(flags);
+            assert!(report.policy.has_unknown_bits());
+            report.verify_policy(false).unwrap();
+
+            let err = report.verify_policy(true).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+
+        // Converting from a u32 drops the unknown flags.
+        assert!(!Policy::from(1u32 << 6).has_unknown_bits());
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Command line tool for CSV attestation.
+
+use codicon::Decoder;
+use csv_rs::api::guest::{Evidence, ReportReq, VerifyOptions};
+use std::{
+    fs::File,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+Usage: csv-attest <command>
+
+Commands:
+    selftest                  Check the cryptography backend, no CSV hardware needed
+    verify <FILE> [MNONCE]    Verify the evidence in FILE, or read from stdin if FILE is `-`
+
+The evidence is the report, its signer evidence, the HSK and the CEK, as
+encoded one after the other. MNONCE is the hex encoded mnonce the report
+was requested with, without it the hmac of the signer evidence isn't
+checked.";
+
+fn selftest() -> ExitCode {
+    let report = csv_rs::selftest();
+    print!("{report}");
+
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Opens `path` for reading, or stdin if `path` is `-`.
+fn open(path: &str) -> io::Result<Box<dyn Read>> {
+    Ok(match path {
+        "-" => Box::new(io::stdin().lock()),
+        path => Box::new(File::open(path)?),
+    })
+}
+
+fn parse_mnonce(hex: &str) -> Option<[u8; 16]> {
+    ReportReq::mnonce_from_hex(hex).ok()
+}
+
+fn verify(path: &str, mnonce: Option<&str>) -> ExitCode {
+    let (mnonce, options) = match mnonce.map(parse_mnonce) {
+        Some(Some(mnonce)) => (mnonce, VerifyOptions::default()),
+        Some(None) => {
+            eprintln!("MNONCE must be 16 hex encoded bytes");
+            return ExitCode::from(2);
+        }
+        None => {
+            eprintln!("warning: no MNONCE given, the hmac of the signer evidence isn't checked");
+            (
+                [0u8; 16],
+                VerifyOptions {
+                    verify_hmac: false,
+                    ..Default::default()
+                },
+            )
+        }
+    };
+
+    let evidence = match open(path).and_then(|reader| Evidence::decode(reader, ())) {
+        Ok(evidence) => evidence,
+        Err(e) => {
+            eprintln!("Failed to read the evidence from {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    print!("{}", evidence.describe());
+    let outcome =
+        evidence
+            .report
+            .verify_full_with(&evidence.signer, &evidence.chain, &mnonce, &options);
+
+    match outcome {
+        Ok(outcome) => {
+            let len = outcome.sn.iter().position(|&b| b == 0).unwrap_or(64);
+            println!(
+                "verified: chip {}",
+                String::from_utf8_lossy(&outcome.sn[..len])
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("FAILED: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["selftest"] => selftest(),
+        ["verify", path] => verify(path, None),
+        ["verify", path, mnonce] => verify(path, Some(mnonce)),
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -5,8 +5,12 @@
 //! Operations that can be done on a Certificate Authority chain.
 
 use crate::{
-    certs::{Algorithm, Usage, Verifiable},
-    crypto::{key::ecc, sig::ecdsa, PublicKey, Signature},
+    certs::{Algorithm, Signer, Usage, Verifiable},
+    crypto::{
+        key::{ecc, group},
+        sig::ecdsa,
+        sm, PrivateKey, PublicKey, Signature,
+    },
     util::*,
 };
 
@@ -56,10 +60,100 @@ impl Certificate {
     /// Writes the certificate content to a file.
     pub fn write_to_file(&self, path: &std::path::Path) -> Result<()> {
         let mut file = std::fs::File::create(path)?;
-        let encoded = bincode::serialize(self).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        let encoded = bincode::serialize(self).map_err(Error::other)?;
         file.write_all(&encoded)?;
         Ok(())
     }
+    /// The certificate encoded as the firmware and the KDS serve it,
+    /// which `decode` reads back.
+    pub fn to_der(&self) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        codicon::Encoder::encode(self, &mut encoded, ()).expect("encoding into a Vec can't fail");
+        encoded
+    }
+
+    /// The SM3 digest of the encoded certificate, see
+    /// [`check_backend`](crate::crypto::check_backend) for its failure.
+    pub fn fingerprint(&self) -> std::result::Result<[u8; 32], crate::error::Error> {
+        crate::crypto::fingerprint(&self.to_der())
+    }
+
+    /// The fingerprint of the certificate, hex encoded.
+    pub fn fingerprint_hex(&self) -> std::result::Result<String, crate::error::Error> {
+        self.fingerprint().map(hex::encode)
+    }
+
+    /// The user ID of the certificate, e.g. `HYGON-SSD-HRK`.
+    pub fn subject(&self) -> String {
+        let size = (self.body.uid_size as usize).min(self.body.user_id.len());
+        String::from_utf8_lossy(&self.body.user_id[..size]).into_owned()
+    }
+
+    /// Generates a private key and its unsigned certificate, under a random
+    /// key ID, e.g. for test chains not rooted at the HYGON HRK.
+    pub fn generate(usage: Usage, uid: Option<String>) -> Result<(Self, PrivateKey<Usage>)> {
+        let uid = match uid {
+            Some(uid) => uid,
+            None => String::try_from(usage)?,
+        };
+        if uid.len() > 254 {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        let mut user_id = [0u8; 254];
+        user_id[..uid.len()].copy_from_slice(uid.as_bytes());
+
+        let mut kid = [0u8; 16];
+        openssl::rand::rand_bytes(&mut kid)?;
+        let algo = Algorithm::try_from(usage)?;
+        let (pubkey, key) = sm::SM2::generate(group::Group::SM2_256)?;
+
+        Ok((
+            Self {
+                body: Body {
+                    preamble: Preamble {
+                        ver: 1u32.to_le(),
+                        data: Data {
+                            kid,
+                            sid: [0u8; 16],
+                            usage,
+                            reserved: [0u8; 24],
+                        },
+                    },
+                    pubkey,
+                    uid_size: uid.len() as u16,
+                    user_id,
+                    reserved: [0u8; 108],
+                },
+                signature: ecdsa::Signature::default(),
+                _reserved: [0u8; 112],
+            },
+            PrivateKey {
+                id: Some(kid),
+                key,
+                hash: algo.try_into()?,
+                usage,
+            },
+        ))
+    }
+}
+
+/// Signs a certificate with the key of a generated HRK certificate, e.g.
+/// the HRK itself or an HSK. `uid` is the user ID of the signer.
+impl Signer<Certificate> for PrivateKey<Usage> {
+    type Output = ();
+
+    fn sign(&self, target: &mut Certificate, uid: String) -> Result<()> {
+        let sid = self.id.ok_or(ErrorKind::InvalidInput)?;
+        target.body.preamble.data.sid = sid;
+
+        let mut msg: Vec<u8> = Vec::new();
+        msg.save(&target.body)?;
+
+        let sig = sm::SM2::sign(self.key, uid.as_bytes(), &msg)?;
+        target.signature = ecdsa::Signature::try_from(&sig[..])?;
+
+        Ok(())
+    }
 }
 
 impl TryFrom<&Certificate> for Signature {
@@ -71,7 +165,7 @@ impl TryFrom<&Certificate> for Signature {
         Ok(Self {
             sig,
             id: Some(value.body.preamble.data.sid),
-            usage: Usage::HRK.into(),
+            usage: Usage::HRK,
             algo: None,
         })
     }
@@ -87,7 +181,7 @@ impl TryFrom<&Certificate> for PublicKey {
             id: Some(value.body.preamble.data.kid),
             key,
             usage: value.body.preamble.data.usage,
-            algo: Some(Algorithm::SM2_SA.into()),
+            algo: Some(Algorithm::SM2_SA),
         })
     }
 }
@@ -112,6 +206,16 @@ impl codicon::Decoder<()> for Certificate {
     }
 }
 
+impl codicon::Encoder<()> for Certificate {
+    type Error = Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> Result<()> {
+        writer.save(&self.body)?;
+        writer.save(&self.signature)?;
+        writer.save(&self._reserved)
+    }
+}
+
 impl codicon::Encoder<crate::Body> for Certificate {
     type Error = Error;
 
@@ -20,6 +20,18 @@ pub struct Chain {
     pub hrk: Certificate,
 }
 
+impl Chain {
+    /// The root of the chain, the HRK.
+    pub fn root(&self) -> &Certificate {
+        &self.hrk
+    }
+
+    /// The leaf of the chain, the HSK.
+    pub fn leaf(&self) -> &Certificate {
+        &self.hsk
+    }
+}
+
 impl codicon::Decoder<()> for Chain {
     type Error = Error;
 
@@ -20,6 +20,18 @@ pub struct Chain {
     pub csv: csv::Chain,
 }
 
+impl Chain {
+    /// The root of the chain, the HRK.
+    pub fn root(&self) -> &ca::Certificate {
+        self.ca.root()
+    }
+
+    /// The leaf of the chain, the PDH.
+    pub fn leaf(&self) -> &csv::Certificate {
+        &self.csv.pdh
+    }
+}
+
 impl codicon::Decoder<()> for Chain {
     type Error = Error;
 
@@ -31,4 +31,30 @@ impl PubKey {
             },
         ))
     }
+
+    /// Verifies that `sig`, a DER encoded SM2 signature, signs `msg` with
+    /// the key under the SM2 user ID [`sm::sm2_id`].
+    ///
+    /// Returns [`crate::error::Error::BadSignature`] if it doesn't.
+    pub fn verify_data(
+        &self,
+        msg: &[u8],
+        sig: &[u8],
+    ) -> std::result::Result<(), crate::error::Error> {
+        if !sm::SM2::verify(self.key, sig, &sm::sm2_id(), msg)? {
+            return Err(crate::error::Error::BadSignature);
+        }
+
+        Ok(())
+    }
+
+    /// The fingerprint of the key, see `fingerprint` of the inner ECC key.
+    pub fn fingerprint(&self) -> std::result::Result<[u8; 32], crate::error::Error> {
+        self.key.fingerprint()
+    }
+
+    /// The fingerprint of the key, hex encoded.
+    pub fn fingerprint_hex(&self) -> std::result::Result<String, crate::error::Error> {
+        self.key.fingerprint_hex()
+    }
 }
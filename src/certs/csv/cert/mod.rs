@@ -11,9 +11,11 @@ use crate::{
     crypto::{self, key::ecc, sig::ecdsa, sm, PrivateKey, PublicKey, Signature},
     util::*,
 };
+#[cfg(feature = "network")]
 use log::*;
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
+#[cfg(feature = "network")]
 use std::env;
 use std::io::{Error, ErrorKind, Read, Result, Write};
 
@@ -65,7 +67,7 @@ impl TryFrom<&crypto::Signature> for Signatures {
 
     #[inline]
     fn try_from(value: &crypto::Signature) -> Result<Self> {
-        let algo = value.algo.unwrap_or_else(|| Algorithm::NONE);
+        let algo = value.algo.unwrap_or(Algorithm::NONE);
         Ok(Signatures {
             usage: value.usage,
             algo,
@@ -75,6 +77,10 @@ impl TryFrom<&crypto::Signature> for Signatures {
     }
 }
 
+/// A CSV certificate, e.g. the CEK or the PEK of a chip.
+///
+/// The format has no validity period: a certificate doesn't expire, see
+/// [`crate::certs::kds::Chain`].
 #[repr(C)]
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
 pub struct Certificate {
@@ -82,14 +88,52 @@ pub struct Certificate {
     pub sigs: [Signatures; 2],
 }
 
+/// The size of an encoded certificate.
+pub const CERTIFICATE_LEN: usize = 2084;
+
 impl Certificate {
+    /// The size of the encoded certificate of version `ver`, given by the
+    /// little-endian `ver` its encoding starts with.
+    ///
+    /// Returns `None` for versions this crate doesn't know.
+    pub fn encoded_len(ver: u32) -> Option<usize> {
+        match ver {
+            1 | 2 => Some(CERTIFICATE_LEN),
+            _ => None,
+        }
+    }
+
     /// Writes the certificate content to a file.
     pub fn write_to_file(&self, path: &std::path::Path) -> Result<()> {
         let mut file = std::fs::File::create(path)?;
-        let encoded = bincode::serialize(self).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        let encoded = bincode::serialize(self).map_err(Error::other)?;
         file.write_all(&encoded)?;
         Ok(())
     }
+    /// The certificate encoded as the firmware and the KDS serve it,
+    /// which `decode` reads back.
+    pub fn to_der(&self) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        codicon::Encoder::encode(self, &mut encoded, ()).expect("encoding into a Vec can't fail");
+        encoded
+    }
+
+    /// The SM3 digest of the encoded certificate, see
+    /// [`check_backend`](crate::crypto::check_backend) for its failure.
+    pub fn fingerprint(&self) -> std::result::Result<[u8; 32], crate::error::Error> {
+        crate::crypto::fingerprint(&self.to_der())
+    }
+
+    /// The fingerprint of the certificate, hex encoded.
+    pub fn fingerprint_hex(&self) -> std::result::Result<String, crate::error::Error> {
+        self.fingerprint().map(hex::encode)
+    }
+
+    /// The user ID of the certificate, e.g. `HYGON-SSD-CEK`.
+    pub fn subject(&self) -> String {
+        let size = (self.body.data.uid_size as usize).min(self.body.data.user_id.len());
+        String::from_utf8_lossy(&self.body.data.user_id[..size]).into_owned()
+    }
 }
 
 impl TryFrom<&Signatures> for Option<Signature> {
@@ -151,10 +195,10 @@ impl Signer<Certificate> for PrivateKey<Usage> {
         let mut msg: Vec<u8> = Vec::new();
         msg.save(&target.body)?;
 
-        let sig = sm::SM2::sign(self.key, &uid.as_bytes().to_vec(), &msg)?;
+        let sig = sm::SM2::sign(self.key, uid.as_bytes(), &msg)?;
 
         let sig = crate::crypto::Signature {
-            usage: self.usage.into(),
+            usage: self.usage,
             sig,
             algo: Some(self.usage.try_into()?),
             id: self.id,
@@ -231,6 +275,18 @@ impl codicon::Decoder<()> for Signatures {
     }
 }
 
+impl codicon::Encoder<()> for Signatures {
+    type Error = Error;
+
+    #[inline]
+    fn encode(&self, mut writer: impl Write, _: ()) -> Result<()> {
+        writer.save(&self.usage)?;
+        writer.save(&self.algo)?;
+        writer.save(&self.signature)?;
+        writer.write_all(&self._reserved)
+    }
+}
+
 impl codicon::Decoder<()> for Certificate {
     type Error = Error;
 
@@ -245,6 +301,16 @@ impl codicon::Decoder<()> for Certificate {
     }
 }
 
+impl codicon::Encoder<()> for Certificate {
+    type Error = Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> Result<()> {
+        writer.save(&self.body)?;
+        self.sigs[0].encode(&mut writer, ())?;
+        self.sigs[1].encode(&mut writer, ())
+    }
+}
+
 impl codicon::Encoder<crate::Body> for Certificate {
     type Error = Error;
 
@@ -280,7 +346,7 @@ impl Body {
             String::try_from(usage)?
         };
 
-        let uid_size = if let Some(u16_value) = uid.len().try_into().ok() {
+        let uid_size = if let Ok(u16_value) = uid.len().try_into() {
             u16_value
         } else {
             return Err(ErrorKind::InvalidInput.into());
@@ -326,6 +392,16 @@ impl Certificate {
         let key: PublicKey = self.try_into()?;
         key.encrypt(data)
     }
+
+    /// The SM2 user ID the certificate's key signs with, [`sm::sm2_id`] if
+    /// the certificate carries none. A corrupt size longer than the ID is
+    /// clamped to it.
+    pub fn sm2_id(&self) -> Vec<u8> {
+        match (self.body.data.uid_size as usize).min(self.body.data.user_id.len()) {
+            0 => sm::sm2_id(),
+            size => self.body.data.user_id[..size].to_vec(),
+        }
+    }
 }
 
 /// Downloads the HSK CEK certificate from the hygon certificate server.
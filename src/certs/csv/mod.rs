@@ -8,4 +8,6 @@ use super::*;
 pub(crate) mod cert;
 mod chain;
 pub use cert::Certificate;
+#[cfg(feature = "network")]
+pub use cert::{download_hskcek, get_certificate_data};
 pub use chain::Chain;
@@ -0,0 +1,188 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! For operating on the certificate chain served by the HYGON KDS.
+
+use super::*;
+use crate::certs::{builtin::HRK, ca, csv};
+
+use codicon::Decoder;
+
+use serde::{Deserialize, Serialize};
+
+/// The certificate chain that endorses a chip's attestation reports.
+///
+/// The HSK and the CEK are served per chip by the HYGON Key Distribution
+/// Service, the HRK is normally the builtin one.
+///
+/// None of the certificates carries a validity period, neither the CA
+/// certificates nor the CSV ones: a chain verifies the same at any time,
+/// so archived evidence still verifies as it did when it was produced. A
+/// chip is only ever distrusted by no longer trusting its certificates.
+///
+/// ```
+/// use codicon::Decoder;
+/// use csv_rs::certs::{builtin::HRK, ca, csv, kds, Verifiable};
+///
+/// let mut bundle = &include_bytes!("../../tests/test_data/hsk_cek.cert")[..];
+/// let hsk = ca::Certificate::decode(&mut bundle, ()).unwrap();
+/// let cek = csv::Certificate::decode(&mut bundle, ()).unwrap();
+/// let hrk = ca::Certificate::decode(&mut &HRK[..], ()).unwrap();
+///
+/// let chain = kds::Chain {
+///     ca: ca::Chain { hsk, hrk },
+///     cek,
+/// };
+/// assert!(chain.verify().is_ok());
+///
+/// // The HSK is not self-signed, so it can't act as the root.
+/// let chain = kds::Chain {
+///     ca: ca::Chain { hsk, hrk: hsk },
+///     cek,
+/// };
+/// assert!(chain.verify().is_err());
+/// ```
+#[repr(C)]
+#[derive(Deserialize, Serialize)]
+pub struct Chain {
+    /// The Certificate Authority chain.
+    pub ca: ca::Chain,
+
+    /// The Chip Endorsement Key certificate.
+    pub cek: csv::Certificate,
+}
+
+impl Chain {
+    /// The root of the chain, the HRK.
+    pub fn root(&self) -> &ca::Certificate {
+        self.ca.root()
+    }
+
+    /// The intermediate of the chain, the HSK.
+    pub fn intermediate(&self) -> &ca::Certificate {
+        &self.ca.hsk
+    }
+
+    /// The leaf of the chain, the CEK.
+    pub fn leaf(&self) -> &csv::Certificate {
+        &self.cek
+    }
+
+    /// Renders the chain from the HRK down, one certificate per link along
+    /// with whether its signer's signature verifies.
+    ///
+    /// ```
+    /// use csv_rs::certs::kds;
+    ///
+    /// let bundle = include_bytes!("../../tests/test_data/hsk_cek.cert");
+    /// let chain = kds::Chain::from_der_bundle(bundle).unwrap();
+    /// println!("{}", chain.describe());
+    /// ```
+    pub fn describe(&self) -> String {
+        let (hrk, hsk, cek) = (self.root(), self.intermediate(), self.leaf());
+
+        [
+            describe_link(
+                "HRK",
+                &hrk.subject(),
+                hrk.body.preamble.data.usage,
+                hrk.fingerprint_hex(),
+                "HRK",
+                (hrk, hrk).verify(),
+            ),
+            describe_link(
+                "HSK",
+                &hsk.subject(),
+                hsk.body.preamble.data.usage,
+                hsk.fingerprint_hex(),
+                "HRK",
+                (hrk, hsk).verify(),
+            ),
+            describe_link(
+                "CEK",
+                &cek.subject(),
+                cek.body.data.pubkey.usage,
+                cek.fingerprint_hex(),
+                "HSK",
+                (hsk, cek).verify(),
+            ),
+        ]
+        .concat()
+    }
+
+    /// Verifies the chain as [`Verifiable::verify`] does, checking the
+    /// signature of each link on a thread of its own.
+    ///
+    /// The links only depend on the keys of the certificates, so they are
+    /// checked concurrently and joined. The error names the first broken
+    /// link from the HRK down.
+    #[cfg(feature = "parallel")]
+    pub fn verify_parallel(&self) -> Result<&csv::Certificate> {
+        let (hrk, hsk, cek) = (self.root(), self.intermediate(), self.leaf());
+        let join = |handle: std::thread::ScopedJoinHandle<'_, Result<()>>| {
+            handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        };
+
+        let links = std::thread::scope(|s| {
+            let root = s.spawn(|| (hrk, hrk).verify());
+            let intermediate = s.spawn(|| (hrk, hsk).verify());
+            let leaf = (hsk, cek).verify();
+            [
+                ("HRK", "HRK", join(root)),
+                ("HSK", "HRK", join(intermediate)),
+                ("CEK", "HSK", leaf),
+            ]
+        });
+
+        for (role, signer, result) in links {
+            result.map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("{role} isn't signed by the {signer}: {e}"),
+                )
+            })?;
+        }
+
+        Ok(cek)
+    }
+
+    /// Encodes the HSK and the CEK in the order the KDS serves them.
+    pub fn to_der_bundle(&self) -> Vec<u8> {
+        [self.ca.hsk.to_der(), self.cek.to_der()].concat()
+    }
+
+    /// Decodes a HSK and CEK bundle as served by the KDS.
+    ///
+    /// The chain is rooted at the builtin HRK.
+    pub fn from_der_bundle(mut bundle: &[u8]) -> Result<Self> {
+        let hsk = ca::Certificate::decode(&mut bundle, ())?;
+        if Usage::try_from(&hsk)? != Usage::HSK {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+
+        let cek = csv::Certificate::decode(&mut bundle, ())?;
+        if Usage::try_from(&cek)? != Usage::CEK {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+
+        let hrk = ca::Certificate::decode(&mut &HRK[..], ())?;
+
+        Ok(Self {
+            ca: ca::Chain { hsk, hrk },
+            cek,
+        })
+    }
+}
+
+impl<'a> Verifiable for &'a Chain {
+    type Output = &'a csv::Certificate;
+
+    fn verify(self) -> Result<Self::Output> {
+        let hsk = self.ca.verify()?;
+        (hsk, &self.cek).verify()?;
+        Ok(&self.cek)
+    }
+}
@@ -3,11 +3,23 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! Everything needed for working with HYGON CSV certificate chains.
+//!
+//! The chains map onto the AMD SEV ones as follows:
+//!
+//! | Role         | CSV | SEV | Type                                                               |
+//! |--------------|-----|-----|--------------------------------------------------------------------|
+//! | root         | HRK | ARK | [`RootCertificate`](crate::certs::RootCertificate)                 |
+//! | intermediate | HSK | ASK | [`IntermediateCertificate`](crate::certs::IntermediateCertificate) |
+//! | leaf         | CEK | CEK | [`LeafCertificate`](crate::certs::LeafCertificate)                 |
+//! | leaf         | PEK | PEK | [`LeafCertificate`](crate::certs::LeafCertificate)                 |
+//!
+//! The chains offer `root()` and `leaf()` accessors along these roles.
 
 pub mod builtin;
 pub mod ca;
 mod chain;
 pub mod csv;
+pub mod kds;
 
 use serde::{Deserialize, Serialize};
 use std::{
@@ -17,6 +29,15 @@ use std::{
 
 pub use chain::Chain;
 
+/// The certificate of the HYGON Root Key, the ARK of SEV.
+pub type RootCertificate = ca::Certificate;
+
+/// The certificate of the HYGON Signing Key, the ASK of SEV.
+pub type IntermediateCertificate = ca::Certificate;
+
+/// The certificates the platform keys, e.g. the CEK and the PEK, come with.
+pub type LeafCertificate = csv::Certificate;
+
 use openssl::hash;
 
 /// An interface for types that may containe entities
@@ -38,9 +59,34 @@ pub trait Signer<T> {
     fn sign(&self, target: &mut T, uid: String) -> Result<Self::Output>;
 }
 
+/// Renders one link of a chain, a certificate and whether the signature of
+/// its signer verifies, for the chains' `describe`.
+pub(crate) fn describe_link(
+    role: &str,
+    subject: &str,
+    usage: Usage,
+    fingerprint: std::result::Result<String, crate::error::Error>,
+    signer: &str,
+    status: Result<()>,
+) -> String {
+    let usage = match String::try_from(usage) {
+        Ok(name) => name,
+        Err(_) => format!("{:#x}", usage.0),
+    };
+    let fingerprint = fingerprint.unwrap_or_else(|e| format!("unavailable ({e})"));
+    let status = match status {
+        Ok(()) => String::from("ok"),
+        Err(e) => format!("FAILED ({e})"),
+    };
+
+    format!(
+        "{role}: subject {subject}, usage {usage}, fingerprint {fingerprint}\n    signed by {signer}: {status}\n"
+    )
+}
+
 /// Denotes a certificate's usage.
 #[repr(C)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Usage(u32);
 
 impl Usage {
@@ -65,18 +111,18 @@ impl Usage {
     const INV: Usage = Usage(0x1000u32.to_le());
 }
 
-impl Default for Usage {
-    fn default() -> Self {
-        Usage(0)
-    }
-}
-
 impl From<u32> for Usage {
     fn from(value: u32) -> Self {
         Self(value.to_le())
     }
 }
 
+impl From<Usage> for u32 {
+    fn from(value: Usage) -> Self {
+        u32::from_le(value.0)
+    }
+}
+
 impl TryFrom<Usage> for String {
     type Error = Error;
 
@@ -108,7 +154,7 @@ impl TryFrom<Usage> for Algorithm {
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Algorithm(u32);
 
 impl Algorithm {
@@ -117,18 +163,29 @@ impl Algorithm {
     pub const NONE: Algorithm = Algorithm(0x0000u32.to_le());
 }
 
-impl Default for Algorithm {
-    fn default() -> Self {
-        Algorithm(0)
-    }
-}
-
 impl From<u32> for Algorithm {
     fn from(value: u32) -> Self {
         Self(value.to_le())
     }
 }
 
+impl From<Algorithm> for u32 {
+    fn from(value: Algorithm) -> Self {
+        u32::from_le(value.0)
+    }
+}
+
+impl std::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Algorithm::SM2_SA => write!(f, "sm2-sa"),
+            Algorithm::SM2_DH => write!(f, "sm2-dh"),
+            Algorithm::NONE => write!(f, "none"),
+            Algorithm(id) => write!(f, "algo({:#x})", u32::from_le(id)),
+        }
+    }
+}
+
 impl TryFrom<Algorithm> for hash::MessageDigest {
     type Error = Error;
 
@@ -8,9 +8,15 @@ use crate::{crypto::key::group::Group, util::*};
 use openssl::{bn, ec, pkey};
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
-use std::io::{Error, Result};
+use std::io::{Error, ErrorKind, Result};
 
 /// The Raw format of ecc pubkey.
+///
+/// The firmware lays out the coordinates little endian, as do the
+/// certificates of the KDS: `x` and `y` hold the coordinates little endian,
+/// in the size of the group, followed by zero bytes. Coordinates from other
+/// sources, e.g. big endian ones of an X.509 key, go through
+/// [`PubKey::from_be`].
 #[repr(C)]
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
 pub struct PubKey {
@@ -21,15 +27,83 @@ pub struct PubKey {
     pub y: [u8; 72],
 }
 
+impl PubKey {
+    /// A key of the curve `g` from its little endian coordinates, the
+    /// firmware's convention.
+    ///
+    /// Fails if a coordinate is longer than the group's size.
+    pub fn from_le(g: Group, x: &[u8], y: &[u8]) -> Result<Self> {
+        let size = g.size()?;
+        if x.len() > size || y.len() > size {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+
+        let mut key = Self {
+            g,
+            x: [0u8; 72],
+            y: [0u8; 72],
+        };
+        key.x[..x.len()].copy_from_slice(x);
+        key.y[..y.len()].copy_from_slice(y);
+        Ok(key)
+    }
+
+    /// A key of the curve `g` from its big endian coordinates, converted to
+    /// the firmware's little endian layout.
+    ///
+    /// Fails if a coordinate is longer than the group's size.
+    pub fn from_be(g: Group, x: &[u8], y: &[u8]) -> Result<Self> {
+        let reversed = |c: &[u8]| c.iter().rev().copied().collect::<Vec<u8>>();
+        Self::from_le(g, &reversed(x), &reversed(y))
+    }
+
+    /// The coordinates of the key, big endian in the size of the group.
+    pub fn be_coordinates(&self) -> Result<(Vec<u8>, Vec<u8>)> {
+        let size = self.g.size()?;
+        let reversed = |c: &[u8]| c[..size].iter().rev().copied().collect::<Vec<u8>>();
+        Ok((reversed(&self.x), reversed(&self.y)))
+    }
+
+    /// The SM3 digest of the encoded key, see
+    /// [`check_backend`](crate::crypto::check_backend) for its failure.
+    pub fn fingerprint(&self) -> std::result::Result<[u8; 32], crate::error::Error> {
+        let mut encoded = Vec::new();
+        encoded.save(self).expect("encoding into a Vec can't fail");
+        crate::crypto::fingerprint(&encoded)
+    }
+
+    /// The fingerprint of the key, hex encoded.
+    pub fn fingerprint_hex(&self) -> std::result::Result<String, crate::error::Error> {
+        self.fingerprint().map(hex::encode)
+    }
+}
+
 impl TryFrom<&PubKey> for ec::EcKey<pkey::Public> {
     type Error = Error;
 
+    /// Decodes the coordinates little endian, see [`PubKey`].
     fn try_from(value: &PubKey) -> Result<Self> {
+        // A zero size would turn the empty coordinates into a zero key.
         let s = value.g.size()?;
+        let group = ec::EcGroup::try_from(value.g)?;
+        let x = bn::BigNum::from_le(&value.x[..s])?;
+        let y = bn::BigNum::from_le(&value.y[..s])?;
+
+        // Coordinates are elements of the curve's field, i.e. less than
+        // its prime, rather than left for OpenSSL to reduce.
+        let mut ctx = bn::BigNumContext::new()?;
+        let mut p = bn::BigNum::new()?;
+        let (mut a, mut b) = (bn::BigNum::new()?, bn::BigNum::new()?);
+        group.components_gfp(&mut p, &mut a, &mut b, &mut ctx)?;
+        if x >= p || y >= p {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                crate::error::Error::InvalidPublicKey,
+            ));
+        }
+
         Ok(ec::EcKey::from_public_key_affine_coordinates(
-            &*ec::EcGroup::try_from(value.g)?,
-            &*bn::BigNum::from_le(&value.x[..s])?,
-            &*bn::BigNum::from_le(&value.y[..s])?,
+            &group, &x, &y,
         )?)
     }
 }
@@ -4,7 +4,11 @@
 
 use openssl::{ec, nid};
 use serde::{Deserialize, Serialize};
-use std::io::{Error, ErrorKind, Result};
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+    str::FromStr,
+};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -13,11 +17,39 @@ pub struct Group(u32);
 impl Group {
     pub const SM2_256: Group = Group(3u32.to_le());
 
+    /// The size in bytes of the curve's coordinates.
+    ///
+    /// Fails with [`crate::error::Error::InvalidCurveSize`] for groups
+    /// without a known size, e.g. a corrupt group value.
     pub fn size(self) -> Result<usize> {
-        Ok(match self {
-            Group::SM2_256 => 32,
-            _ => return Err(ErrorKind::InvalidInput.into()),
-        })
+        match self {
+            Group::SM2_256 => Ok(32),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                crate::error::Error::InvalidCurveSize,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Group::SM2_256 => write!(f, "sm2p256"),
+            Group(id) => write!(f, "group({})", u32::from_le(id)),
+        }
+    }
+}
+
+impl FromStr for Group {
+    type Err = crate::error::Error;
+
+    /// Parses the name of a curve as displayed, e.g. `sm2p256`.
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "sm2p256" => Ok(Group::SM2_256),
+            _ => Err(crate::error::Error::UnknownGroup(name.to_string())),
+        }
     }
 }
 
@@ -4,5 +4,5 @@
 
 //! Interfaces for public and private keys.
 
-pub(crate) mod ecc;
+pub mod ecc;
 pub mod group;
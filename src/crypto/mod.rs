@@ -5,17 +5,71 @@
 //! Interfaces for cryptography.
 
 pub mod key;
-pub(crate) mod sig;
+pub mod sig;
 pub mod sm;
 
 use crate::{
     certs::{Algorithm, Usage},
-    crypto::key::ecc,
+    crypto::key::{ecc, group::Group},
     Body,
 };
 use openssl::hash;
 use openssl_sys::EC_KEY;
 use std::io::{Error, ErrorKind, Result};
+use std::sync::OnceLock;
+
+/// The SM3 digest identifying an encoded key or certificate.
+///
+/// Returns [`Error::UnsupportedBackend`] if the OpenSSL backend lacks SM3.
+///
+/// [`Error::UnsupportedBackend`]: crate::error::Error::UnsupportedBackend
+pub(crate) fn fingerprint(encoded: &[u8]) -> std::result::Result<[u8; 32], crate::error::Error> {
+    check_backend()?;
+    let digest = hash::hash(hash::MessageDigest::sm3(), encoded)?;
+    Ok(digest[..].try_into().unwrap())
+}
+
+/// The primitive the backend lacks, if any, as probed by [`check_backend`].
+static MISSING_PRIMITIVE: OnceLock<Option<&'static str>> = OnceLock::new();
+
+/// Checks that the OpenSSL backend provides SM3 and SM2.
+///
+/// The backend is probed on the first call only, later calls return the
+/// cached outcome. Returns [`Error::UnsupportedBackend`] naming the missing
+/// primitive otherwise, rather than letting hashing or verification fail
+/// deep down with an opaque error.
+///
+/// [`Error::UnsupportedBackend`]: crate::error::Error::UnsupportedBackend
+pub fn check_backend() -> std::result::Result<(), crate::error::Error> {
+    match MISSING_PRIMITIVE.get_or_init(probe_backend) {
+        None => Ok(()),
+        Some(missing) => Err(crate::error::Error::UnsupportedBackend(missing.to_string())),
+    }
+}
+
+/// Hashes a known vector with SM3 and signs with a generated SM2 key.
+fn probe_backend() -> Option<&'static str> {
+    const SM3_ABC: [u8; 32] = [
+        0x66, 0xc7, 0xf0, 0xf4, 0x62, 0xee, 0xed, 0xd9, 0xd1, 0xf2, 0xd4, 0x6b, 0xdc, 0x10, 0xe4,
+        0xe2, 0x41, 0x67, 0xc4, 0x87, 0x5c, 0xf2, 0xf7, 0xa2, 0x29, 0x7d, 0xa0, 0x2b, 0x8f, 0x4b,
+        0xa8, 0xe0,
+    ];
+
+    match hash::hash(hash::MessageDigest::sm3(), b"abc") {
+        Ok(digest) if digest[..] == SM3_ABC => (),
+        _ => return Some("SM3"),
+    }
+
+    let sm2 = sm::SM2::generate(Group::SM2_256).and_then(|(key, prv)| {
+        let sig = sm::SM2::sign(prv, sm::DEFAULT_SM2_ID, b"probe")?;
+        sm::SM2::verify(key, &sig, sm::DEFAULT_SM2_ID, b"probe")
+    });
+    if !matches!(sm2, Ok(true)) {
+        return Some("SM2");
+    }
+
+    None
+}
 
 #[derive(Debug)]
 pub struct Signature {
@@ -57,7 +111,7 @@ impl PublicKey {
 
         let mut buf: Vec<u8> = Vec::new();
         msg.encode(&mut buf, Body)?;
-        sm::SM2::verify(self.key, &sig.sig, &Vec::from(uid), &buf).map(|ok| {
+        sm::SM2::verify(self.key, &sig.sig, uid, &buf).map(|ok| {
             // SM2 verify will return Ok(true) if the signature
             // is verified and Ok(false) if not. This patches the result
             // to return Err if SM2 returns Ok(false).
@@ -70,6 +124,6 @@ impl PublicKey {
     }
 
     pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
-        sm::SM2::encrypt(&data, self.key)
+        sm::SM2::encrypt(data, self.key)
     }
 }
@@ -2,6 +2,7 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use super::SM2_SIG_COMPONENT_LEN;
 use crate::util::*;
 use log::*;
 use openssl::{bn, ecdsa};
@@ -14,9 +15,9 @@ use std::io::{Error, Result};
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
 pub struct Signature {
     #[serde(with = "BigArray")]
-    pub r: [u8; 72],
+    pub r: [u8; SM2_SIG_COMPONENT_LEN],
     #[serde(with = "BigArray")]
-    pub s: [u8; 72],
+    pub s: [u8; SM2_SIG_COMPONENT_LEN],
 }
 
 impl From<ecdsa::EcdsaSig> for Signature {
@@ -61,8 +62,8 @@ impl TryFrom<&Signature> for Vec<u8> {
 impl Default for Signature {
     fn default() -> Self {
         Self {
-            r: [0u8; 72],
-            s: [0u8; 72],
+            r: [0u8; SM2_SIG_COMPONENT_LEN],
+            s: [0u8; SM2_SIG_COMPONENT_LEN],
         }
     }
 }
@@ -5,3 +5,90 @@
 //! Interfaces for signatures.
 
 pub(crate) mod ecdsa;
+
+use crate::crypto::key::group::Group;
+use static_assertions::const_assert;
+use std::io::{Error, ErrorKind, Result};
+
+/// The size of each of `r` and `s` in a signature as laid out by the
+/// firmware.
+pub const SM2_SIG_COMPONENT_LEN: usize = 72;
+
+/// The size of a signature as laid out by the firmware, `r` then `s`.
+pub const SM2_SIG_LEN: usize = 2 * SM2_SIG_COMPONENT_LEN;
+
+/// The size of a signature as laid out by the firmware, i.e.
+/// [`SM2_SIG_LEN`].
+pub const SIGNATURE_LEN: usize = SM2_SIG_LEN;
+
+const_assert!(std::mem::size_of::<ecdsa::Signature>() == SM2_SIG_LEN);
+
+/// A signature as laid out by the firmware in reports and certificates.
+///
+/// `r` and `s` are stored little-endian in [`SM2_SIG_COMPONENT_LEN`] bytes
+/// each, of which only the
+/// size of the curve's group is used, the firmware leaves the others zero.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Signature {
+    sig: ecdsa::Signature,
+    size: usize,
+}
+
+impl Signature {
+    /// Wraps the raw `r` and `s` of a signature on the curve `group`.
+    pub fn new(raw: &[u8; SIGNATURE_LEN], group: Group) -> Result<Self> {
+        let mut sig = ecdsa::Signature::default();
+        sig.r.copy_from_slice(&raw[..SM2_SIG_COMPONENT_LEN]);
+        sig.s.copy_from_slice(&raw[SM2_SIG_COMPONENT_LEN..]);
+
+        Self::with_group(sig, group)
+    }
+
+    /// Decodes a DER encoded signature on the curve `group`.
+    pub fn from_der(der: &[u8], group: Group) -> Result<Self> {
+        Self::with_group(ecdsa::Signature::try_from(der)?, group)
+    }
+
+    /// Encodes the signature in DER, as verified by OpenSSL.
+    pub fn to_der(&self) -> Result<Vec<u8>> {
+        Vec::try_from(&self.sig)
+    }
+
+    /// The `r` half of the signature, little-endian in the group's size.
+    pub fn r(&self) -> &[u8] {
+        &self.sig.r[..self.size]
+    }
+
+    /// The `s` half of the signature, little-endian in the group's size.
+    pub fn s(&self) -> &[u8] {
+        &self.sig.s[..self.size]
+    }
+
+    /// The signature as laid out by the firmware.
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_LEN] {
+        let mut raw = [0u8; SIGNATURE_LEN];
+        raw[..SM2_SIG_COMPONENT_LEN].copy_from_slice(&self.sig.r);
+        raw[SM2_SIG_COMPONENT_LEN..].copy_from_slice(&self.sig.s);
+        raw
+    }
+
+    /// Wraps a signature on the SM2 curve, as found in reports.
+    pub(crate) fn sm2(sig: ecdsa::Signature) -> Self {
+        Self { sig, size: 32 }
+    }
+
+    fn with_group(sig: ecdsa::Signature, group: Group) -> Result<Self> {
+        let size = group.size()?;
+
+        // The bytes past the group's size must be zero, or the signature
+        // isn't on the curve.
+        if sig.r[size..].iter().chain(&sig.s[size..]).any(|&b| b != 0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "signature larger than the curve's group",
+            ));
+        }
+
+        Ok(Self { sig, size })
+    }
+}
@@ -6,11 +6,17 @@
 
 use crate::crypto::key::{ecc, group};
 use libc::*;
-use openssl::nid;
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::EcGroup,
+    hash::{Hasher, MessageDigest},
+    nid,
+};
 use openssl_sys::*;
 use std::{
     io::{Error, ErrorKind, Result},
     ptr,
+    sync::{PoisonError, RwLock},
 };
 
 #[cfg(ossl111)]
@@ -18,6 +24,39 @@ pub const EVP_PKEY_CTRL_SET1_ID: c_int = EVP_PKEY_ALG_CTRL + 11;
 
 const ECDH_KDF_MAX: size_t = 1 << 30;
 
+/// The user ID of GB/T 32918.2 for signers that agreed on no other one.
+pub const DEFAULT_SM2_ID: &[u8] = b"1234567812345678";
+
+static SM2_ID: RwLock<Option<Vec<u8>>> = RwLock::new(None);
+
+/// Sets the user ID to verify with when a signer's certificate carries none.
+///
+/// The ID must be shorter than 8192 bytes, as its bit length is encoded on
+/// 16 bits in ZA.
+pub fn set_sm2_id(id: &[u8]) -> Result<()> {
+    entl(id)?;
+    *SM2_ID.write().unwrap_or_else(PoisonError::into_inner) = Some(id.to_vec());
+    Ok(())
+}
+
+/// The user ID to verify with when a signer's certificate carries none,
+/// [`DEFAULT_SM2_ID`] unless changed with [`set_sm2_id`].
+pub fn sm2_id() -> Vec<u8> {
+    SM2_ID
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SM2_ID.to_vec())
+}
+
+/// The bit length of `id` as encoded in ZA.
+fn entl(id: &[u8]) -> Result<u16> {
+    id.len()
+        .checked_mul(8)
+        .and_then(|bits| u16::try_from(bits).ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "SM2 user ID is too long"))
+}
+
 extern "C" {
     #[cfg(ossl111)]
     pub fn EVP_PKEY_set_alias_type(pkey: *mut EVP_PKEY, ttype: c_int) -> c_int;
@@ -56,13 +95,63 @@ pub unsafe fn EVP_PKEY_CTX_set1_id(
 pub struct SM2 {}
 
 impl SM2 {
+    /// Computes the ZA digest binding the signer's `id` and public key,
+    /// which is hashed in front of every message the signer signs.
+    pub fn za(ecc_pubkey: &ecc::PubKey, id: &[u8]) -> Result<[u8; 32]> {
+        let entl = entl(id)?;
+        let pubkey_size = ecc_pubkey.g.size()?;
+        let curve: nid::Nid = ecc_pubkey.g.try_into()?;
+
+        let group = EcGroup::from_curve_name(curve)?;
+        let mut ctx = BigNumContext::new()?;
+        let (mut p, mut a, mut b) = (BigNum::new()?, BigNum::new()?, BigNum::new()?);
+        group.components_gfp(&mut p, &mut a, &mut b, &mut ctx)?;
+        let (mut xg, mut yg) = (BigNum::new()?, BigNum::new()?);
+        group
+            .generator_opt()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "curve has no generator"))?
+            .affine_coordinates_gfp(&group, &mut xg, &mut yg, &mut ctx)?;
+
+        let mut hasher = Hasher::new(MessageDigest::sm3())?;
+        hasher.update(&entl.to_be_bytes())?;
+        hasher.update(id)?;
+        for bn in [&a, &b, &xg, &yg] {
+            hasher.update(&bn.to_vec_padded(pubkey_size as i32)?)?;
+        }
+        for coord in [&ecc_pubkey.x, &ecc_pubkey.y] {
+            let coord = coord[..pubkey_size]
+                .iter()
+                .rev()
+                .cloned()
+                .collect::<Vec<_>>();
+            hasher.update(&coord)?;
+        }
+
+        let za = hasher.finish()?;
+        Ok(za[..].try_into().unwrap())
+    }
+
+    /// Computes the digest a signature of `msg` is over: the SM3 of the ZA
+    /// of `id`, see [`SM2::za`], followed by the message.
+    pub fn digest(ecc_pubkey: &ecc::PubKey, id: &[u8], msg: &[u8]) -> Result<[u8; 32]> {
+        let mut hasher = Hasher::new(MessageDigest::sm3())?;
+        hasher.update(&SM2::za(ecc_pubkey, id)?)?;
+        hasher.update(msg)?;
+        let digest = hasher.finish()?;
+        Ok(digest[..].try_into().unwrap())
+    }
+
     /// use SM2 algorithm to verify a msg's signature
-    pub fn verify(
-        ecc_pubkey: ecc::PubKey,
-        sig: &[u8],
-        id: &Vec<u8>,
-        msg: &Vec<u8>,
-    ) -> Result<bool> {
+    ///
+    /// The message is hashed behind the ZA of `id`, see [`SM2::za`].
+    pub fn verify(ecc_pubkey: ecc::PubKey, sig: &[u8], id: &[u8], msg: &[u8]) -> Result<bool> {
+        let digest = SM2::digest(&ecc_pubkey, id, msg)?;
+        SM2::verify_digest(ecc_pubkey, sig, &digest)
+    }
+
+    /// Verifies the DER signature `sig` over a `digest` computed with
+    /// [`SM2::digest`].
+    pub fn verify_digest(ecc_pubkey: ecc::PubKey, sig: &[u8], digest: &[u8; 32]) -> Result<bool> {
         let mut verify_result = false;
         let pubkey_size = ecc_pubkey.g.size()?;
 
@@ -101,18 +190,15 @@ impl SM2 {
             #[cfg(ossl111)]
             EVP_PKEY_set_alias_type(pkey, EVP_PKEY_SM2);
 
-            let mctx: *mut EVP_MD_CTX = EVP_MD_CTX_new();
             let pctx = EVP_PKEY_CTX_new(pkey, ptr::null_mut());
-            EVP_PKEY_CTX_set1_id(pctx, id.as_ptr() as *mut c_void, id.len() as c_int);
-            EVP_MD_CTX_set_pkey_ctx(mctx, pctx);
-            EVP_DigestVerifyInit(mctx, ptr::null_mut(), EVP_sm3(), ptr::null_mut(), pkey);
-            EVP_DigestVerifyUpdate(mctx, msg.as_ptr() as *mut c_void, msg.len());
-            if EVP_DigestVerifyFinal(mctx, sig.as_ptr(), sig.len()) == 1 {
+            if EVP_PKEY_verify_init(pctx) == 1
+                && EVP_PKEY_verify(pctx, sig.as_ptr(), sig.len(), digest.as_ptr(), digest.len())
+                    == 1
+            {
                 verify_result = true;
             }
             EVP_PKEY_CTX_free(pctx);
             EVP_PKEY_free(pkey);
-            EVP_MD_CTX_free(mctx);
         }
         Ok(verify_result)
     }
@@ -163,13 +249,21 @@ impl SM2 {
         Ok((pubkey, eckey))
     }
 
-    pub fn sign(pri_key: *mut EC_KEY, id: &Vec<u8>, data: &Vec<u8>) -> Result<Vec<u8>> {
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn sign(pri_key: *mut EC_KEY, id: &[u8], data: &[u8]) -> Result<Vec<u8>> {
         let r = unsafe {
             let pkey = EVP_PKEY_new();
             if pkey.is_null() {
                 return Err(ErrorKind::InvalidData.into());
             }
+            // The EVP_PKEY frees the key it's assigned, keep it alive for
+            // the caller to sign with again.
+            if EC_KEY_up_ref(pri_key) <= 0 {
+                EVP_PKEY_free(pkey);
+                return Err(Error::new(ErrorKind::InvalidData, "EC_KEY_up_ref failed"));
+            }
             if EVP_PKEY_assign(pkey, EVP_PKEY_SM2, pri_key as *mut c_void) <= 0 {
+                EC_KEY_free(pri_key);
                 EVP_PKEY_free(pkey);
                 return Err(Error::new(ErrorKind::InvalidData, "EVP_PKEY_assign failed"));
             }
@@ -373,3 +467,65 @@ impl SM2 {
         Ok(ciphertext_buf)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The generator of the SM2 curve, i.e. the public key of the private key 1.
+    fn generator() -> ecc::PubKey {
+        let xg = hex::decode("32c4ae2c1f1981195f9904466a39c9948fe30bbff2660be1715a4589334c74c7")
+            .unwrap();
+        let yg = hex::decode("bc3736a2f4f6779c59bdcee36b692153d0a9877cc62a474002df32e52139f0a0")
+            .unwrap();
+        let mut key = ecc::PubKey {
+            g: group::Group::SM2_256,
+            x: [0u8; 72],
+            y: [0u8; 72],
+        };
+        key.x[..32].copy_from_slice(&xg.into_iter().rev().collect::<Vec<_>>());
+        key.y[..32].copy_from_slice(&yg.into_iter().rev().collect::<Vec<_>>());
+        key
+    }
+
+    #[test]
+    fn sm2_za_default_id() {
+        let za = SM2::za(&generator(), DEFAULT_SM2_ID).unwrap();
+        assert_eq!(
+            hex::encode(za),
+            "5b32bfe35482899b195d72c09d33ccdb465b2ded883240ff91f120a68bc91de8"
+        );
+    }
+
+    #[test]
+    fn sm2_za_empty_id() {
+        assert_eq!(entl(b"").unwrap(), 0);
+
+        let za = SM2::za(&generator(), b"").unwrap();
+        assert_eq!(
+            hex::encode(za),
+            "c13adcc1829f563f2a01ef3c4e0685647bf32a650a35273443150d44f5809ff8"
+        );
+    }
+
+    #[test]
+    fn sm2_za_long_id() {
+        // 2400 bits, so both bytes of ENTLA are used.
+        let id = [b'x'; 300];
+        assert_eq!(entl(&id).unwrap().to_be_bytes(), [0x09, 0x60]);
+
+        let za = SM2::za(&generator(), &id).unwrap();
+        assert_eq!(
+            hex::encode(za),
+            "1934a7b3b306f252e6adc978b0f920530d555ed25690499822c82ae94534da51"
+        );
+    }
+
+    #[test]
+    fn sm2_id_too_long() {
+        assert_eq!(entl(&[0u8; 8191]).unwrap(), 65528);
+        assert!(entl(&[0u8; 8192]).is_err());
+        assert!(SM2::za(&generator(), &[0u8; 8192]).is_err());
+        assert!(set_sm2_id(&[0u8; 8192]).is_err());
+    }
+}
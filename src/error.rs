@@ -4,11 +4,16 @@
 //
 
 use openssl::error::ErrorStack;
+use static_assertions::assert_impl_all;
 use std::{
     convert::From,
     error,
+    ffi::CStr,
     fmt::{Debug, Display},
     io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 /// Error conditions returned by the CSV platform or by layers above it
@@ -147,6 +152,253 @@ pub enum Error {
 
     /// Unknown error
     Unknown, // 0x0029
+
+    /// The mnonce carried by an attestation report is not the requested one.
+    MnonceMismatch {
+        /// The mnonce requested by the guest.
+        expected: [u8; 16],
+        /// The mnonce recovered from the report.
+        got: [u8; 16],
+    },
+
+    /// An OpenSSL operation failed, with the errors OpenSSL queued.
+    OpenSsl(ErrorStack),
+
+    /// No DCU of the topology has the requested gpu_id.
+    UnknownGpuId(u32),
+
+    /// A reserved byte of a firmware structure is not zero.
+    NonZeroReserved {
+        /// The offset of the first non-zero reserved byte.
+        offset: usize,
+    },
+
+    /// The signature algorithm claimed by a report doesn't use the curve of
+    /// the key it is verified with.
+    AlgoCurveMismatch,
+
+    /// No elliptic curve group has the given name.
+    UnknownGroup(String),
+
+    /// The coordinates of a key's elliptic curve group have no valid size.
+    InvalidCurveSize,
+
+    /// The attestation message version isn't one this crate supports.
+    UnsupportedVersion(u32),
+
+    /// A device node doesn't exist or can't be opened, e.g. for lack of
+    /// permissions.
+    DeviceUnavailable {
+        /// The path of the device node.
+        path: PathBuf,
+        /// What to check to make the device available.
+        hint: String,
+    },
+
+    /// A public key's coordinates aren't elements of its curve's field.
+    InvalidPublicKey,
+
+    /// A remote verifier answered with an HTTP error.
+    RemoteHttp {
+        /// The HTTP status of the answer.
+        status: u16,
+        /// The body of the answer, lossily decoded.
+        body: String,
+    },
+
+    /// A remote verifier answered with a verdict that doesn't parse.
+    MalformedVerdict(String),
+
+    /// The report has a zero anonce, i.e. its fields aren't masked.
+    ZeroAnonce,
+
+    /// The report's `report_data` doesn't bind the expected data.
+    ReportDataMismatch,
+
+    /// The OpenSSL backend lacks a primitive attestation relies on, SM3 or
+    /// SM2.
+    UnsupportedBackend(String),
+
+    /// None of the candidate chains verifies the report, with the error of
+    /// each chain in order.
+    NoChainVerifies(Vec<Error>),
+
+    /// The report request succeeded but the firmware didn't write a
+    /// response, e.g. under a stubbed kernel.
+    EmptyResponse,
+
+    /// The policy carried by an attestation report is not the expected one.
+    PolicyMismatch {
+        /// The bits of the expected policy.
+        expected: u32,
+        /// The bits of the policy recovered from the report.
+        got: u32,
+    },
+
+    /// A device didn't become available within the given time.
+    Timeout(Duration),
+
+    /// The version of a response page isn't one this crate knows.
+    UnknownReportVersion(u32),
+
+    /// The DCU whose ID is `gpu_id` reports another measurement than the
+    /// other DCUs of the host.
+    MeasurementDivergence {
+        /// The ID of the outlying DCU.
+        gpu_id: u32,
+    },
+
+    /// The user may read the ID of none of the DCU nodes of the topology.
+    ///
+    /// Device nodes the user may not open, e.g. the CSV guest device, are
+    /// reported as [`Error::DeviceUnavailable`] instead.
+    PermissionDenied {
+        /// The nodes whose ID couldn't be read.
+        nodes: Vec<usize>,
+    },
+
+    /// The report's version is lower than the verifier requires.
+    ReportVersionTooOld {
+        /// The version of the report.
+        got: u32,
+        /// The lowest version the verifier trusts.
+        min: u32,
+    },
+
+    /// No DCU of the host produced a report that verifies, with the error
+    /// of each DCU along with its ID, in topology order.
+    NoDcuVerifies(Vec<(u32, Error)>),
+}
+
+assert_impl_all!(Error: Send, Sync);
+
+impl Error {
+    /// The kind of the failure, the name of its variant, e.g.
+    /// `"MnonceMismatch"`, for clients branching on it.
+    ///
+    /// Kinds are stable, a kind is only added along with its variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::IoError(_) => "IoError",
+            Error::InvalidPlatformState => "InvalidPlatformState",
+            Error::InvalidGuestState => "InvalidGuestState",
+            Error::InvalidConfig => "InvalidConfig",
+            Error::InvalidLen => "InvalidLen",
+            Error::AlreadyOwned => "AlreadyOwned",
+            Error::InvalidCertificate => "InvalidCertificate",
+            Error::PolicyFailure => "PolicyFailure",
+            Error::Inactive => "Inactive",
+            Error::InvalidAddress => "InvalidAddress",
+            Error::BadSignature => "BadSignature",
+            Error::BadMeasurement => "BadMeasurement",
+            Error::AsidOwned => "AsidOwned",
+            Error::InvalidAsid => "InvalidAsid",
+            Error::WbinvdRequired => "WbinvdRequired",
+            Error::DfFlushRequired => "DfFlushRequired",
+            Error::InvalidGuest => "InvalidGuest",
+            Error::InvalidCommand => "InvalidCommand",
+            Error::Active => "Active",
+            Error::HardwarePlatform => "HardwarePlatform",
+            Error::HardwareUnsafe => "HardwareUnsafe",
+            Error::Unsupported => "Unsupported",
+            Error::InvalidParam => "InvalidParam",
+            Error::ResourceLimit => "ResourceLimit",
+            Error::SecureDataInvalid => "SecureDataInvalid",
+            Error::InvalidPageSize => "InvalidPageSize",
+            Error::InvalidPageState => "InvalidPageState",
+            Error::InvalidMdataEntry => "InvalidMdataEntry",
+            Error::InvalidPageOwner => "InvalidPageOwner",
+            Error::AEADOFlow => "AEADOFlow",
+            Error::RbModeExited => "RbModeExited",
+            Error::RMPInitRequired => "RMPInitRequired",
+            Error::BadSvn => "BadSvn",
+            Error::BadVersion => "BadVersion",
+            Error::ShutdownRequired => "ShutdownRequired",
+            Error::UpdateFailed => "UpdateFailed",
+            Error::RestoreRequired => "RestoreRequired",
+            Error::RMPInitFailed => "RMPInitFailed",
+            Error::InvalidKey => "InvalidKey",
+            Error::Custom(_) => "Custom",
+            Error::Unknown => "Unknown",
+            Error::MnonceMismatch { .. } => "MnonceMismatch",
+            Error::OpenSsl(_) => "OpenSsl",
+            Error::UnknownGpuId(_) => "UnknownGpuId",
+            Error::NonZeroReserved { .. } => "NonZeroReserved",
+            Error::AlgoCurveMismatch => "AlgoCurveMismatch",
+            Error::UnknownGroup(_) => "UnknownGroup",
+            Error::InvalidCurveSize => "InvalidCurveSize",
+            Error::UnsupportedVersion(_) => "UnsupportedVersion",
+            Error::DeviceUnavailable { .. } => "DeviceUnavailable",
+            Error::InvalidPublicKey => "InvalidPublicKey",
+            Error::RemoteHttp { .. } => "RemoteHttp",
+            Error::MalformedVerdict(_) => "MalformedVerdict",
+            Error::ZeroAnonce => "ZeroAnonce",
+            Error::ReportDataMismatch => "ReportDataMismatch",
+            Error::UnsupportedBackend(_) => "UnsupportedBackend",
+            Error::NoChainVerifies(_) => "NoChainVerifies",
+            Error::EmptyResponse => "EmptyResponse",
+            Error::PolicyMismatch { .. } => "PolicyMismatch",
+            Error::Timeout(_) => "Timeout",
+            Error::UnknownReportVersion(_) => "UnknownReportVersion",
+            Error::MeasurementDivergence { .. } => "MeasurementDivergence",
+            Error::PermissionDenied { .. } => "PermissionDenied",
+            Error::ReportVersionTooOld { .. } => "ReportVersionTooOld",
+            Error::NoDcuVerifies(_) => "NoDcuVerifies",
+        }
+    }
+
+    /// Maps the failure to open the device node at `path` to
+    /// [`Error::DeviceUnavailable`] when it is missing or not accessible,
+    /// with a hint naming the kernel `module` providing the node, or the
+    /// group owning it when the node isn't accessible.
+    pub(crate) fn device_unavailable(path: &Path, error: io::Error, module: &str) -> Error {
+        let hint = match error.kind() {
+            io::ErrorKind::NotFound => format!("check that the {module} kernel module is loaded"),
+            io::ErrorKind::PermissionDenied => {
+                let access =
+                    format!("check that the user has read and write access to the {module} device");
+                match std::fs::metadata(path) {
+                    Ok(metadata) => format!(
+                        "{access}, e.g. is a member of the group {} owning it or has CAP_DAC_OVERRIDE",
+                        group_name(metadata.gid())
+                    ),
+                    Err(_) => access,
+                }
+            }
+            _ => return Error::IoError(error),
+        };
+
+        Error::DeviceUnavailable {
+            path: path.into(),
+            hint,
+        }
+    }
+}
+
+/// The name of the group `gid`, or its number if the group database has
+/// none for it.
+fn group_name(gid: u32) -> String {
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        let ret =
+            unsafe { libc::getgrgid_r(gid, &mut group, buf.as_mut_ptr(), buf.len(), &mut result) };
+        // The entry of the group doesn't fit, e.g. for many members.
+        if ret == libc::ERANGE && buf.len() < 1 << 20 {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        break;
+    }
+
+    if result.is_null() {
+        return gid.to_string();
+    }
+    // SAFETY: the name points into `buf`, set by the successful lookup.
+    unsafe { CStr::from_ptr(group.gr_name) }
+        .to_string_lossy()
+        .into_owned()
 }
 
 /// There are a number of error conditions that can occur between this
@@ -214,15 +466,104 @@ impl std::fmt::Display for Error {
             Error::InvalidKey => "The key requested is invalid, not present, or not allowed",
             Error::Custom(msg) => msg,
             Error::Unknown => "Unknown Error",
+            Error::MnonceMismatch { .. } => "The report's mnonce doesn't match the requested one",
+            Error::OpenSsl(stack) => return write!(f, "OpenSSL error: {stack}"),
+            Error::UnknownGpuId(_) => "No DCU in the topology has the requested gpu_id",
+            Error::NonZeroReserved { .. } => "A reserved field is not zero",
+            Error::AlgoCurveMismatch => "The signature algorithm doesn't match the key's curve",
+            Error::UnknownGroup(name) => {
+                return write!(f, "Unknown elliptic curve group: {name}")
+            }
+            Error::InvalidCurveSize => "The elliptic curve group has an invalid coordinate size",
+            Error::UnsupportedVersion(version) => {
+                return write!(f, "Unsupported attestation message version: {version}")
+            }
+            Error::InvalidPublicKey => "A public key coordinate is out of range",
+            Error::RemoteHttp { status, body } => {
+                return write!(f, "The remote verifier answered HTTP {status}: {body}")
+            }
+            Error::MalformedVerdict(reason) => {
+                return write!(f, "Malformed verdict from the remote verifier: {reason}")
+            }
+            Error::ZeroAnonce => "The report's anonce is zero, its fields aren't masked",
+            Error::ReportDataMismatch => "The report's report_data doesn't bind the expected data",
+            Error::UnsupportedBackend(missing) => {
+                return write!(
+                    f,
+                    "The OpenSSL backend lacks {missing}, link an OpenSSL built with SM2 and SM3, \
+                     e.g. 1.1.1 or later without no-sm2 and no-sm3"
+                )
+            }
+            Error::NoChainVerifies(errors) => {
+                write!(f, "None of the {} candidate chains verifies the report", errors.len())?;
+                for (i, e) in errors.iter().enumerate() {
+                    write!(f, "{}chain {i}: {e}", if i == 0 { ": " } else { "; " })?;
+                }
+                return Ok(());
+            }
+            Error::EmptyResponse => "The firmware didn't write a response to the report request",
+            Error::PolicyMismatch { expected, got } => {
+                return write!(
+                    f,
+                    "The report's policy {got:#010x} isn't the expected {expected:#010x}"
+                )
+            }
+            Error::Timeout(waited) => {
+                return write!(f, "The device didn't become available within {waited:?}")
+            }
+            Error::UnknownReportVersion(version) => {
+                return write!(f, "Unknown report version {version}")
+            }
+            Error::MeasurementDivergence { gpu_id } => {
+                return write!(
+                    f,
+                    "The DCU {gpu_id} reports another measurement than the other DCUs"
+                )
+            }
+            Error::PermissionDenied { nodes } => {
+                return write!(
+                    f,
+                    "Permission denied reading the DCU ID of the topology nodes {nodes:?}"
+                )
+            }
+            Error::ReportVersionTooOld { got, min } => {
+                return write!(f, "The report's version {got} is lower than {min}")
+            }
+            Error::NoDcuVerifies(failures) => {
+                write!(f, "None of the {} DCUs produced a report that verifies", failures.len())?;
+                for (i, (gpu_id, e)) in failures.iter().enumerate() {
+                    write!(f, "{}DCU {gpu_id}: {e}", if i == 0 { ": " } else { "; " })?;
+                }
+                return Ok(());
+            }
+            Error::DeviceUnavailable { path, hint } => {
+                return write!(f, "{} is unavailable: {hint}", path.display())
+            }
         };
         write!(f, "{err_description}")
     }
 }
 
+/// Serializes the error as `{ "kind": ..., "message": ... }`, with its
+/// [`Error::kind`] and its description, e.g. for a verifier service to
+/// return the reason of a failed verification.
+#[cfg(feature = "error-serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
 impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Error::IoError(e) => Some(e),
+            Error::OpenSsl(e) => Some(e),
             _ => None,
         }
     }
@@ -231,6 +572,16 @@ impl error::Error for Error {
 impl From<io::Error> for Error {
     #[inline]
     fn from(error: io::Error) -> Error {
+        // Keep the stack of OpenSSL errors passed up as I/O errors.
+        if let Some(stack) = error.get_ref().and_then(|e| e.downcast_ref::<ErrorStack>()) {
+            return Error::OpenSsl(stack.clone());
+        }
+
+        // Unwrap the errors of this crate passed up as I/O errors.
+        if error.get_ref().is_some_and(|e| e.is::<Error>()) {
+            return *error.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
+
         Error::IoError(error)
     }
 }
@@ -238,7 +589,7 @@ impl From<io::Error> for Error {
 impl From<ErrorStack> for Error {
     #[inline]
     fn from(error: ErrorStack) -> Error {
-        Error::IoError(io::Error::from(error))
+        Error::OpenSsl(error)
     }
 }
 
@@ -315,8 +666,8 @@ impl From<Indeterminate<Error>> for io::Error {
     #[inline]
     fn from(indeterminate: Indeterminate<Error>) -> io::Error {
         match indeterminate {
-            Indeterminate::Known(e) => io::Error::new(io::ErrorKind::Other, e),
-            Indeterminate::Unknown => io::Error::new(io::ErrorKind::Other, "unknown CSV error"),
+            Indeterminate::Known(e) => io::Error::other(e),
+            Indeterminate::Unknown => io::Error::other("unknown CSV error"),
         }
     }
 }
@@ -326,3 +677,183 @@ impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
         Error::Custom(e.to_string())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use openssl::ec::EcKey;
+
+    /// One error of each variant.
+    fn every_variant() -> Vec<Error> {
+        vec![
+            Error::IoError(io::ErrorKind::NotFound.into()),
+            Error::InvalidPlatformState,
+            Error::InvalidGuestState,
+            Error::InvalidConfig,
+            Error::InvalidLen,
+            Error::AlreadyOwned,
+            Error::InvalidCertificate,
+            Error::PolicyFailure,
+            Error::Inactive,
+            Error::InvalidAddress,
+            Error::BadSignature,
+            Error::BadMeasurement,
+            Error::AsidOwned,
+            Error::InvalidAsid,
+            Error::WbinvdRequired,
+            Error::DfFlushRequired,
+            Error::InvalidGuest,
+            Error::InvalidCommand,
+            Error::Active,
+            Error::HardwarePlatform,
+            Error::HardwareUnsafe,
+            Error::Unsupported,
+            Error::InvalidParam,
+            Error::ResourceLimit,
+            Error::SecureDataInvalid,
+            Error::InvalidPageSize,
+            Error::InvalidPageState,
+            Error::InvalidMdataEntry,
+            Error::InvalidPageOwner,
+            Error::AEADOFlow,
+            Error::RbModeExited,
+            Error::RMPInitRequired,
+            Error::BadSvn,
+            Error::BadVersion,
+            Error::ShutdownRequired,
+            Error::UpdateFailed,
+            Error::RestoreRequired,
+            Error::RMPInitFailed,
+            Error::InvalidKey,
+            Error::Custom("custom".into()),
+            Error::Unknown,
+            Error::MnonceMismatch {
+                expected: [0; 16],
+                got: [1; 16],
+            },
+            Error::OpenSsl(ErrorStack::get()),
+            Error::UnknownGpuId(1),
+            Error::NonZeroReserved { offset: 1 },
+            Error::AlgoCurveMismatch,
+            Error::UnknownGroup("group".into()),
+            Error::InvalidCurveSize,
+            Error::UnsupportedVersion(3),
+            Error::DeviceUnavailable {
+                path: PathBuf::from("/dev/csv-guest"),
+                hint: "hint".into(),
+            },
+            Error::InvalidPublicKey,
+            Error::RemoteHttp {
+                status: 500,
+                body: "body".into(),
+            },
+            Error::MalformedVerdict("verdict".into()),
+            Error::ZeroAnonce,
+            Error::ReportDataMismatch,
+            Error::UnsupportedBackend("SM2".into()),
+            Error::NoChainVerifies(vec![Error::BadSignature]),
+            Error::EmptyResponse,
+            Error::PolicyMismatch {
+                expected: 0,
+                got: 1,
+            },
+            Error::Timeout(Duration::from_secs(1)),
+            Error::UnknownReportVersion(3),
+            Error::MeasurementDivergence { gpu_id: 1 },
+            Error::PermissionDenied { nodes: vec![0] },
+            Error::ReportVersionTooOld { got: 1, min: 2 },
+            Error::NoDcuVerifies(vec![(1, Error::BadSignature)]),
+        ]
+    }
+
+    #[test]
+    fn kinds() {
+        let errors = every_variant();
+        assert_eq!(Error::BadSignature.kind(), "BadSignature");
+        assert_eq!(Error::UnsupportedVersion(3).kind(), "UnsupportedVersion");
+
+        // Every variant has its own kind.
+        let mut kinds: Vec<_> = errors.iter().map(Error::kind).collect();
+        kinds.sort_unstable();
+        kinds.dedup();
+        assert_eq!(kinds.len(), errors.len());
+    }
+
+    #[cfg(feature = "error-serde")]
+    #[test]
+    fn serialize_every_variant() {
+        for error in every_variant() {
+            let value = serde_json::to_value(&error).unwrap();
+            assert_eq!(
+                value,
+                serde_json::json!({ "kind": error.kind(), "message": error.to_string() })
+            );
+        }
+
+        let error = Error::PolicyMismatch {
+            expected: 1,
+            got: 0,
+        };
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            r#"{"kind":"PolicyMismatch","message":"The report's policy 0x00000000 isn't the expected 0x00000001"}"#
+        );
+    }
+
+    #[test]
+    fn openssl_stack_preserved() {
+        let stack = EcKey::private_key_from_der(b"not a key").unwrap_err();
+        let reason = stack.errors()[0].reason().unwrap().to_string();
+
+        let msg = Error::from(stack.clone()).to_string();
+        assert!(msg.contains(&reason), "{msg}");
+
+        let msg = Error::from(io::Error::from(stack)).to_string();
+        assert!(msg.contains(&reason), "{msg}");
+    }
+
+    #[test]
+    fn unwrap_io_errors() {
+        for error in every_variant() {
+            let kind = error.kind();
+            let unwrapped = Error::from(io::Error::other(error));
+            assert_eq!(unwrapped.kind(), kind);
+        }
+    }
+
+    #[test]
+    fn device_unavailable() {
+        let path = Path::new("/dev/csv-guest");
+
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::PermissionDenied] {
+            match Error::device_unavailable(path, kind.into(), "csv-guest") {
+                Error::DeviceUnavailable { path: p, hint } => {
+                    assert_eq!(p, path);
+                    assert!(hint.contains("csv-guest"), "{hint}");
+                }
+                other => panic!("unexpected error: {other:?}"),
+            }
+        }
+
+        // The hint names the group owning an existing node.
+        let null = Path::new("/dev/null");
+        let group = group_name(std::fs::metadata(null).unwrap().gid());
+        match Error::device_unavailable(null, io::ErrorKind::PermissionDenied.into(), "csv-guest") {
+            Error::DeviceUnavailable { hint, .. } => {
+                assert!(hint.contains(&format!("group {group} owning it")), "{hint}");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+
+        let error =
+            Error::device_unavailable(path, io::ErrorKind::InvalidInput.into(), "csv-guest");
+        assert!(matches!(error, Error::IoError(e) if e.kind() == io::ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn group_names() {
+        assert_eq!(group_name(0), "root");
+        // A group missing from the database is named by its number.
+        assert_eq!(group_name(u32::MAX - 1), (u32::MAX - 1).to_string());
+    }
+}
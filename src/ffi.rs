@@ -0,0 +1,213 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! C bindings for requesting and verifying guest attestation reports.
+//!
+//! The declarations are in `include/csv_rs.h`. Build the shared and static
+//! libraries for C programs, `libcsv_rs.so` and `libcsv_rs.a`, with:
+//!
+//! ```text
+//! cargo build --release --lib --features ffi
+//! ```
+
+use crate::{
+    api::guest::{AttestationReport, CsvGuest, ReportSigner, REPORT_RSP_LEN},
+    certs::kds,
+    error::Error,
+};
+
+use codicon::Decoder;
+
+use libc::{c_int, size_t};
+use std::{panic, slice};
+
+/// The call succeeded.
+pub const CSV_OK: c_int = 0;
+
+/// A required pointer is null.
+pub const CSV_ERR_NULL_POINTER: c_int = -1;
+
+/// The report page can't be decoded.
+pub const CSV_ERR_INVALID_REPORT: c_int = -2;
+
+/// The certificate chain can't be decoded.
+pub const CSV_ERR_INVALID_CHAIN: c_int = -3;
+
+/// The report doesn't carry the requested mnonce.
+pub const CSV_ERR_MNONCE_MISMATCH: c_int = -4;
+
+/// The report, its signer evidence or the certificate chain doesn't verify.
+pub const CSV_ERR_VERIFICATION: c_int = -5;
+
+/// The CSV guest device failed to produce a report.
+pub const CSV_ERR_DEVICE: c_int = -6;
+
+/// The library panicked, the call didn't complete.
+pub const CSV_ERR_PANIC: c_int = -7;
+
+/// Runs `call`, returning [`CSV_ERR_PANIC`] rather than unwinding into the C
+/// caller if it panics.
+fn catch_panic(call: impl FnOnce() -> c_int) -> c_int {
+    // Nothing the call touches is observed again after a panic.
+    panic::catch_unwind(panic::AssertUnwindSafe(call)).unwrap_or(CSV_ERR_PANIC)
+}
+
+/// Verifies a report page as written by the firmware.
+///
+/// `page` holds the `REPORT_RSP_LEN` bytes of the report and its signer
+/// evidence, `chain` the `chain_len` bytes of the HSK and CEK as served by
+/// the KDS and `mnonce` the 16 bytes the report was requested with.
+///
+/// Returns `CSV_OK` if the report verifies up to the builtin HRK, and
+/// `CSV_ERR_PANIC` rather than unwinding if the library panics.
+///
+/// # Safety
+///
+/// The pointers must be valid for reads of the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn csv_verify_report(
+    page: *const u8,
+    chain: *const u8,
+    chain_len: size_t,
+    mnonce: *const u8,
+) -> c_int {
+    catch_panic(|| verify_report(page, chain, chain_len, mnonce))
+}
+
+unsafe fn verify_report(
+    page: *const u8,
+    chain: *const u8,
+    chain_len: size_t,
+    mnonce: *const u8,
+) -> c_int {
+    if page.is_null() || chain.is_null() || mnonce.is_null() {
+        return CSV_ERR_NULL_POINTER;
+    }
+
+    let mut page = slice::from_raw_parts(page, REPORT_RSP_LEN);
+    let chain = slice::from_raw_parts(chain, chain_len);
+    let mnonce = &*(mnonce as *const [u8; 16]);
+
+    let (report, signer) = match AttestationReport::decode(&mut page, ())
+        .and_then(|report| Ok((report, ReportSigner::decode(&mut page, ())?)))
+    {
+        Ok(evidence) => evidence,
+        Err(_) => return CSV_ERR_INVALID_REPORT,
+    };
+
+    let chain = match kds::Chain::from_der_bundle(chain) {
+        Ok(chain) => chain,
+        Err(_) => return CSV_ERR_INVALID_CHAIN,
+    };
+
+    match report.verify_full(&signer, &chain, mnonce) {
+        Ok(_) => CSV_OK,
+        Err(Error::MnonceMismatch { .. }) => CSV_ERR_MNONCE_MISMATCH,
+        Err(_) => CSV_ERR_VERIFICATION,
+    }
+}
+
+/// Requests a report page from the CSV guest device.
+///
+/// `data` holds the 64 bytes to include in the report and `mnonce` the 16
+/// bytes to request it with, either can be null to use zeroes and a random
+/// mnonce respectively. The `REPORT_RSP_LEN` bytes of the page are written
+/// to `out` as the firmware wrote them, ready for `csv_verify_report`.
+/// Without the `rand` feature, a null `mnonce` fails with
+/// `CSV_ERR_NULL_POINTER`. A panic of the library returns `CSV_ERR_PANIC`.
+///
+/// # Safety
+///
+/// `data` and `mnonce` must be null or valid for reads of their lengths,
+/// `out` must be valid for writes of `REPORT_RSP_LEN` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn csv_get_report(data: *const u8, mnonce: *const u8, out: *mut u8) -> c_int {
+    catch_panic(|| get_report(data, mnonce, out))
+}
+
+unsafe fn get_report(data: *const u8, mnonce: *const u8, out: *mut u8) -> c_int {
+    if out.is_null() || (cfg!(not(feature = "rand")) && mnonce.is_null()) {
+        return CSV_ERR_NULL_POINTER;
+    }
+
+    let data = (!data.is_null()).then(|| *(data as *const [u8; 64]));
+    let mnonce = (!mnonce.is_null()).then(|| *(mnonce as *const [u8; 16]));
+
+    let response = CsvGuest::open().and_then(|mut guest| guest.request_report(data, mnonce));
+    let (response, mnonce) = match response {
+        Ok(response) => response,
+        Err(_) => return CSV_ERR_DEVICE,
+    };
+
+    match response.signer.verified(
+        &mnonce,
+        &response.report.body.mnonce,
+        &response.report.anonce,
+    ) {
+        Ok(_) => {}
+        Err(Error::MnonceMismatch { .. }) => return CSV_ERR_MNONCE_MISMATCH,
+        Err(_) => return CSV_ERR_VERIFICATION,
+    }
+
+    let page = response.to_page();
+    std::ptr::copy_nonoverlapping(page.as_ptr(), out, page.len());
+
+    CSV_OK
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::guest::offset;
+    use crate::testutil::{golden_response, GOLDEN_HSK_CEK, GOLDEN_MNONCE};
+
+    #[test]
+    fn verify_report() {
+        let (page, mnonce) = (golden_response().to_page(), GOLDEN_MNONCE);
+        let ret = unsafe {
+            csv_verify_report(
+                page.as_ptr(),
+                GOLDEN_HSK_CEK.as_ptr(),
+                GOLDEN_HSK_CEK.len(),
+                mnonce.as_ptr(),
+            )
+        };
+        assert_eq!(ret, CSV_OK);
+    }
+
+    #[test]
+    fn verify_report_errors() {
+        let (mut page, mnonce) = (golden_response().to_page(), GOLDEN_MNONCE);
+        let verify = |page: &[u8], chain: &[u8], mnonce: &[u8; 16]| unsafe {
+            csv_verify_report(page.as_ptr(), chain.as_ptr(), chain.len(), mnonce.as_ptr())
+        };
+
+        let ret = unsafe {
+            csv_verify_report(
+                std::ptr::null(),
+                GOLDEN_HSK_CEK.as_ptr(),
+                GOLDEN_HSK_CEK.len(),
+                mnonce.as_ptr(),
+            )
+        };
+        assert_eq!(ret, CSV_ERR_NULL_POINTER);
+        assert_eq!(
+            verify(&page, &GOLDEN_HSK_CEK[..100], &mnonce),
+            CSV_ERR_INVALID_CHAIN
+        );
+        assert_eq!(
+            verify(&page, GOLDEN_HSK_CEK, &[0; 16]),
+            CSV_ERR_MNONCE_MISMATCH
+        );
+
+        page[offset::attestation_report::MEASURE] ^= 1;
+        assert_eq!(verify(&page, GOLDEN_HSK_CEK, &mnonce), CSV_ERR_VERIFICATION);
+    }
+
+    #[test]
+    fn panic_error() {
+        assert_eq!(catch_panic(|| CSV_OK), CSV_OK);
+        assert_eq!(catch_panic(|| panic!("in the library")), CSV_ERR_PANIC);
+    }
+}
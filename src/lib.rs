@@ -17,8 +17,23 @@ pub mod error;
 
 pub mod session;
 
+/// C bindings.
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+/// Launch measurement recomputation.
+pub mod measure;
+
+/// Self-test of the cryptography backend.
+pub mod selftest;
+
+/// Golden evidence for tests.
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
+
 mod util;
 
+pub use selftest::selftest;
 pub use util::cached_chain;
 
 use std::io::Write;
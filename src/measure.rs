@@ -0,0 +1,72 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Recomputation of the launch measurement of a CSV guest.
+//!
+//! The firmware keeps a single SM3 context per guest, the launch digest.
+//! Every `LAUNCH_UPDATE_DATA` (and, on CSV2, `LAUNCH_UPDATE_VMSA`) feeds
+//! the plaintext it encrypts to that context, in the order the commands are
+//! issued. The launch digest is finalized by `LAUNCH_MEASURE` and is what
+//! the guest's attestation reports carry as `measure`.
+//!
+//! The expected measurement is thus the SM3 digest of the concatenation of
+//! the launch components, e.g. the firmware image, then the kernel, the
+//! initrd and the command line if the launcher encrypts them in that order.
+
+use crate::error::Error;
+use openssl::{
+    error::ErrorStack,
+    hash::{DigestBytes, Hasher, MessageDigest},
+};
+
+/// Computes the launch digest of a guest launched with `components`, in
+/// the order they were encrypted.
+///
+/// Returns [`Error::UnsupportedBackend`] if the OpenSSL backend lacks SM3.
+///
+/// ```
+/// use csv_rs::measure;
+///
+/// let whole = measure::expected(&[b"OVMF", b"kernel"])?;
+///
+/// // Only the order of the bytes matters, not how they are split.
+/// assert_eq!(whole, measure::expected(&[b"OVMFker", b"nel"])?);
+/// assert_ne!(whole, measure::expected(&[b"kernel", b"OVMF"])?);
+/// # Ok::<(), csv_rs::error::Error>(())
+/// ```
+pub fn expected(components: &[&[u8]]) -> Result<[u8; 32], Error> {
+    crate::crypto::check_backend()?;
+    let digest = launch_digest(components)?;
+    Ok(digest[..].try_into().unwrap())
+}
+
+fn launch_digest(components: &[&[u8]]) -> Result<DigestBytes, ErrorStack> {
+    let mut hasher = Hasher::new(MessageDigest::sm3())?;
+    for component in components {
+        hasher.update(component)?;
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expected_vector() {
+        let components: [&[u8]; 4] = [b"OVMF", b"kernel", b"initrd", b"console=ttyS0"];
+        assert_eq!(
+            hex::encode(expected(&components).unwrap()),
+            "eeb582f0fe157032253bd6cb341d09568a89999007c7cddd2ef35c2af75dd130"
+        );
+    }
+
+    #[test]
+    fn expected_empty() {
+        assert_eq!(
+            hex::encode(expected(&[]).unwrap()),
+            "1ab21d8355cfa17f8e61194831e81a8f22bec8c728fefb747ed035eb5082aa2b"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A self-test of the cryptography backend that doesn't need CSV hardware.
+
+use crate::{
+    api::guest::{xor_with_anonce, AttestationReport, ReportReq, ReportSigner},
+    certs::{kds, Verifiable},
+    crypto::{key::group, sm},
+    error::Error,
+};
+
+use codicon::Decoder;
+
+use std::fmt::{self, Display};
+
+/// An attestation report and its signer evidence recorded from the firmware.
+const EVIDENCE: &[u8] = include_bytes!("../tests/test_data/report.cert");
+
+/// The HSK and CEK endorsing the chip `EVIDENCE` was recorded on.
+const HSK_CEK: &[u8] = include_bytes!("../tests/test_data/hsk_cek.cert");
+
+/// A check of one primitive.
+type CheckFn = fn() -> Result<(), Error>;
+
+/// The outcome of checking one primitive.
+#[derive(Debug)]
+pub struct Check {
+    /// The name of the primitive.
+    pub name: &'static str,
+
+    /// Whether the primitive works, and why not if it doesn't.
+    pub result: Result<(), Error>,
+}
+
+/// The outcome of [`selftest`], one check per primitive.
+#[derive(Debug)]
+pub struct Report {
+    /// The checks, in the order they ran.
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Whether every primitive works.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.result.is_ok())
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.result {
+                Ok(()) => writeln!(f, "{:<8} ok", check.name)?,
+                Err(e) => writeln!(f, "{:<8} FAILED: {:?}", check.name, e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs the cryptographic paths of attestation against known vectors.
+///
+/// This confirms the crypto backend works on the platform before trying
+/// real hardware.
+///
+/// ```
+/// let report = csv_rs::selftest();
+/// assert!(report.passed(), "{}", report);
+/// ```
+pub fn selftest() -> Report {
+    let checks: [(&'static str, CheckFn); 4] =
+        [("sm3", sm3), ("hmac", hmac), ("sm2", sm2), ("chain", chain)];
+
+    Report {
+        checks: checks
+            .into_iter()
+            .map(|(name, check)| Check {
+                name,
+                result: check(),
+            })
+            .collect(),
+    }
+}
+
+/// Hashes a known report request.
+fn sm3() -> Result<(), Error> {
+    let data: [u8; 64] = [
+        103, 198, 105, 115, 81, 255, 74, 236, 41, 205, 186, 171, 242, 251, 227, 70, 124, 194, 84,
+        248, 27, 232, 231, 141, 118, 90, 46, 99, 51, 159, 201, 154, 102, 50, 13, 183, 49, 88, 163,
+        90, 37, 93, 5, 23, 88, 233, 94, 212, 171, 178, 205, 198, 155, 180, 84, 17, 14, 130, 116,
+        65, 33, 61, 220, 135,
+    ];
+    let mnonce: [u8; 16] = [
+        112, 233, 62, 161, 65, 225, 252, 103, 62, 1, 126, 151, 234, 220, 107, 150,
+    ];
+    let hash: [u8; 32] = [
+        19, 76, 8, 98, 33, 246, 247, 155, 28, 21, 245, 185, 118, 74, 162, 128, 82, 15, 160, 233,
+        212, 130, 106, 177, 89, 6, 119, 243, 130, 21, 3, 153,
+    ];
+
+    if ReportReq::new(Some(data), mnonce)?.hash != hash {
+        return Err(Error::BadMeasurement);
+    }
+
+    Ok(())
+}
+
+/// Checks the hmac of the recorded signer evidence and restores it.
+fn hmac() -> Result<(), Error> {
+    let mut evidence = EVIDENCE;
+    let report = AttestationReport::decode(&mut evidence, ())?;
+    let signer = ReportSigner::decode(&mut evidence, ())?;
+
+    let mut mnonce = report.body.mnonce;
+    xor_with_anonce(&mut mnonce, &report.anonce)?;
+
+    // The restored evidence carries the PEK the report is signed with.
+    signer
+        .verified(&mnonce, &report.body.mnonce, &report.anonce)?
+        .pek_certificate()?;
+    Ok(())
+}
+
+/// Signs with a generated key and verifies the signature.
+fn sm2() -> Result<(), Error> {
+    let (key, prv) = sm::SM2::generate(group::Group::SM2_256)?;
+    let msg = b"csv-rs selftest";
+    let sig = sm::SM2::sign(prv, sm::DEFAULT_SM2_ID, msg)?;
+
+    if !sm::SM2::verify(key, &sig, sm::DEFAULT_SM2_ID, msg)? {
+        return Err(Error::BadSignature);
+    }
+
+    Ok(())
+}
+
+/// Verifies the recorded chain up to the builtin HRK.
+fn chain() -> Result<(), Error> {
+    kds::Chain::from_der_bundle(HSK_CEK)?.verify()?;
+    Ok(())
+}
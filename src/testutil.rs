@@ -0,0 +1,264 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Golden evidence for tests that don't need CSV hardware.
+//!
+//! The evidence was recorded from the firmware of the chip `NZA9T14052605`
+//! along with the HSK and CEK the KDS serves for it, so it verifies all the
+//! way up to the builtin HRK:
+//!
+//! ```
+//! use csv_rs::testutil;
+//!
+//! let report = testutil::golden_report();
+//! let outcome = report
+//!     .verify_full(
+//!         &testutil::golden_signer(),
+//!         &testutil::golden_chain(),
+//!         &testutil::GOLDEN_MNONCE,
+//!     )
+//!     .unwrap();
+//! assert_eq!(outcome.sn, testutil::golden_restored_signer().sn);
+//! ```
+//!
+//! Tests varying the evidence generate it instead with [`make_report`],
+//! endorsed by a chain of freshly generated keys.
+
+use crate::{
+    api::guest::{AttestationReport, Body, GuestPolicy, ReportRsp, ReportSigner},
+    certs::{ca, csv, kds, Algorithm, Signer, Usage},
+    crypto::{sig::ecdsa, sm, PrivateKey},
+};
+use openssl::{hash::MessageDigest, pkey, sign};
+
+/// An attestation report and its signer evidence as the firmware wrote
+/// them, without the padding of the page.
+pub const GOLDEN_EVIDENCE: &[u8] = include_bytes!("../tests/test_data/report.cert");
+
+/// The HSK and CEK endorsing the chip [`GOLDEN_EVIDENCE`] was recorded on,
+/// as served by the KDS.
+pub const GOLDEN_HSK_CEK: &[u8] = include_bytes!("../tests/test_data/hsk_cek.cert");
+
+/// The mnonce the golden report was requested with.
+pub const GOLDEN_MNONCE: [u8; 16] = [
+    0x67, 0xc6, 0x69, 0x73, 0x51, 0xff, 0x4a, 0xec, 0x29, 0xcd, 0xba, 0xab, 0xf2, 0xfb, 0xe3, 0x46,
+];
+
+/// The golden response, as [`ReportRsp::from_page`] decodes it.
+pub fn golden_response() -> ReportRsp {
+    ReportRsp::from_page(GOLDEN_EVIDENCE).expect("the golden evidence decodes")
+}
+
+/// The golden attestation report.
+pub fn golden_report() -> AttestationReport {
+    golden_response().report
+}
+
+/// The golden signer evidence as the firmware wrote it, i.e. not yet
+/// restored by [`ReportSigner::verified`].
+pub fn golden_signer() -> ReportSigner {
+    golden_response().signer
+}
+
+/// The golden signer evidence, restored after checking its hmac.
+pub fn golden_restored_signer() -> ReportSigner {
+    let response = golden_response();
+    response
+        .signer
+        .verified(
+            &GOLDEN_MNONCE,
+            &response.report.body.mnonce,
+            &response.report.anonce,
+        )
+        .expect("the golden evidence was requested with the golden mnonce")
+}
+
+/// The chain endorsing the golden report, rooted at the builtin HRK.
+pub fn golden_chain() -> kds::Chain {
+    kds::Chain::from_der_bundle(GOLDEN_HSK_CEK).expect("the golden chain decodes")
+}
+
+/// The content of a report generated by [`make_report`], unmasked.
+#[derive(Debug, Clone)]
+pub struct ReportParams {
+    /// The report data the report is requested with.
+    pub report_data: [u8; 64],
+    /// The mnonce the report is requested with.
+    pub mnonce: [u8; 16],
+    /// The anonce the report is masked with.
+    pub anonce: u32,
+    /// The launch digest of the guest.
+    pub measure: [u8; 32],
+    /// The bits of the guest policy.
+    pub policy: u32,
+    /// The ID of the VM.
+    pub vm_id: [u8; 16],
+    /// The version of the VM.
+    pub vm_version: [u8; 16],
+    /// The digest of the guest owner's public key.
+    pub user_pubkey_digest: [u8; 32],
+    /// The serial number of the chip, NUL padded.
+    pub sn: [u8; 64],
+}
+
+/// The mnonce reports are generated with by default.
+pub const TEST_MNONCE: [u8; 16] = *b"csv-rs-test-mnon";
+
+/// The serial number of the chip reports are generated for by default.
+pub const TEST_SN: &str = "TESTCHIP0000001";
+
+impl Default for ReportParams {
+    fn default() -> Self {
+        let mut sn = [0u8; 64];
+        sn[..TEST_SN.len()].copy_from_slice(TEST_SN.as_bytes());
+
+        Self {
+            report_data: [0u8; 64],
+            mnonce: TEST_MNONCE,
+            anonce: 0x1234_5678,
+            measure: [0u8; 32],
+            policy: 0,
+            vm_id: [0u8; 16],
+            vm_version: [0u8; 16],
+            user_pubkey_digest: [0u8; 32],
+            sn,
+        }
+    }
+}
+
+/// Generates a response as the firmware would write it for `params`, along
+/// with the chain endorsing it and the mnonce it was requested with.
+///
+/// The keys of the HRK, HSK, CEK and PEK are generated for every call, so
+/// the chain is rooted at a test HRK rather than the builtin one. Everything
+/// else is taken from `params`:
+///
+/// ```
+/// use csv_rs::testutil::{make_report, ReportParams};
+///
+/// let (response, chain, mnonce) = make_report(ReportParams::default());
+/// let outcome = response
+///     .report
+///     .verify_full(&response.signer, &chain, &mnonce)
+///     .unwrap();
+/// assert!(outcome.sn.starts_with(b"TESTCHIP"));
+/// ```
+///
+/// See [`TestChip`] for several reports of the same chip.
+pub fn make_report(params: ReportParams) -> (ReportRsp, kds::Chain, [u8; 16]) {
+    let chip = TestChip::generate();
+    let mnonce = params.mnonce;
+    (chip.report(params), chip.chain(), mnonce)
+}
+
+/// A chip of generated keys, endorsed by a chain rooted at a test HRK.
+pub struct TestChip {
+    chain: kds::Chain,
+    pek: csv::Certificate,
+    pek_key: PrivateKey<Usage>,
+}
+
+impl TestChip {
+    /// Generates the keys of the HRK, HSK, CEK and PEK of a new chip.
+    pub fn generate() -> Self {
+        Self::generate_keys().expect("generating a test chain can't fail")
+    }
+
+    fn generate_keys() -> std::io::Result<Self> {
+        let (mut hrk, hrk_key) = ca::Certificate::generate(Usage::HRK, None)?;
+        let subject = hrk.subject();
+        hrk_key.sign(&mut hrk, subject)?;
+
+        let (mut hsk, hsk_key) = ca::Certificate::generate(Usage::HSK, None)?;
+        hrk_key.sign(&mut hsk, hrk.subject())?;
+
+        let (mut cek, cek_key) = csv::Certificate::generate(Usage::CEK, None)?;
+        hsk_key.sign(&mut cek, hsk.subject())?;
+
+        let (mut pek, pek_key) = csv::Certificate::generate(Usage::PEK, None)?;
+        cek_key.sign(&mut pek, cek.subject())?;
+
+        Ok(Self {
+            chain: kds::Chain {
+                ca: ca::Chain { hsk, hrk },
+                cek,
+            },
+            pek,
+            pek_key,
+        })
+    }
+
+    /// The chain endorsing the chip, from its test HRK down to its CEK.
+    pub fn chain(&self) -> kds::Chain {
+        kds::Chain {
+            ca: ca::Chain {
+                hsk: self.chain.ca.hsk,
+                hrk: self.chain.ca.hrk,
+            },
+            cek: self.chain.cek,
+        }
+    }
+
+    /// Generates a response of the chip as the firmware would write it for
+    /// `params`, signed by the chip's PEK.
+    pub fn report(&self, params: ReportParams) -> ReportRsp {
+        let anonce = params.anonce;
+        let mask = |data: &mut [u8]| {
+            for (byte, mask) in data.iter_mut().zip(anonce.to_le_bytes().iter().cycle()) {
+                *byte ^= mask;
+            }
+        };
+
+        let mut body = Body {
+            user_pubkey_digest: params.user_pubkey_digest,
+            vm_id: params.vm_id,
+            vm_version: params.vm_version,
+            report_data: params.report_data,
+            mnonce: params.mnonce,
+            measure: params.measure,
+            policy: GuestPolicy::from(params.policy ^ anonce),
+        };
+        mask(&mut body.user_pubkey_digest);
+        mask(&mut body.vm_id);
+        mask(&mut body.vm_version);
+        mask(&mut body.report_data);
+        mask(&mut body.mnonce);
+        mask(&mut body.measure);
+
+        let sig = sm::SM2::sign(
+            self.pek_key.key,
+            &self.pek.sm2_id(),
+            &body.to_signed_bytes(),
+        )
+        .expect("signing with a generated key can't fail");
+
+        let mut response = ReportRsp::default();
+        response.report = AttestationReport {
+            body,
+            sig_usage: u32::from(Usage::PEK) ^ anonce,
+            sig_algo: u32::from(Algorithm::SM2_SA) ^ anonce,
+            anonce,
+            sig: ecdsa::Signature::try_from(&sig[..]).expect("SM2 signatures are DER encoded"),
+        };
+
+        let signer = &mut response.signer;
+        signer.pek_cert.copy_from_slice(&self.pek.to_der());
+        mask(&mut signer.pek_cert);
+        signer.sn = params.sn;
+        mask(&mut signer.sn);
+
+        let key = pkey::PKey::hmac(&params.mnonce).expect("the mnonce is a valid hmac key");
+        let mut mac = sign::Signer::new(MessageDigest::sm3(), &key).expect("SM3 is available");
+        mac.update(&signer.pek_cert).unwrap();
+        mac.update(&signer.sn).unwrap();
+        mac.update(signer.reserved()).unwrap();
+        signer.mac = mac
+            .sign_to_vec()
+            .unwrap()
+            .try_into()
+            .expect("SM3 digests are 32 bytes");
+
+        response
+    }
+}
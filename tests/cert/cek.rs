@@ -13,3 +13,41 @@ fn verify() {
     let cek = csv::Certificate::decode(&mut &CEK[..], ()).unwrap();
     (&hsk, &cek).verify().unwrap();
 }
+
+#[test]
+fn fingerprint() {
+    let cek = csv::Certificate::decode(&mut &CEK[..], ()).unwrap();
+
+    assert_eq!(
+        cek.fingerprint_hex().unwrap(),
+        "d030d78da9c204cff3e2b00c292881d005cdc25fa85be64427ecc83ce726e04c"
+    );
+    assert_eq!(
+        cek.body.data.pubkey.fingerprint_hex().unwrap(),
+        "b434b312ae70aac33dd245eb4337557801f5de458c9a0e4328401cc308748189"
+    );
+    assert_eq!(
+        cek.body.data.pubkey.fingerprint().unwrap(),
+        cek.body.data.pubkey.key.fingerprint().unwrap()
+    );
+}
+
+#[test]
+fn to_der() {
+    let cek = csv::Certificate::decode(&mut &CEK[..], ()).unwrap();
+
+    let der = cek.to_der();
+    assert_eq!(der, CEK);
+    let again = csv::Certificate::decode(&mut &der[..], ()).unwrap();
+    assert_eq!(again.to_der(), der);
+}
+
+#[test]
+fn sm2_id() {
+    let mut cek = csv::Certificate::decode(&mut &CEK[..], ()).unwrap();
+    assert_eq!(cek.sm2_id(), cek.subject().as_bytes());
+
+    // A corrupt size is clamped to the ID rather than read past it.
+    cek.body.data.uid_size = u16::MAX;
+    assert_eq!(cek.sm2_id().len(), cek.body.data.user_id.len());
+}
@@ -11,3 +11,31 @@ fn verify() {
     let hrk = ca::Certificate::decode(&mut &HRK[..], ()).unwrap();
     (&hrk, &hrk).verify().unwrap();
 }
+
+#[test]
+fn fingerprint() {
+    let hrk = ca::Certificate::decode(&mut &HRK[..], ()).unwrap();
+
+    assert_eq!(
+        hrk.fingerprint_hex().unwrap(),
+        "f5a46663059fdb4cdd06d097ed21782142923bb3430b3b938f23d54292094e3a"
+    );
+    assert_eq!(
+        hrk.body.pubkey.fingerprint_hex().unwrap(),
+        "d837cad274662505c8d5b6f032c39976c28454b66db81fc0efbbea719941c111"
+    );
+    assert_eq!(
+        hex::encode(hrk.fingerprint().unwrap()),
+        hrk.fingerprint_hex().unwrap()
+    );
+}
+
+#[test]
+fn to_der() {
+    let hrk = ca::Certificate::decode(&mut &HRK[..], ()).unwrap();
+
+    let der = hrk.to_der();
+    assert_eq!(der, HRK);
+    let again = ca::Certificate::decode(&mut &der[..], ()).unwrap();
+    assert_eq!(again.to_der(), der);
+}
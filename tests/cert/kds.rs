@@ -0,0 +1,112 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use super::*;
+use csv_rs::certs::{kds, Verifiable};
+
+#[test]
+fn der_bundle_round_trip() {
+    let chain = kds::Chain::from_der_bundle(HSK_CEK).unwrap();
+    let bundle = chain.to_der_bundle();
+    assert_eq!(HSK_CEK, &bundle[..]);
+
+    let chain = kds::Chain::from_der_bundle(&bundle).unwrap();
+    chain.verify().unwrap();
+}
+
+#[test]
+fn der_bundle_wrong_order() {
+    let mut bundle = CEK.to_vec();
+    bundle.extend_from_slice(HSK);
+    assert!(kds::Chain::from_der_bundle(&bundle).is_err());
+}
+
+#[test]
+fn roles() {
+    use csv_rs::certs::{IntermediateCertificate, LeafCertificate, RootCertificate, Usage};
+
+    let chain = kds::Chain::from_der_bundle(HSK_CEK).unwrap();
+    let root: &RootCertificate = chain.root();
+    let intermediate: &IntermediateCertificate = chain.intermediate();
+    let leaf: &LeafCertificate = chain.leaf();
+
+    assert_eq!(Usage::try_from(root).unwrap(), Usage::HRK);
+    assert_eq!(Usage::try_from(intermediate).unwrap(), Usage::HSK);
+    assert_eq!(Usage::try_from(leaf).unwrap(), Usage::CEK);
+}
+
+#[test]
+fn describe() {
+    let mut chain = kds::Chain::from_der_bundle(HSK_CEK).unwrap();
+
+    let description = chain.describe();
+    assert!(description.starts_with("HRK: subject HYGON-SSD-HRK, usage HYGON-SSD-HRK"));
+    assert!(description.contains("HSK: subject HYGON-SSD-HSK"));
+    assert!(description.contains("CEK: subject HYGON-SSD-CEK"));
+    assert!(!description.contains("FAILED"), "{}", description);
+
+    // The HSK isn't self-signed, so a chain rooted at it breaks at the top.
+    chain.ca.hrk = chain.ca.hsk;
+    let description = chain.describe();
+    let first = description.lines().nth(1).unwrap();
+    assert!(first.contains("signed by HRK: FAILED"), "{}", description);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn verify_parallel() {
+    use codicon::Decoder;
+    use csv_rs::certs::{ca, csv};
+
+    let chain = kds::Chain::from_der_bundle(HSK_CEK).unwrap();
+    chain.verify_parallel().unwrap();
+
+    // The HSK is not self-signed, so it can't act as the root.
+    let hsk = ca::Certificate::decode(&mut &HSK[..], ()).unwrap();
+    let broken = kds::Chain {
+        ca: ca::Chain { hsk, hrk: hsk },
+        cek: csv::Certificate::decode(&mut &CEK[..], ()).unwrap(),
+    };
+    let err = broken.verify_parallel().unwrap_err();
+    assert!(err.to_string().starts_with("HRK isn't signed"), "{err}");
+
+    // A CEK whose key isn't the one the HSK signed.
+    let mut cek = CEK.to_vec();
+    cek[100] ^= 1;
+    let broken = kds::Chain {
+        ca: chain.ca,
+        cek: csv::Certificate::decode(&mut &cek[..], ()).unwrap(),
+    };
+    let err = broken.verify_parallel().unwrap_err();
+    assert!(err.to_string().starts_with("CEK isn't signed"), "{err}");
+}
+
+/// Compares the sequential verification of a chain with
+/// [`kds::Chain::verify_parallel`], run with `cargo test --release
+/// --features parallel --test certs -- --ignored --nocapture verify_parallel_latency`.
+#[cfg(feature = "parallel")]
+#[ignore]
+#[test]
+fn verify_parallel_latency() {
+    use std::time::Instant;
+
+    const CHAINS: u32 = 1_000;
+
+    let chain = kds::Chain::from_der_bundle(HSK_CEK).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..CHAINS {
+        chain.verify().unwrap();
+    }
+    let sequential = start.elapsed() / CHAINS;
+
+    let start = Instant::now();
+    for _ in 0..CHAINS {
+        chain.verify_parallel().unwrap();
+    }
+    let parallel = start.elapsed() / CHAINS;
+
+    println!("verify: {sequential:?}, verify_parallel: {parallel:?} per chain");
+}
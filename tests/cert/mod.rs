@@ -7,3 +7,4 @@ use super::*;
 mod cek;
 mod hrk;
 mod hsk;
+mod kds;
@@ -3,3 +3,4 @@ mod cert;
 /// The public HRK certificate.
 pub const HSK: &[u8] = include_bytes!("test_data/hsk.cert");
 pub const CEK: &[u8] = include_bytes!("test_data/cek.cert");
+pub const HSK_CEK: &[u8] = include_bytes!("test_data/hsk_cek.cert");
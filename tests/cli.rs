@@ -0,0 +1,72 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+const EVIDENCE: &[u8] = include_bytes!("test_data/report.cert");
+const HSK_CEK: &[u8] = include_bytes!("test_data/hsk_cek.cert");
+
+/// The mnonce the recorded report was requested with.
+const MNONCE: &str = "67c6697351ff4aec29cdbaabf2fbe346";
+
+fn recorded() -> Vec<u8> {
+    [EVIDENCE, HSK_CEK].concat()
+}
+
+fn csv_attest(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_csv-attest"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The tool may reject the input and exit before reading all of it.
+    if let Err(e) = child.stdin.take().unwrap().write_all(stdin) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe, "{e}");
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn verify_stdin() {
+    for args in [&["verify", "-"][..], &["verify", "-", MNONCE]] {
+        let output = csv_attest(args, &recorded());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success(), "{stdout}");
+        assert!(stdout.contains("verified: chip NZA9T14052605"), "{stdout}");
+    }
+}
+
+#[test]
+fn verify_file() {
+    let path = std::env::temp_dir().join(format!("csv-attest-{}.bin", std::process::id()));
+    std::fs::write(&path, recorded()).unwrap();
+
+    let output = csv_attest(&["verify", path.to_str().unwrap(), MNONCE], &[]);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+}
+
+#[test]
+fn verify_rejects() {
+    // Truncated evidence.
+    let output = csv_attest(&["verify", "-"], EVIDENCE);
+    assert_eq!(output.status.code(), Some(1));
+
+    // Evidence checked against another mnonce.
+    let output = csv_attest(&["verify", "-", &"00".repeat(16)], &recorded());
+    assert_eq!(output.status.code(), Some(1));
+
+    // A malformed mnonce.
+    let output = csv_attest(&["verify", "-", "00"], &recorded());
+    assert_eq!(output.status.code(), Some(2));
+}
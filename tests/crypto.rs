@@ -3,7 +3,14 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use csv_rs::{crypto::key::group, crypto::sm};
+use csv_rs::{crypto, crypto::key::group, crypto::sm};
+
+#[test]
+fn check_backend() {
+    crypto::check_backend().unwrap();
+    // The outcome is cached.
+    crypto::check_backend().unwrap();
+}
 
 #[test]
 fn sm2_gen_sig_verify() {
@@ -12,5 +19,174 @@ fn sm2_gen_sig_verify() {
     let data: Vec<u8> = vec![1, 2, 3, 4];
     let sig = sm::SM2::sign(prv, &id, &data).unwrap();
     let res = sm::SM2::verify(key, &sig, &id, &data).unwrap();
-    assert_eq!(true, res);
+    assert!(res);
+}
+
+#[test]
+fn sm2_verify_default_id() {
+    let (key, prv) = sm::SM2::generate(group::Group::SM2_256).unwrap();
+    let data: Vec<u8> = vec![1, 2, 3, 4];
+    let sig = sm::SM2::sign(prv, sm::DEFAULT_SM2_ID, &data).unwrap();
+    assert!(sm::SM2::verify(key, &sig, sm::DEFAULT_SM2_ID, &data).unwrap());
+    assert!(!sm::SM2::verify(key, &sig, b"test", &data).unwrap());
+}
+
+#[test]
+fn pubkey_verify_data() {
+    use csv_rs::{
+        certs::{csv, Usage},
+        error::Error,
+    };
+
+    let (cert, prv) = csv::Certificate::generate(Usage::PEK, None).unwrap();
+    let data = b"attested tls key";
+    let sig = sm::SM2::sign(prv.key, &sm::sm2_id(), data).unwrap();
+
+    let pubkey = cert.body.data.pubkey;
+    pubkey.verify_data(data, &sig).unwrap();
+    assert!(matches!(
+        pubkey.verify_data(b"tampered", &sig),
+        Err(Error::BadSignature)
+    ));
+    assert!(matches!(
+        pubkey.verify_data(data, b"not a signature"),
+        Err(Error::BadSignature)
+    ));
+}
+
+#[test]
+fn signature_r_s_split() {
+    use csv_rs::crypto::sig::{Signature, SIGNATURE_LEN};
+
+    let (_, prv) = sm::SM2::generate(group::Group::SM2_256).unwrap();
+    let der = sm::SM2::sign(prv, sm::DEFAULT_SM2_ID, b"split").unwrap();
+
+    let sig = Signature::from_der(&der, group::Group::SM2_256).unwrap();
+    assert_eq!(sig.r().len(), 32);
+    assert_eq!(sig.s().len(), 32);
+    assert_eq!(sig.to_der().unwrap(), der);
+
+    let raw = sig.to_bytes();
+    assert_eq!(raw.len(), SIGNATURE_LEN);
+    assert_eq!(&raw[..32], sig.r());
+    assert_eq!(&raw[72..104], sig.s());
+    assert!(raw[32..72].iter().chain(&raw[104..]).all(|&b| b == 0));
+    assert_eq!(Signature::new(&raw, group::Group::SM2_256).unwrap(), sig);
+
+    // Bytes past the group's size don't fit the SM2 curve.
+    let mut oversized = raw;
+    oversized[40] = 1;
+    assert!(Signature::new(&oversized, group::Group::SM2_256).is_err());
+}
+
+#[test]
+fn sm2_verify_id_lengths() {
+    let long_id = [b'x'; 300];
+    let data = b"signed under various user IDs";
+
+    for id in [&b""[..], sm::DEFAULT_SM2_ID, &long_id[..]] {
+        let (key, prv) = sm::SM2::generate(group::Group::SM2_256).unwrap();
+        let sig = sm::SM2::sign(prv, id, data).unwrap();
+        assert!(sm::SM2::verify(key, &sig, id, data).unwrap());
+        assert!(!sm::SM2::verify(key, &sig, b"another id", data).unwrap());
+    }
+}
+
+#[test]
+fn group_name_round_trip() {
+    use csv_rs::error::Error;
+
+    let name = group::Group::SM2_256.to_string();
+    assert_eq!(name, "sm2p256");
+    assert_eq!(name.parse::<group::Group>().unwrap(), group::Group::SM2_256);
+
+    match "p256".parse::<group::Group>() {
+        Err(Error::UnknownGroup(name)) => assert_eq!(name, "p256"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn zero_size_group_rejected() {
+    use csv_rs::error::Error;
+    use openssl::{ec, pkey};
+
+    let (mut key, prv) = sm::SM2::generate(group::Group::SM2_256).unwrap();
+    let data = b"zero size";
+    let sig = sm::SM2::sign(prv, sm::DEFAULT_SM2_ID, data).unwrap();
+
+    // A corrupt group value, which has no coordinate size.
+    key.g = bincode::deserialize(&0u32.to_le_bytes()).unwrap();
+
+    let err = ec::EcKey::<pkey::Public>::try_from(&key).unwrap_err();
+    assert!(matches!(Error::from(err), Error::InvalidCurveSize));
+
+    let err = sm::SM2::verify(key, &sig, sm::DEFAULT_SM2_ID, data).unwrap_err();
+    assert!(matches!(Error::from(err), Error::InvalidCurveSize));
+}
+
+#[test]
+fn out_of_range_coordinate_rejected() {
+    use csv_rs::error::Error;
+    use openssl::{bn, ec, pkey};
+
+    let (mut key, _) = sm::SM2::generate(group::Group::SM2_256).unwrap();
+    ec::EcKey::<pkey::Public>::try_from(&key).unwrap();
+
+    // The prime of the SM2 field, the first value out of its range.
+    let p = bn::BigNum::from_hex_str(
+        "FFFFFFFEFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF00000000FFFFFFFFFFFFFFFF",
+    )
+    .unwrap();
+    let mut coordinate = [0u8; 72];
+    coordinate[..32].copy_from_slice(&p.to_vec_padded(32).unwrap());
+    coordinate[..32].reverse();
+
+    let mut x = key;
+    x.x = coordinate;
+    let err = ec::EcKey::<pkey::Public>::try_from(&x).unwrap_err();
+    assert!(matches!(Error::from(err), Error::InvalidPublicKey));
+
+    let mut y = key;
+    y.y = coordinate;
+    let err = ec::EcKey::<pkey::Public>::try_from(&y).unwrap_err();
+    assert!(matches!(Error::from(err), Error::InvalidPublicKey));
+
+    key.x = [0xff; 72];
+    assert!(ec::EcKey::<pkey::Public>::try_from(&key).is_err());
+}
+
+#[test]
+fn coordinate_endianness() {
+    use csv_rs::{crypto::key::ecc::PubKey, testutil};
+    use openssl::{ec, pkey};
+
+    // The firmware's keys are little endian: the golden PEK, decoded by
+    // default, verifies the golden report.
+    let signer = testutil::golden_restored_signer();
+    let pek = signer.pek_certificate().unwrap();
+    let key = pek.body.data.pubkey.key;
+    let (x, y) = key.be_coordinates().unwrap();
+    assert_eq!(PubKey::from_be(key.g, &x, &y).unwrap(), key);
+    assert_eq!(
+        PubKey::from_le(key.g, &key.x[..32], &key.y[..32]).unwrap(),
+        key
+    );
+    assert!(testutil::golden_report()
+        .verify_with_key_id(&pek.body.data.pubkey, &pek.sm2_id())
+        .is_ok());
+
+    // The same bytes, taken as big endian, are another point, if one at
+    // all.
+    let swapped = PubKey::from_be(key.g, &key.x[..32], &key.y[..32]).unwrap();
+    assert_ne!(swapped, key);
+    let decoded = ec::EcKey::<pkey::Public>::try_from(&key).unwrap();
+    if let Ok(other) = ec::EcKey::<pkey::Public>::try_from(&swapped) {
+        assert_ne!(
+            other.public_key_to_der().unwrap(),
+            decoded.public_key_to_der().unwrap()
+        );
+    }
+
+    assert!(PubKey::from_le(key.g, &[0; 33], &[0; 32]).is_err());
 }
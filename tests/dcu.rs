@@ -3,7 +3,14 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use csv_rs::api::dcu::*;
+use csv_rs::{
+    api::{
+        dcu::*,
+        guest::{ReportReq, ReportRsp, ReportSource},
+    },
+    error::Error,
+    testutil,
+};
 //use crate::certs::csv;
 use rand::{thread_rng, Rng};
 
@@ -23,6 +30,121 @@ fn get_report() {
     }
 }
 
+#[cfg_attr(not(has_dev_dcu), ignore)]
+#[test_log::test]
+fn get_reports_with_meta() {
+    let mut userdata = [0u8; 64];
+    thread_rng().fill(&mut userdata);
+    let mut dcu_device = DcuDevice::new().unwrap();
+
+    let gpu_ids: Vec<_> = Topology::default()
+        .dcu_nodes()
+        .unwrap()
+        .into_iter()
+        .map(|(_, gpu_id)| gpu_id)
+        .collect();
+
+    for (meta, report) in dcu_device.get_reports_with_meta(userdata).unwrap() {
+        assert_eq!(meta.version, 1);
+        assert!(gpu_ids.contains(&meta.gpu_id));
+        assert!(meta.requested_at <= std::time::SystemTime::now());
+        assert_eq!(report.body.user_data, userdata);
+    }
+}
+
+#[cfg_attr(not(has_dev_dcu), ignore)]
+#[test_log::test]
+fn get_report_concurrently() {
+    use std::sync::{Arc, Mutex};
+
+    let dcu_device = Arc::new(Mutex::new(DcuDevice::new().unwrap()));
+
+    let workers: Vec<_> = (0..8)
+        .map(|_| {
+            let dcu_device = dcu_device.clone();
+            std::thread::spawn(move || {
+                let mut userdata = [0u8; 64];
+                thread_rng().fill(&mut userdata);
+
+                for _ in 0..4 {
+                    let reports = dcu_device.lock().unwrap().get_report(userdata).unwrap();
+
+                    // A response interleaved with another thread's would
+                    // carry the other thread's userdata.
+                    for report in &reports {
+                        assert_eq!(userdata, report.body.user_data);
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+/// A stand-in for a device, which like the DCU ioctl writes the request to
+/// a buffer it then reads the report from.
+#[derive(Default)]
+struct Chip {
+    pending: Option<([u8; 64], [u8; 16])>,
+}
+
+impl ReportSource for Chip {
+    fn request(&mut self, data: [u8; 64], mnonce: Option<[u8; 16]>) -> Result<ReportRsp, Error> {
+        self.pending = Some((data, mnonce.unwrap()));
+        // Leaves another thread the time to overwrite the request, were the
+        // requests not serialized.
+        std::thread::yield_now();
+        let (mut report_data, mut mnonce) = self.pending.take().unwrap();
+
+        // The golden response answering the request, masked as the firmware
+        // does. Its signature no longer verifies.
+        let mut response = testutil::golden_response();
+        let anonce = response.report.anonce().to_le_bytes();
+        for data in [&mut report_data[..], &mut mnonce[..]] {
+            for (byte, mask) in data.iter_mut().zip(anonce.iter().cycle()) {
+                *byte ^= mask;
+            }
+        }
+        response.report.body.report_data = report_data;
+        response.report.body.mnonce = mnonce;
+        Ok(response)
+    }
+}
+
+#[test_log::test]
+fn share_source_concurrently() {
+    use std::sync::{Arc, Mutex};
+
+    let source = Arc::new(Mutex::new(Chip::default()));
+
+    let workers: Vec<_> = (0..8u8)
+        .map(|i| {
+            let source = source.clone();
+            std::thread::spawn(move || {
+                for j in 0..4u8 {
+                    let request = ReportReq::new(Some([i; 64]), [j; 16]).unwrap();
+                    let response = source
+                        .lock()
+                        .unwrap()
+                        .request([i; 64], Some([j; 16]))
+                        .unwrap();
+
+                    // A request interleaved with another thread's would be
+                    // answered with the other thread's data.
+                    response.report.verify_request_binding(&request).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
 #[cfg_attr(not(has_dev_dcu), ignore)]
 #[cfg(feature = "network")]
 #[tokio::test]
@@ -36,3 +158,140 @@ async fn get_report_and_verify() {
 
     verify_reports(&reports, &userdata).await.unwrap();
 }
+
+#[cfg_attr(not(has_dev_dcu), ignore)]
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn get_verified_report() {
+    use csv_rs::certs::{csv, kds};
+
+    let mut rng = thread_rng();
+    let mut userdata = [0u8; 64];
+    rng.fill(&mut userdata);
+    let mut dcu_device = DcuDevice::new().unwrap();
+
+    let reports = dcu_device.get_report(userdata).unwrap();
+    let cert_data = csv::get_certificate_data(&reports[0].body.chip_id)
+        .await
+        .unwrap();
+    let chain = kds::Chain::from_der_bundle(&cert_data).unwrap();
+
+    let (_, report) = dcu_device.get_verified_report(userdata, &chain).unwrap();
+    assert_eq!(report.body.chip_id, reports[0].body.chip_id);
+}
+
+fn topology_fixture(layout: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/test_data/topology")
+        .join(layout)
+        .join("nodes")
+}
+
+#[test]
+fn topology_gpu_id_layout() {
+    let topology = Topology::new(topology_fixture("gpu_id"));
+    assert_eq!(topology.dcu_nodes().unwrap(), [(1, 55872), (2, 6510)]);
+}
+
+#[test]
+fn topology_properties_layout() {
+    let topology = Topology::new(topology_fixture("properties"));
+    assert_eq!(topology.dcu_nodes().unwrap(), [(1, 27558)]);
+}
+
+#[test]
+fn topology_fallback() {
+    let topology = Topology::new("/nonexistent/nodes").with_fallback(topology_fixture("gpu_id"));
+    assert_eq!(topology.dcu_nodes().unwrap(), [(1, 55872), (2, 6510)]);
+}
+
+#[test]
+fn topology_not_found() {
+    let topology = Topology::new("/nonexistent/nodes").with_fallback("/nonexistent/too");
+    let err = topology.dcu_nodes().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert!(err.to_string().contains("/nonexistent/nodes"));
+    assert!(err.to_string().contains("/nonexistent/too"));
+}
+
+#[test]
+fn topology_node_of() {
+    use csv_rs::error::Error;
+
+    let topology = Topology::new(topology_fixture("gpu_id"));
+    assert_eq!(topology.node_of(6510).unwrap(), 2);
+    assert!(matches!(topology.node_of(0), Err(Error::UnknownGpuId(0))));
+    assert!(matches!(
+        topology.node_of(1234),
+        Err(Error::UnknownGpuId(1234))
+    ));
+}
+
+#[test]
+fn get_reports_parallel_per_device_errors() {
+    // /dev/null takes the place of the device, so every ioctl fails on its
+    // own, without failing the other DCUs' requests.
+    let dcu_device = DcuDevice::open_mkfd("/dev/null")
+        .unwrap()
+        .with_topology(Topology::new(topology_fixture("gpu_id")));
+
+    let outcomes = dcu_device.get_reports_parallel([0u8; 64]).unwrap();
+    let gpu_ids: Vec<_> = outcomes.iter().map(|(gpu_id, _)| *gpu_id).collect();
+    assert_eq!(gpu_ids, [55872, 6510]);
+    assert!(outcomes.iter().all(|(_, outcome)| outcome.is_err()));
+}
+
+#[cfg_attr(not(has_dev_dcu), ignore)]
+#[test_log::test]
+fn get_reports_parallel_against_serial() {
+    use std::time::Instant;
+
+    let mut userdata = [0u8; 64];
+    thread_rng().fill(&mut userdata);
+    let mut dcu_device = DcuDevice::new().unwrap();
+
+    let start = Instant::now();
+    let serial = dcu_device.get_report(userdata).unwrap();
+    let serial_time = start.elapsed();
+
+    let start = Instant::now();
+    let parallel = dcu_device.get_reports_parallel(userdata).unwrap();
+    let parallel_time = start.elapsed();
+
+    log::info!(
+        "{} reports: serial {:?}, parallel {:?}",
+        serial.len(),
+        serial_time,
+        parallel_time
+    );
+
+    let parallel: Vec<_> = parallel
+        .into_iter()
+        .map(|(_, outcome)| outcome.unwrap())
+        .collect();
+    assert_eq!(parallel.len(), serial.len());
+    for (parallel, serial) in parallel.iter().zip(&serial) {
+        assert_eq!(parallel.body.chip_id, serial.body.chip_id);
+        assert_eq!(parallel.body.user_data, userdata);
+    }
+}
+
+#[test]
+fn verify_report_with_golden_chain() {
+    use csv_rs::{error::Error, testutil};
+
+    let chain = testutil::golden_chain();
+    let userdata = [0x5au8; 64];
+    let mut report = AttestationReport::default();
+
+    // The userdata is checked first.
+    assert!(matches!(
+        verify_report_with_chain(&report, &userdata, &chain),
+        Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::InvalidData
+    ));
+
+    // The chain verifies, but it doesn't endorse an unsigned report.
+    report.body.user_data = userdata;
+    assert!(verify_report_with_chain(&report, &userdata, &chain).is_err());
+    assert!(verify_report(&report, &userdata, testutil::GOLDEN_HSK_CEK).is_err());
+}
@@ -0,0 +1,40 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Builds of the library with other features than those of the tests.
+//!
+//! Each build compiles the crate anew, so these are left to CI:
+//! `cargo test --test features -- --ignored`.
+
+use std::process::Command;
+
+/// Checks the library with `features` only, on top of no default feature.
+fn check_lib(features: &str) {
+    let target_dir = std::env::temp_dir().join("csv-rs-features");
+    let output = Command::new(env!("CARGO"))
+        .args([
+            "check",
+            "--lib",
+            "--no-default-features",
+            "--features",
+            features,
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("CARGO_TARGET_DIR", target_dir)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+#[ignore]
+fn without_rand() {
+    check_lib("ffi,server");
+}
@@ -0,0 +1,90 @@
+// Copyright (C) Hygon Info Technologies Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+#![cfg(feature = "server")]
+
+use csv_rs::{
+    api::guest::{request_report_from_socket, ReportRsp, ReportSource, Server},
+    error::Error,
+    testutil,
+};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Serves the golden response, recording the requests.
+struct Golden(std::sync::mpsc::Sender<([u8; 64], Option<[u8; 16]>)>);
+
+impl ReportSource for Golden {
+    fn request(&mut self, data: [u8; 64], mnonce: Option<[u8; 16]>) -> Result<ReportRsp, Error> {
+        self.0.send((data, mnonce)).unwrap();
+        if data[0] == 0xff {
+            return Err(Error::InvalidLen);
+        }
+        Ok(testutil::golden_response())
+    }
+}
+
+fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("csv-rs-{}-{name}.sock", std::process::id()))
+}
+
+#[test]
+fn serve_reports() {
+    let path = socket_path("serve");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let server = Server::bind(&path, Golden(tx)).unwrap();
+    std::thread::spawn(move || server.serve());
+
+    let response =
+        request_report_from_socket(&path, [1u8; 64], Some(testutil::GOLDEN_MNONCE)).unwrap();
+    assert_eq!(
+        rx.recv().unwrap(),
+        ([1u8; 64], Some(testutil::GOLDEN_MNONCE))
+    );
+    assert_eq!(response.to_page(), testutil::golden_response().to_page());
+
+    let chain = testutil::golden_chain();
+    response
+        .report
+        .verify_full(&response.signer, &chain, &testutil::GOLDEN_MNONCE)
+        .unwrap();
+
+    request_report_from_socket(&path, [2u8; 64], None).unwrap();
+    assert_eq!(rx.recv().unwrap(), ([2u8; 64], None));
+
+    // The failure of the source is relayed to the client.
+    match request_report_from_socket(&path, [0xffu8; 64], None) {
+        Err(Error::Custom(msg)) => assert!(msg.contains(&Error::InvalidLen.to_string()), "{msg}"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn drop_stalled_client() {
+    let path = socket_path("stalled");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let server = Server::bind(&path, Golden(tx))
+        .unwrap()
+        .with_timeout(Duration::from_millis(100));
+    std::thread::spawn(move || server.serve());
+
+    // A client sending half a request holds the server until it times out.
+    let mut stalled = UnixStream::connect(&path).unwrap();
+    stalled.write_all(&[3u8; 32]).unwrap();
+
+    request_report_from_socket(&path, [4u8; 64], None).unwrap();
+    assert_eq!(rx.recv().unwrap(), ([4u8; 64], None));
+
+    // The stalled client was dropped.
+    let mut rest = Vec::new();
+    stalled.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+}